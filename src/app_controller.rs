@@ -1,43 +1,90 @@
 //! UI-side application controller.
 //!
 //! This module is responsible for wiring Slint callbacks to the database layer and
-//! for translating DB models into Slint models.
+//! for translating DB models into Slint models. Roster and presence access goes through
+//! `db_operations::Repository`, so the same logic works with any storage backend.
 //!
 //! Design notes:
 //! - We keep small in-memory caches (`selection_groups`, `all_persons_for_selection`) to
 //!   compute filtered lists quickly when the user changes the selected group.
 //! - The heavy “refresh everything from DB” work is encapsulated in `refresh::make_refresh_groups()`.
 
-use std::{cell::RefCell, rc::Rc};
+use std::{cell::RefCell, path::PathBuf, rc::Rc};
 use std::collections::{HashMap, HashSet};
 
 use rusqlite::Connection;
 use slint::ComponentHandle;
 
+use crate::db_operations::{BackupReason, Repository, SqliteRepository};
+use crate::settings::Settings;
 use crate::{GroupData, MainWindow, PersonData};
 
+mod alerts;
+mod backups;
+mod events;
 mod filter;
+mod format;
 mod handlers;
+mod headcount;
+mod integrity;
+mod journal;
+mod leave_pass;
+mod logs;
+mod messages;
+mod person_form;
+mod privacy;
+mod profiles;
 mod refresh;
+mod roll_call;
+mod rota;
+mod search;
+mod time_outside;
+
+pub use profiles::{choose_camp, OpenedCamp};
+
+/// Wires `app` to the camp database `conn` opened from `db_path` (with `passphrase` if it
+/// is encrypted) and backs it up.
+pub fn install(
+    app: &MainWindow,
+    conn: Rc<RefCell<Connection>>,
+    settings: Settings,
+    db_path: PathBuf,
+    passphrase: Option<String>,
+) {
+    let repo: Rc<dyn Repository> = Rc::new(SqliteRepository::new(conn.clone()));
+    let settings = Rc::new(RefCell::new(settings));
+    let backups = backups::Backups::new(conn.clone(), passphrase, settings.clone());
 
-pub fn install(app: &MainWindow, conn: Rc<RefCell<Connection>>) {
     // Data caches for filtering persons by selected group
     let selection_groups: Rc<RefCell<Vec<GroupData>>> = Rc::new(RefCell::new(Vec::new()));
     let all_persons_for_selection: Rc<RefCell<Vec<PersonData>>> = Rc::new(RefCell::new(Vec::new()));
+    // Participants of the active event, the persons listed on the main screen
+    let event_persons: Rc<RefCell<Vec<PersonData>>> = Rc::new(RefCell::new(Vec::new()));
 
     // Main screen selection state
     let checked_person_ids: Rc<RefCell<HashSet<i32>>> = Rc::new(RefCell::new(HashSet::new()));
     let out_person_ids: Rc<RefCell<HashSet<i32>>> = Rc::new(RefCell::new(HashSet::new()));
     let group_members_by_id: Rc<RefCell<HashMap<i32, Vec<i32>>>> = Rc::new(RefCell::new(HashMap::new()));
+    // Set while the checked persons are exactly a group picked on the main screen,
+    // so GET_IN/GET_OUT can be logged as one group event.
+    let selected_group_id: Rc<RefCell<Option<i32>>> = Rc::new(RefCell::new(None));
+    // Ids matching the main screen search field, `None` while it is empty
+    let matching_person_ids: Rc<RefCell<Option<HashSet<i32>>>> = Rc::new(RefCell::new(None));
+
+    // Logs screen filter and loaded pages
+    let log_view: Rc<RefCell<logs::LogView>> = Rc::new(RefCell::new(logs::LogView::default()));
 
     let refresh_groups = refresh::make_refresh_groups(
         app.as_weak(),
-        conn.clone(),
+        repo.clone(),
         selection_groups.clone(),
         all_persons_for_selection.clone(),
+        event_persons.clone(),
         checked_person_ids.clone(),
         out_person_ids.clone(),
         group_members_by_id.clone(),
+        matching_person_ids.clone(),
+        log_view.clone(),
     );
 
     refresh_groups();
@@ -45,40 +92,102 @@ pub fn install(app: &MainWindow, conn: Rc<RefCell<Connection>>) {
     handlers::wire_group_selection_changed(
         app,
         selection_groups.clone(),
-        all_persons_for_selection.clone(),
+        all_persons_for_selection,
     );
 
     handlers::wire_main_person_toggled(
         app,
-        all_persons_for_selection.clone(),
+        event_persons.clone(),
         checked_person_ids.clone(),
         out_person_ids.clone(),
+        selected_group_id.clone(),
+        matching_person_ids.clone(),
     );
     handlers::wire_main_group_clicked(
         app,
-        all_persons_for_selection.clone(),
+        event_persons.clone(),
         checked_person_ids.clone(),
         out_person_ids.clone(),
         group_members_by_id,
+        selected_group_id.clone(),
+        matching_person_ids.clone(),
     );
     handlers::wire_main_get_in(
         app,
-        conn.clone(),
-        all_persons_for_selection.clone(),
+        repo.clone(),
+        event_persons.clone(),
         checked_person_ids.clone(),
         out_person_ids.clone(),
+        selected_group_id.clone(),
+        matching_person_ids.clone(),
         refresh_groups.clone(),
     );
     handlers::wire_main_get_out(
         app,
-        conn.clone(),
-        all_persons_for_selection.clone(),
+        repo.clone(),
+        event_persons.clone(),
         checked_person_ids.clone(),
-        out_person_ids,
+        out_person_ids.clone(),
+        selected_group_id.clone(),
+        matching_person_ids.clone(),
         refresh_groups.clone(),
     );
+    search::wire_person_search(
+        app,
+        repo.clone(),
+        event_persons,
+        checked_person_ids.clone(),
+        out_person_ids,
+        selected_group_id.clone(),
+        matching_person_ids,
+    );
+
+    handlers::wire_add_person_request(app, repo.clone(), refresh_groups.clone());
+    handlers::wire_add_group_request(app, repo.clone(), refresh_groups.clone());
+    handlers::wire_add_person_to_group_request(app, repo.clone(), refresh_groups.clone());
+    handlers::wire_archive_requests(app, repo.clone(), backups.clone(), refresh_groups.clone());
+    person_form::wire_person_profile_requests(app, repo.clone(), refresh_groups.clone());
+    privacy::wire_privacy_requests(app, repo.clone(), backups.clone(), refresh_groups.clone());
+    logs::wire_log_requests(app, repo.clone(), log_view.clone());
+    time_outside::wire_time_outside_requests(app, repo.clone());
+    alerts::wire_alert_requests(app, repo.clone());
+    roll_call::wire_roll_call_requests(app, repo.clone(), refresh_groups.clone());
+    rota::wire_rota_requests(app, repo.clone());
+    journal::wire_journal_requests(app, repo.clone());
+
+    // Another camp or event was opened: nothing selected in the old one carries over.
+    let refresh_on_switch = refresh_groups.clone();
+    let on_switched = move || {
+        checked_person_ids.borrow_mut().clear();
+        selected_group_id.borrow_mut().take();
+        *log_view.borrow_mut() = logs::LogView::default();
+        refresh_on_switch();
+    };
+    events::wire_event_requests(app, repo, refresh_groups.clone(), on_switched.clone());
+
+    // A camp was opened, at startup or by switching: back it up, then check it.
+    let checked: Rc<RefCell<integrity::Checked>> = Rc::default();
+    let on_camp_opened = {
+        let app_weak = app.as_weak();
+        let (conn, backups, checked) = (conn.clone(), backups.clone(), checked.clone());
+        move || {
+            if let Some(app) = app_weak.upgrade() {
+                backups.take(&app, BackupReason::Opened);
+                integrity::check_camp(&app, &conn.borrow(), &checked);
+            }
+        }
+    };
+    profiles::wire_camp_requests(
+        app,
+        conn.clone(),
+        settings,
+        Rc::new(RefCell::new(db_path)),
+        backups.clone(),
+        on_switched.clone(),
+        on_camp_opened.clone(),
+    );
+    backups::wire_backup_requests(app, backups.clone(), on_switched);
+    integrity::wire_integrity_requests(app, conn, backups, checked, refresh_groups);
 
-    handlers::wire_add_person_request(app, conn.clone(), refresh_groups.clone());
-    handlers::wire_add_group_request(app, conn.clone(), refresh_groups.clone());
-    handlers::wire_add_person_to_group_request(app, conn, refresh_groups);
+    on_camp_opened();
 }
@@ -0,0 +1,151 @@
+use std::{rc::Rc, time::Duration};
+
+use chrono::{NaiveTime, Utc};
+use slint::{ComponentHandle, ModelRc, SharedString, Timer, TimerMode, VecModel};
+
+use crate::db_operations::{AlertLimits, Methodology, Overdue, OverdueReason, Repository};
+use crate::{AlertLimitsData, MainWindow, OverdueData};
+
+use super::format::{format_time, full_name};
+use super::messages::{report_error, report_message};
+use super::time_outside::format_duration;
+
+/// How often the OUT set is checked again; presence changes check it right away (refresh).
+const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Overdue banner and the Settings limits; called on every refresh.
+pub(super) fn set_alert_models(app: &MainWindow, repo: &dyn Repository) {
+    match repo.alert_limits() {
+        Ok(limits) => app.set_alert_limits(limits_to_data(&limits)),
+        Err(e) => report_error(app, "Error loading the alert limits", &e),
+    }
+    check_overdue(app, repo);
+}
+
+/// Settings screen ALERTS and the periodic check behind the overdue banner.
+pub(super) fn wire_alert_requests(app: &MainWindow, repo: Rc<dyn Repository>) {
+    let timer = Timer::default();
+    let app_weak = app.as_weak();
+    let timer_repo = repo.clone();
+    timer.start(TimerMode::Repeated, CHECK_INTERVAL, move || {
+        if let Some(app) = app_weak.upgrade() {
+            check_overdue(&app, timer_repo.as_ref());
+        }
+    });
+
+    let app_weak = app.as_weak();
+    app.on_save_alert_limits(move |data| {
+        let Some(app) = app_weak.upgrade() else {
+            return false;
+        };
+        let Some(limits) = limits_from_data(&app, &data) else {
+            return false;
+        };
+        if let Err(e) = repo.set_alert_limits(&limits) {
+            report_error(&app, "Error saving the alert limits", &e);
+            return false;
+        }
+        app.set_alert_limits(limits_to_data(&limits));
+        check_overdue(&app, repo.as_ref());
+        // Next check a full interval after this one.
+        timer.restart();
+        true
+    });
+}
+
+// Refills the banner for the active event; empty without one.
+fn check_overdue(app: &MainWindow, repo: &dyn Repository) {
+    let now = Utc::now();
+    let overdue = repo
+        .active_event()
+        .and_then(|event_id| match event_id {
+            Some(event_id) => repo.overdue_persons(event_id, now),
+            None => Ok(Vec::new()),
+        })
+        .unwrap_or_else(|e| {
+            report_error(app, "Error checking for overdue persons", &e);
+            Vec::new()
+        });
+    let items: Vec<OverdueData> = overdue.iter().map(|o| overdue_to_data(o, now)).collect();
+    app.set_overdue(ModelRc::new(VecModel::from(items)));
+}
+
+fn overdue_to_data(o: &Overdue, now: chrono::DateTime<Utc>) -> OverdueData {
+    let reasons: Vec<String> = o
+        .reasons
+        .iter()
+        .map(|r| match r {
+            OverdueReason::MaxOut { limit } => format!("limit {}", format_duration(*limit)),
+            OverdueReason::Curfew { curfew } => format!("curfew {}", format_time(*curfew)),
+        })
+        .collect();
+    OverdueData {
+        person_id: o.person.id,
+        name: SharedString::from(full_name(&o.person)),
+        away: SharedString::from(format_duration(now - o.left)),
+        left: SharedString::from(format_time(o.left)),
+        reasons: SharedString::from(reasons.join(", ")),
+    }
+}
+
+fn limits_to_data(limits: &AlertLimits) -> AlertLimitsData {
+    let minutes = |m| {
+        limits
+            .max_out_minutes(m)
+            .map(|minutes| SharedString::from(minutes.to_string()))
+            .unwrap_or_default()
+    };
+    AlertLimitsData {
+        cub: minutes(Methodology::Cub),
+        scout: minutes(Methodology::Scout),
+        venturescout: minutes(Methodology::VentureScout),
+        rover: minutes(Methodology::Rover),
+        curfew: limits
+            .curfew
+            .map(|c| SharedString::from(c.format("%H:%M").to_string()))
+            .unwrap_or_default(),
+        curfew_end: limits
+            .curfew_end
+            .map(|c| SharedString::from(c.format("%H:%M").to_string()))
+            .unwrap_or_default(),
+    }
+}
+
+// `None` (and an error banner) when a field can't be parsed. Empty fields are no limit.
+fn limits_from_data(app: &MainWindow, data: &AlertLimitsData) -> Option<AlertLimits> {
+    let mut limits = AlertLimits::default();
+    let fields = [
+        (Methodology::Cub, &data.cub),
+        (Methodology::Scout, &data.scout),
+        (Methodology::VentureScout, &data.venturescout),
+        (Methodology::Rover, &data.rover),
+    ];
+    for (methodology, text) in fields {
+        let text = text.trim();
+        if text.is_empty() {
+            continue;
+        }
+        match text.parse::<u32>() {
+            Ok(minutes) if minutes > 0 => limits.set_max_out_minutes(methodology, Some(minutes)),
+            _ => {
+                report_message(app, &format!("Invalid time limit '{text}', enter whole minutes."));
+                return None;
+            }
+        }
+    }
+
+    for (text, time) in [(&data.curfew, &mut limits.curfew), (&data.curfew_end, &mut limits.curfew_end)] {
+        let text = text.trim();
+        if text.is_empty() {
+            continue;
+        }
+        match NaiveTime::parse_from_str(text, "%H:%M") {
+            Ok(parsed) => *time = Some(parsed),
+            Err(_) => {
+                report_message(app, &format!("Invalid curfew time '{text}', use HH:MM."));
+                return None;
+            }
+        }
+    }
+    Some(limits)
+}
@@ -0,0 +1,208 @@
+use std::{
+    cell::RefCell,
+    path::{Path, PathBuf},
+    rc::Rc,
+    time::Duration,
+};
+
+use rusqlite::Connection;
+use slint::{ComponentHandle, ModelRc, SharedString, Timer, TimerMode, VecModel};
+
+use crate::db_operations::{self, BackupInfo, BackupReason, DbResult};
+use crate::settings::Settings;
+use crate::{BackupData, MainWindow};
+
+use super::messages::{report_error, report_message};
+
+/// Takes backups of the open camp. Cloned into every callback that needs one; profile
+/// switching keeps the passphrase current (`set_passphrase`), since encrypted backups need it.
+#[derive(Clone)]
+pub(super) struct Backups {
+    conn: Rc<RefCell<Connection>>,
+    passphrase: Rc<RefCell<Option<String>>>,
+    settings: Rc<RefCell<Settings>>,
+}
+
+impl Backups {
+    pub(super) fn new(conn: Rc<RefCell<Connection>>, passphrase: Option<String>, settings: Rc<RefCell<Settings>>) -> Self {
+        Backups {
+            conn,
+            passphrase: Rc::new(RefCell::new(passphrase)),
+            settings,
+        }
+    }
+
+    /// Passphrase of the open camp; `None` for plaintext camps.
+    pub(super) fn set_passphrase(&self, passphrase: Option<String>) {
+        *self.passphrase.borrow_mut() = passphrase;
+    }
+
+    /// Backs the camp up and drops the oldest backups beyond `Settings::backups_kept` per
+    /// reason.
+    /// `false` (and an error banner) if the backup failed; callers about to delete data
+    /// should stop there.
+    pub(super) fn take(&self, app: &MainWindow, reason: BackupReason) -> bool {
+        let kept = self.settings.borrow().backups_kept.max(1);
+        let conn = self.conn.borrow();
+        let result = db_operations::create_backup(&conn, self.passphrase.borrow().as_deref(), reason)
+            .and_then(|path| db_operations::rotate_backups(&db_path(&conn), kept).map(|_| path));
+        match result {
+            Ok(path) => {
+                eprintln!("Camp backed up to {}", path.display());
+                true
+            }
+            Err(e) => {
+                report_error(app, "Error backing up the camp", &e);
+                false
+            }
+        }
+    }
+
+    /// Deletes every backup, the pre-migration ones (`*.bak`) included, after an anonymization:
+    /// they all still hold the removed data.
+    pub(super) fn delete_all(&self, app: &MainWindow) {
+        let path = db_path(&self.conn.borrow());
+        let deleted = db_operations::delete_backups(&path)
+            .and_then(|backups| db_operations::delete_migration_backups(&path).map(|bak| backups + bak));
+        match deleted {
+            Ok(0) => {}
+            Ok(deleted) => eprintln!("Deleted {deleted} backups"),
+            Err(e) => report_error(app, "Error deleting the backups", &e),
+        }
+    }
+
+    fn list(&self) -> DbResult<Vec<BackupInfo>> {
+        db_operations::list_backups(&db_path(&self.conn.borrow()))
+    }
+
+    fn restore(&self, backup: &Path) -> DbResult<PathBuf> {
+        let passphrase = self.passphrase.borrow().clone();
+        db_operations::restore_backup(&mut self.conn.borrow_mut(), backup, passphrase.as_deref())
+    }
+}
+
+fn db_path(conn: &Connection) -> PathBuf {
+    PathBuf::from(conn.path().unwrap_or_default())
+}
+
+/// Settings screen backups: the periodic backup, its settings, BACK_UP_NOW and the
+/// restore list. Restoring resets the UI like switching camps (`on_switched`).
+pub(super) fn wire_backup_requests(app: &MainWindow, backups: Backups, on_switched: impl Fn() + Clone + 'static) {
+    let settings = backups.settings.clone();
+    set_backup_settings(app, &settings.borrow());
+
+    let timer = Rc::new(Timer::default());
+    start_timer(&timer, app, &backups, settings.borrow().backup_interval_minutes);
+
+    let app_weak = app.as_weak();
+    let settings_backups = backups.clone();
+    app.on_save_backup_settings(move |interval, kept| {
+        let Some(app) = app_weak.upgrade() else {
+            return false;
+        };
+        let (Ok(interval), Ok(kept)) = (interval.trim().parse::<u32>(), kept.trim().parse::<usize>()) else {
+            report_message(&app, "Enter whole numbers for the backup interval and the number of backups.");
+            return false;
+        };
+        if kept == 0 {
+            report_message(&app, "At least one backup must be kept.");
+            return false;
+        }
+
+        let mut settings = settings.borrow_mut();
+        settings.backup_interval_minutes = interval;
+        settings.backups_kept = kept;
+        if let Err(e) = settings.save() {
+            eprintln!("Couldn't save settings: {e}");
+        }
+        set_backup_settings(&app, &settings);
+        start_timer(&timer, &app, &settings_backups, interval);
+        true
+    });
+
+    let app_weak = app.as_weak();
+    let manual_backups = backups.clone();
+    app.on_backup_now(move || {
+        let Some(app) = app_weak.upgrade() else {
+            return;
+        };
+        if manual_backups.take(&app, BackupReason::Manual) {
+            set_backup_models(&app, &manual_backups);
+        }
+    });
+
+    let app_weak = app.as_weak();
+    let list_backups = backups.clone();
+    app.on_backups_opened(move || {
+        if let Some(app) = app_weak.upgrade() {
+            set_backup_models(&app, &list_backups);
+        }
+    });
+
+    let app_weak = app.as_weak();
+    app.on_restore_backup(move |path| {
+        let Some(app) = app_weak.upgrade() else {
+            return false;
+        };
+        let restored = backups.restore(Path::new(path.as_str()));
+        if let Err(e) = &restored {
+            report_error(&app, "Error restoring the backup", e);
+        }
+        // The list gains the `BeforeRestore` backup either way once it was taken.
+        set_backup_models(&app, &backups);
+        if restored.is_err() {
+            return false;
+        }
+        on_switched();
+        true
+    });
+}
+
+// (Re)starts the periodic backup; `minutes == 0` turns it off.
+fn start_timer(timer: &Timer, app: &MainWindow, backups: &Backups, minutes: u32) {
+    timer.stop();
+    if minutes == 0 {
+        return;
+    }
+    let app_weak = app.as_weak();
+    let backups = backups.clone();
+    timer.start(TimerMode::Repeated, Duration::from_secs(u64::from(minutes) * 60), move || {
+        if let Some(app) = app_weak.upgrade() {
+            backups.take(&app, BackupReason::Scheduled);
+        }
+    });
+}
+
+fn set_backup_settings(app: &MainWindow, settings: &Settings) {
+    app.set_backup_interval(SharedString::from(settings.backup_interval_minutes.to_string()));
+    app.set_backups_kept(SharedString::from(settings.backups_kept.to_string()));
+}
+
+fn set_backup_models(app: &MainWindow, backups: &Backups) {
+    let folder = db_operations::backup_dir(&db_path(&backups.conn.borrow()));
+    app.set_backup_folder(SharedString::from(folder.display().to_string()));
+    let list = backups.list().unwrap_or_else(|e| {
+        report_error(app, "Error listing backups", &e);
+        Vec::new()
+    });
+    let items: Vec<BackupData> = list.iter().map(backup_to_data).collect();
+    app.set_backups(ModelRc::new(VecModel::from(items)));
+}
+
+fn backup_to_data(b: &BackupInfo) -> BackupData {
+    BackupData {
+        path: SharedString::from(b.path.display().to_string()),
+        taken_at: SharedString::from(b.taken_at.format("%Y-%m-%d %H:%M:%S").to_string()),
+        reason: match b.reason {
+            BackupReason::Opened => 0,
+            BackupReason::Scheduled => 1,
+            BackupReason::BeforeDelete => 2,
+            BackupReason::BeforeRestore => 3,
+            BackupReason::Manual => 4,
+            BackupReason::BeforeRepair => 5,
+        },
+        persons: b.persons as i32,
+        logs: b.logs as i32,
+        size_kb: (b.size / 1024) as i32,
+    }
+}
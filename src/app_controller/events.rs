@@ -0,0 +1,212 @@
+use std::rc::Rc;
+
+use chrono::NaiveDate;
+use slint::{ComponentHandle, ModelRc, SharedString, VecModel};
+
+use crate::{EventData, MainWindow, PersonData};
+
+use crate::db_operations::{self, Repository};
+
+use super::messages::{report_error, report_message};
+
+/// Fills the event ComboBox and the events modal. `roster` is every active person,
+/// `participant_ids` those taking part in the active event.
+pub(super) fn set_event_models(
+    app: &MainWindow,
+    events: &[db_operations::Event],
+    active_event_id: Option<i32>,
+    roster: &[PersonData],
+    participant_ids: &[i32],
+) {
+    let events_data: Vec<EventData> = events.iter().map(event_to_event_data).collect();
+    let active_index = events.iter().position(|e| Some(e.id) == active_event_id);
+
+    app.set_event_names(ModelRc::new(VecModel::from(
+        events_data.iter().map(|e| e.name.clone()).collect::<Vec<_>>(),
+    )));
+    app.set_active_event(active_index.map(|i| events_data[i].clone()).unwrap_or_default());
+    app.set_active_event_index(active_index.map_or(-1, |i| i as i32));
+    app.set_events(ModelRc::new(VecModel::from(events_data)));
+
+    let checked: Vec<bool> = roster.iter().map(|p| participant_ids.contains(&p.id)).collect();
+    app.set_event_roster(ModelRc::new(VecModel::from(roster.to_vec())));
+    app.set_event_roster_checked(ModelRc::new(VecModel::from(checked)));
+}
+
+/// Event switcher and the events modal.
+///
+/// Choosing or creating an event makes it the active one; `on_switched` then resets
+/// the main screen selection and reloads, like opening another camp.
+pub(super) fn wire_event_requests(
+    app: &MainWindow,
+    repo: Rc<dyn Repository>,
+    refresh_groups: impl Fn() + Clone + 'static,
+    on_switched: impl Fn() + Clone + 'static,
+) {
+    let app_weak = app.as_weak();
+    let (select_repo, select_switched) = (repo.clone(), on_switched.clone());
+    app.on_event_selected(move |event_id| {
+        let Some(app) = app_weak.upgrade() else {
+            return;
+        };
+        if let Err(e) = select_repo.set_active_event(event_id) {
+            report_error(&app, "Error switching event", &e);
+            return;
+        }
+        select_switched();
+    });
+
+    let app_weak = app.as_weak();
+    let (save_repo, save_refresh) = (repo.clone(), refresh_groups.clone());
+    app.on_save_event(move |id, name, starts_on, ends_on, location, retention_days| {
+        let Some(app) = app_weak.upgrade() else {
+            return false;
+        };
+
+        let name = name.trim();
+        if name.is_empty() {
+            report_message(&app, "The event needs a name.");
+            return false;
+        }
+        let (Some(starts_on), Some(ends_on)) = (parse_date(&app, &starts_on), parse_date(&app, &ends_on)) else {
+            return false;
+        };
+        if let (Some(start), Some(end)) = (starts_on, ends_on)
+            && end < start
+        {
+            report_message(&app, "The event can't end before it starts.");
+            return false;
+        }
+        let Some(retention_days) = parse_days(&app, &retention_days) else {
+            return false;
+        };
+
+        let event = db_operations::Event {
+            id,
+            name: name.to_string(),
+            starts_on,
+            ends_on,
+            location: location.trim().to_string(),
+            retention_days,
+        };
+
+        if id != 0 {
+            if let Err(e) = save_repo.update_event(&event) {
+                report_error(&app, "Error updating event", &e);
+                return false;
+            }
+            save_refresh();
+            return true;
+        }
+
+        let created = save_repo
+            .add_event(&event)
+            .and_then(|new_id| save_repo.set_active_event(new_id));
+        if let Err(e) = created {
+            report_error(&app, "Error creating event", &e);
+            return false;
+        }
+        on_switched();
+        true
+    });
+
+    let app_weak = app.as_weak();
+    let (toggle_repo, toggle_refresh) = (repo.clone(), refresh_groups.clone());
+    app.on_event_participant_toggled(move |person_id, takes_part| {
+        let Some(app) = app_weak.upgrade() else {
+            return;
+        };
+        let Some(event_id) = active_event_or_report(&app, toggle_repo.as_ref()) else {
+            return;
+        };
+
+        let result = if takes_part {
+            toggle_repo.add_participant(event_id, person_id)
+        } else {
+            toggle_repo.remove_participant(event_id, person_id)
+        };
+        if let Err(e) = result {
+            report_error(&app, "Error updating participants", &e);
+        }
+        toggle_refresh();
+    });
+
+    let app_weak = app.as_weak();
+    app.on_event_add_everyone(move || {
+        let Some(app) = app_weak.upgrade() else {
+            return;
+        };
+        let Some(event_id) = active_event_or_report(&app, repo.as_ref()) else {
+            return;
+        };
+
+        let added = repo.persons().and_then(|persons| {
+            let taking_part: Vec<i32> = repo.participants(event_id)?.iter().map(|p| p.person.id).collect();
+            persons
+                .iter()
+                .filter(|p| !taking_part.contains(&p.id))
+                .try_for_each(|p| repo.add_participant(event_id, p.id))
+        });
+        if let Err(e) = added {
+            report_error(&app, "Error updating participants", &e);
+        }
+        refresh_groups();
+    });
+}
+
+/// The active event, or `None` with a message asking the user to pick one.
+pub(super) fn active_event_or_report(app: &MainWindow, repo: &dyn Repository) -> Option<i32> {
+    match repo.active_event() {
+        Ok(Some(event_id)) => Some(event_id),
+        Ok(None) => {
+            report_message(app, "No event is selected. Pick or create one first.");
+            None
+        }
+        Err(e) => {
+            report_error(app, "Error loading the active event", &e);
+            None
+        }
+    }
+}
+
+// "YYYY-MM-DD"; empty means not set. `None` (and an error banner) when it can't be parsed.
+pub(super) fn parse_date(app: &MainWindow, text: &str) -> Option<Option<NaiveDate>> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Some(None);
+    }
+    match NaiveDate::parse_from_str(text, "%Y-%m-%d") {
+        Ok(date) => Some(Some(date)),
+        Err(_) => {
+            report_message(app, &format!("Invalid date '{text}', use YYYY-MM-DD."));
+            None
+        }
+    }
+}
+
+// Whole days; empty means not set. `None` (and an error banner) when it isn't a number.
+pub(super) fn parse_days(app: &MainWindow, text: &str) -> Option<Option<u32>> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Some(None);
+    }
+    match text.parse() {
+        Ok(days) => Some(Some(days)),
+        Err(_) => {
+            report_message(app, &format!("Invalid number of days '{text}'."));
+            None
+        }
+    }
+}
+
+fn event_to_event_data(e: &db_operations::Event) -> EventData {
+    let date = |d: Option<NaiveDate>| d.map(|d| SharedString::from(d.format("%Y-%m-%d").to_string())).unwrap_or_default();
+    EventData {
+        id: e.id,
+        name: SharedString::from(e.name.as_str()),
+        starts_on: date(e.starts_on),
+        ends_on: date(e.ends_on),
+        location: SharedString::from(e.location.as_str()),
+        retention_days: e.retention_days.map(|d| SharedString::from(d.to_string())).unwrap_or_default(),
+    }
+}
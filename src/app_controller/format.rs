@@ -0,0 +1,35 @@
+//! Text formatting shared by the screens and the printable exports (rota, morning report,
+//! person data). Times are shown in local time.
+
+use chrono::{DateTime, Local, Utc};
+
+use crate::db_operations::Person;
+
+/// "Surname Name", the order of every list.
+pub(super) fn full_name(person: &Person) -> String {
+    format!("{} {}", person.surname, person.name)
+}
+
+/// "YYYY-MM-DD HH:MM".
+pub(super) fn format_local(time: DateTime<Utc>) -> String {
+    time.with_timezone(&Local).format("%Y-%m-%d %H:%M").to_string()
+}
+
+/// "HH:MM".
+pub(super) fn format_time(time: DateTime<Utc>) -> String {
+    time.with_timezone(&Local).format("%H:%M").to_string()
+}
+
+/// "HH:MM" today, else "YYYY-MM-DD HH:MM".
+pub(super) fn format_recent(time: DateTime<Utc>) -> String {
+    if time.with_timezone(&Local).date_naive() == Local::now().date_naive() {
+        format_time(time)
+    } else {
+        format_local(time)
+    }
+}
+
+/// Escapes text for HTML element content and double-quoted attributes.
+pub(super) fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
@@ -5,14 +5,17 @@ use std::{
 
 use std::collections::{HashMap, HashSet};
 
-use rusqlite::Connection;
-use slint::{ComponentHandle, ModelRc, VecModel};
+use slint::{ComponentHandle, ModelRc, SharedString, VecModel};
 
 use crate::{MainWindow, PersonData};
 
-use crate::db_operations;
+use crate::db_operations::{self, BackupReason, Repository};
 
+use super::backups::Backups;
+use super::events::active_event_or_report;
 use super::filter::filter_persons_excluding_group;
+use super::leave_pass;
+use super::messages::report_error;
 
 #[cfg(debug_assertions)]
 macro_rules! main_debug {
@@ -52,10 +55,14 @@ pub(super) fn wire_group_selection_changed(
 
 pub(super) fn wire_add_person_request(
     app: &MainWindow,
-    conn: Rc<RefCell<Connection>>,
+    repo: Rc<dyn Repository>,
     refresh_groups: impl Fn() + Clone + 'static,
 ) {
+    let app_weak = app.as_weak();
     app.on_add_person_request(move |name, surname, rank, methodology| {
+        let Some(app) = app_weak.upgrade() else {
+            return;
+        };
         let Some(rank_enum) = parse_rank(rank) else {
             eprintln!("Invalid rank value: {}", rank);
             return;
@@ -65,21 +72,21 @@ pub(super) fn wire_add_person_request(
             return;
         };
 
-        let person = db_operations::Person {
-            id: 0,
-            name: name.to_string(),
-            surname: surname.to_string(),
-            rank_level: rank_enum,
-            methodology: methodology_enum,
-            is_inside: db_operations::IsInside::Out,
-        };
+        let person = db_operations::Person::new(name.to_string(), surname.to_string(), rank_enum, methodology_enum);
 
-        {
-            let conn_ref = conn.borrow();
-            if let Err(e) = db_operations::insert_to_db(&*conn_ref, db_operations::DatabaseRecord::Person(person)) {
-                eprintln!("Error during insertion person: {}", e);
+        let person_id = match repo.add_person(&person) {
+            Ok(id) => id,
+            Err(e) => {
+                report_error(&app, "Error during insertion person", &e);
                 return;
             }
+        };
+
+        // Someone added during an event is taking part in it.
+        if let Ok(Some(event_id)) = repo.active_event()
+            && let Err(e) = repo.add_participant(event_id, person_id)
+        {
+            report_error(&app, "Error adding person to the event", &e);
         }
 
         refresh_groups();
@@ -88,21 +95,25 @@ pub(super) fn wire_add_person_request(
 
 pub(super) fn wire_add_group_request(
     app: &MainWindow,
-    conn: Rc<RefCell<Connection>>,
+    repo: Rc<dyn Repository>,
     refresh_groups: impl Fn() + Clone + 'static,
 ) {
+    let app_weak = app.as_weak();
     app.on_add_group_request(move |name| {
+        let Some(app) = app_weak.upgrade() else {
+            return;
+        };
         let group = db_operations::Group {
             id: 0,
             name: name.to_string(),
+            kind: db_operations::GroupKind::User,
+            methodology: None,
+            archived_at: None,
         };
 
-        {
-            let conn_ref = conn.borrow();
-            if let Err(e) = db_operations::insert_to_db(&*conn_ref, db_operations::DatabaseRecord::Group(group)) {
-                eprintln!("Error during insertion group: {}", e);
-                return;
-            }
+        if let Err(e) = repo.add_group(&group) {
+            report_error(&app, "Error during insertion group", &e);
+            return;
         }
 
         refresh_groups();
@@ -111,34 +122,27 @@ pub(super) fn wire_add_group_request(
 
 pub(super) fn wire_add_person_to_group_request(
     app: &MainWindow,
-    conn: Rc<RefCell<Connection>>,
+    repo: Rc<dyn Repository>,
     refresh_groups: impl Fn() + Clone + 'static,
 ) {
+    let app_weak = app.as_weak();
     app.on_add_person_to_group_request(move |person_id, group_id| {
-        {
-            let conn_ref = conn.borrow();
-
-            match relation_exists(&conn_ref, group_id, person_id) {
-                Ok(true) => {
-                    eprintln!(
-                        "Relation already exists: person {} in group {}",
-                        person_id, group_id
-                    );
-                    return;
-                }
-                Ok(false) => {
-                    if let Err(e) = db_operations::insert_to_db(
-                        &*conn_ref,
-                        db_operations::DatabaseRecord::GroupMembers(group_id, person_id),
-                    ) {
-                        eprintln!("Error during insertion of relation: {}", e);
-                        return;
-                    }
-                }
-                Err(e) => {
-                    eprintln!("Error checking existing relation: {}", e);
-                    return;
-                }
+        let Some(app) = app_weak.upgrade() else {
+            return;
+        };
+
+        match repo.add_member(group_id, person_id) {
+            Ok(()) => {}
+            Err(db_operations::DbError::Duplicate(_)) => {
+                eprintln!(
+                    "Relation already exists: person {} in group {}",
+                    person_id, group_id
+                );
+                return;
+            }
+            Err(e) => {
+                report_error(&app, "Error during insertion of relation", &e);
+                return;
             }
         }
 
@@ -146,11 +150,100 @@ pub(super) fn wire_add_person_to_group_request(
     });
 }
 
+pub(super) fn wire_archive_requests(
+    app: &MainWindow,
+    repo: Rc<dyn Repository>,
+    backups: Backups,
+    refresh_groups: impl Fn() + Clone + 'static,
+) {
+    app.on_remove_person_request(archive_action(
+        app,
+        repo.clone(),
+        refresh_groups.clone(),
+        None,
+        "Error archiving person",
+        |repo, id| repo.archive_person(id),
+    ));
+    app.on_restore_person_request(archive_action(
+        app,
+        repo.clone(),
+        refresh_groups.clone(),
+        None,
+        "Error restoring person",
+        |repo, id| repo.restore_person(id),
+    ));
+    app.on_purge_person_request(archive_action(
+        app,
+        repo.clone(),
+        refresh_groups.clone(),
+        Some(backups.clone()),
+        "Error purging person",
+        |repo, id| repo.purge_person(id),
+    ));
+    app.on_archive_group_request(archive_action(
+        app,
+        repo.clone(),
+        refresh_groups.clone(),
+        None,
+        "Error archiving group",
+        |repo, id| repo.archive_group(id),
+    ));
+    app.on_restore_group_request(archive_action(
+        app,
+        repo.clone(),
+        refresh_groups.clone(),
+        None,
+        "Error restoring group",
+        |repo, id| repo.restore_group(id),
+    ));
+    app.on_purge_group_request(archive_action(
+        app,
+        repo,
+        refresh_groups,
+        Some(backups),
+        "Error purging group",
+        |repo, id| repo.purge_group(id),
+    ));
+}
+
+// Shared body of the archive/restore/purge callbacks: run `action`, report or refresh.
+// With `backup_first` the camp is backed up before, and nothing happens if that fails.
+fn archive_action(
+    app: &MainWindow,
+    repo: Rc<dyn Repository>,
+    refresh_groups: impl Fn() + Clone + 'static,
+    backup_first: Option<Backups>,
+    context: &'static str,
+    action: fn(&dyn Repository, i32) -> db_operations::DbResult<()>,
+) -> impl FnMut(i32) + 'static {
+    let app_weak = app.as_weak();
+    move |id| {
+        let Some(app) = app_weak.upgrade() else {
+            return;
+        };
+
+        if let Some(backups) = &backup_first
+            && !backups.take(&app, BackupReason::BeforeDelete)
+        {
+            return;
+        }
+        if let Err(e) = action(repo.as_ref(), id) {
+            report_error(&app, context, &e);
+            return;
+        }
+
+        main_debug!("[archive] {} ok for id={}", context, id);
+        refresh_groups();
+    }
+}
+
 pub(super) fn wire_main_person_toggled(
     app: &MainWindow,
     all_persons_for_main: Rc<RefCell<Vec<PersonData>>>,
     checked_person_ids: Rc<RefCell<HashSet<i32>>>,
     out_person_ids: Rc<RefCell<HashSet<i32>>>,
+    selected_group_id: Rc<RefCell<Option<i32>>>,
+    matching_person_ids: Rc<RefCell<Option<HashSet<i32>>>>,
 ) {
     let app_weak = app.as_weak();
     app.on_main_person_toggled(move |person_id| {
@@ -158,6 +251,9 @@ pub(super) fn wire_main_person_toggled(
             return;
         };
 
+        // Hand-edited selection is no longer "the group".
+        selected_group_id.borrow_mut().take();
+
         let now_checked = {
             let mut set = checked_person_ids.borrow_mut();
             if set.contains(&person_id) {
@@ -175,6 +271,7 @@ pub(super) fn wire_main_person_toggled(
             &all_persons_for_main.borrow(),
             &out_person_ids.borrow(),
             &checked_person_ids.borrow(),
+            matching_person_ids.borrow().as_ref(),
         );
     });
 }
@@ -185,6 +282,8 @@ pub(super) fn wire_main_group_clicked(
     checked_person_ids: Rc<RefCell<HashSet<i32>>>,
     out_person_ids: Rc<RefCell<HashSet<i32>>>,
     group_members_by_id: Rc<RefCell<HashMap<i32, Vec<i32>>>>,
+    selected_group_id: Rc<RefCell<Option<i32>>>,
+    matching_person_ids: Rc<RefCell<Option<HashSet<i32>>>>,
 ) {
     let app_weak = app.as_weak();
     app.on_main_group_clicked(move |group_id| {
@@ -211,22 +310,31 @@ pub(super) fn wire_main_group_clicked(
             }
             main_debug!("[main] checked_person_ids size={}", set.len());
         }
+        *selected_group_id.borrow_mut() = Some(group_id);
+        // The whole group moves, so show all of it.
+        if matching_person_ids.borrow_mut().take().is_some() {
+            app.set_person_search(SharedString::new());
+        }
 
         set_main_people_models(
             &app,
             &all_persons_for_main.borrow(),
             &out_person_ids.borrow(),
             &checked_person_ids.borrow(),
+            matching_person_ids.borrow().as_ref(),
         );
     });
 }
 
+#[allow(clippy::too_many_arguments)]
 pub(super) fn wire_main_get_in(
     app: &MainWindow,
-    conn: Rc<RefCell<Connection>>,
+    repo: Rc<dyn Repository>,
     all_persons_for_main: Rc<RefCell<Vec<PersonData>>>,
     checked_person_ids: Rc<RefCell<HashSet<i32>>>,
     out_person_ids: Rc<RefCell<HashSet<i32>>>,
+    selected_group_id: Rc<RefCell<Option<i32>>>,
+    matching_person_ids: Rc<RefCell<Option<HashSet<i32>>>>,
     refresh_groups: impl Fn() + Clone + 'static,
 ) {
     let app_weak = app.as_weak();
@@ -236,15 +344,11 @@ pub(super) fn wire_main_get_in(
         };
 
         let selected: Vec<i32> = checked_person_ids.borrow().iter().copied().collect();
+        let group_id = *selected_group_id.borrow();
 
-        // Persist DB state
-        {
-            let conn_ref = conn.borrow();
-            for id in &selected {
-                if let Err(e) = db_operations::set_person_is_inside(&*conn_ref, *id, db_operations::IsInside::In) {
-                    eprintln!("Error updating person is_inside (GET_IN) for id {}: {}", id, e);
-                }
-            }
+        // Persist DB state (all or nothing)
+        if !persist_presence(&app, repo.as_ref(), group_id, &selected, db_operations::IsInside::In, None) {
+            return;
         }
 
         {
@@ -254,37 +358,46 @@ pub(super) fn wire_main_get_in(
             }
         }
         checked_person_ids.borrow_mut().clear();
+        selected_group_id.borrow_mut().take();
 
         main_debug!("[main] GET_IN moved {} ids", selected.len());
         refresh_groups();
-        set_main_people_models(&app, &all_persons_for_main.borrow(), &out_person_ids.borrow(), &checked_person_ids.borrow());
+        set_main_people_models(
+            &app,
+            &all_persons_for_main.borrow(),
+            &out_person_ids.borrow(),
+            &checked_person_ids.borrow(),
+            matching_person_ids.borrow().as_ref(),
+        );
     });
 }
 
+#[allow(clippy::too_many_arguments)]
 pub(super) fn wire_main_get_out(
     app: &MainWindow,
-    conn: Rc<RefCell<Connection>>,
+    repo: Rc<dyn Repository>,
     all_persons_for_main: Rc<RefCell<Vec<PersonData>>>,
     checked_person_ids: Rc<RefCell<HashSet<i32>>>,
     out_person_ids: Rc<RefCell<HashSet<i32>>>,
+    selected_group_id: Rc<RefCell<Option<i32>>>,
+    matching_person_ids: Rc<RefCell<Option<HashSet<i32>>>>,
     refresh_groups: impl Fn() + Clone + 'static,
 ) {
     let app_weak = app.as_weak();
-    app.on_main_get_out(move || {
+    app.on_main_get_out(move |data| {
         let Some(app) = app_weak.upgrade() else {
-            return;
+            return false;
+        };
+        let Some(pass) = leave_pass::details_from_data(&app, &data) else {
+            return false;
         };
 
         let selected: Vec<i32> = checked_person_ids.borrow().iter().copied().collect();
+        let group_id = *selected_group_id.borrow();
 
-        // Persist DB state
-        {
-            let conn_ref = conn.borrow();
-            for id in &selected {
-                if let Err(e) = db_operations::set_person_is_inside(&*conn_ref, *id, db_operations::IsInside::Out) {
-                    eprintln!("Error updating person is_inside (GET_OUT) for id {}: {}", id, e);
-                }
-            }
+        // Persist DB state (all or nothing)
+        if !persist_presence(&app, repo.as_ref(), group_id, &selected, db_operations::IsInside::Out, pass.as_ref()) {
+            return false;
         }
 
         {
@@ -294,22 +407,73 @@ pub(super) fn wire_main_get_out(
             }
         }
         checked_person_ids.borrow_mut().clear();
+        selected_group_id.borrow_mut().take();
 
         main_debug!("[main] GET_OUT moved {} ids", selected.len());
         refresh_groups();
-        set_main_people_models(&app, &all_persons_for_main.borrow(), &out_person_ids.borrow(), &checked_person_ids.borrow());
+        set_main_people_models(
+            &app,
+            &all_persons_for_main.borrow(),
+            &out_person_ids.borrow(),
+            &checked_person_ids.borrow(),
+            matching_person_ids.borrow().as_ref(),
+        );
+        true
     });
 }
 
-fn set_main_people_models(
+// With `group_id` the batch is logged as one group event with the members linked to it.
+// `pass` gives every person leaving a leave pass; it is ignored for GET_IN.
+fn persist_presence(
+    app: &MainWindow,
+    repo: &dyn Repository,
+    group_id: Option<i32>,
+    selected: &[i32],
+    is_inside: db_operations::IsInside,
+    pass: Option<&db_operations::LeaveDetails>,
+) -> bool {
+    if selected.is_empty() {
+        return true;
+    }
+
+    let Some(event_id) = active_event_or_report(app, repo) else {
+        return false;
+    };
+
+    let operator = operator_name(app);
+    let operator = operator.as_deref();
+
+    let result = match (group_id, pass) {
+        (_, Some(pass)) if is_inside == db_operations::IsInside::Out => {
+            repo.leave_with_pass(event_id, group_id, selected, operator, pass)
+        }
+        (Some(group_id), _) => repo.set_group_presence(event_id, group_id, selected, is_inside, operator),
+        (None, _) => repo.set_presence(event_id, selected, is_inside, operator),
+    };
+
+    match result {
+        Ok(batch) => {
+            main_debug!("[main] presence batch {} wrote {} rows", batch.batch_id, batch.person_ids.len());
+            true
+        }
+        Err(e) => {
+            report_error(app, &format!("Error updating person is_inside ({:?})", is_inside), &e);
+            false
+        }
+    }
+}
+
+/// Splits `persons_all` into the IN/OUT lists. With `matches`, only those ids are shown.
+pub(super) fn set_main_people_models(
     app: &MainWindow,
     persons_all: &[PersonData],
     out_set: &HashSet<i32>,
     checked_set: &HashSet<i32>,
+    matches: Option<&HashSet<i32>>,
 ) {
     let mut people_in: Vec<PersonData> = Vec::new();
     let mut people_out: Vec<PersonData> = Vec::new();
-    for p in persons_all {
+    for p in persons_all.iter().filter(|p| matches.is_none_or(|m| m.contains(&p.id))) {
         if out_set.contains(&p.id) {
             people_out.push(p.clone());
         } else {
@@ -328,29 +492,17 @@ fn set_main_people_models(
     app.set_people_out(ModelRc::new(VecModel::from(people_out)));
 }
 
-fn relation_exists(conn: &Connection, group_id: i32, person_id: i32) -> rusqlite::Result<bool> {
-    let exists: rusqlite::Result<Option<i32>> = conn
-        .query_row(
-            "SELECT 1 FROM `GroupMembers` WHERE `group_id` = ?1 AND `person_id` = ?2 LIMIT 1;",
-            (group_id, person_id),
-            |row| row.get(0),
-        )
-        .map(Some)
-        .or_else(|e| {
-            if let rusqlite::Error::QueryReturnedNoRows = e {
-                Ok(None)
-            } else {
-                Err(e)
-            }
-        });
-
-    Ok(exists?.is_some())
-}
-
-fn parse_rank(rank: i32) -> Option<db_operations::RankLevel> {
+pub(super) fn parse_rank(rank: i32) -> Option<db_operations::RankLevel> {
     db_operations::RankLevel::try_from(rank).ok()
 }
 
-fn parse_methodology(methodology: i32) -> Option<db_operations::Methodology> {
+pub(super) fn parse_methodology(methodology: i32) -> Option<db_operations::Methodology> {
     db_operations::Methodology::try_from(methodology).ok()
 }
+
+/// The operator name typed on the main screen, trimmed; `None` when empty.
+pub(super) fn operator_name(app: &MainWindow) -> Option<String> {
+    let operator = app.get_operator_name();
+    let operator = operator.trim();
+    (!operator.is_empty()).then(|| operator.to_string())
+}
@@ -0,0 +1,162 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use slint::{Color, ModelRc, SharedString, VecModel};
+
+use crate::db_operations::{DbResult, GroupWithMembers, IsInside, Methodology, Participant, Repository};
+use crate::{HeadcountData, MainWindow};
+
+use super::format::format_recent;
+use super::messages::report_error;
+
+const METHODOLOGIES: [Methodology; 4] = [Methodology::Cub, Methodology::Scout, Methodology::VentureScout, Methodology::Rover];
+
+/// Counts behind one headcount row.
+#[derive(Default)]
+struct Tally {
+    inside: i32,
+    outside: i32,
+    last_movement: Option<DateTime<Utc>>,
+}
+
+impl Tally {
+    fn add(&mut self, is_inside: IsInside, moved_at: Option<DateTime<Utc>>) {
+        match is_inside {
+            IsInside::In => self.inside += 1,
+            IsInside::Out => self.outside += 1,
+        }
+        self.last_movement = self.last_movement.max(moved_at);
+    }
+
+    fn to_data(&self, label: &str, color: Color) -> HeadcountData {
+        HeadcountData {
+            label: SharedString::from(label),
+            color,
+            inside: self.inside,
+            outside: self.outside,
+            total: self.inside + self.outside,
+            last_movement: self.last_movement.map(|t| SharedString::from(format_recent(t))).unwrap_or_default(),
+        }
+    }
+}
+
+/// Main screen headcount of the active event: the whole camp, each methodology and each
+/// user group with participants in it. Called on every refresh, with the same
+/// participants (and stored presence) as the IN/OUT lists.
+pub(super) fn set_headcount_models(
+    app: &MainWindow,
+    repo: &dyn Repository,
+    event_id: Option<i32>,
+    participants: &[Participant],
+    groups: &[GroupWithMembers],
+) {
+    let last_moves = match event_id.map(|id| last_moves(repo, id)) {
+        Some(Ok(moves)) => moves,
+        Some(Err(e)) => {
+            report_error(app, "Error loading the last movements", &e);
+            HashMap::new()
+        }
+        None => HashMap::new(),
+    };
+    let (camp, by_methodology, by_group) = headcount(participants, groups, &last_moves);
+    app.set_headcount(camp);
+    app.set_headcount_by_methodology(ModelRc::new(VecModel::from(by_methodology)));
+    app.set_headcount_by_group(ModelRc::new(VecModel::from(by_group)));
+}
+
+// Newest log row of each person in the event.
+fn last_moves(repo: &dyn Repository, event_id: i32) -> DbResult<HashMap<i32, DateTime<Utc>>> {
+    Ok(repo
+        .presence_at(event_id, Utc::now())?
+        .into_iter()
+        .filter_map(|s| Some((s.person_id, s.last?.time)))
+        .collect())
+}
+
+// Rows of the camp, each methodology and each user group, skipping empty ones.
+fn headcount(
+    participants: &[Participant],
+    groups: &[GroupWithMembers],
+    last_moves: &HashMap<i32, DateTime<Utc>>,
+) -> (HeadcountData, Vec<HeadcountData>, Vec<HeadcountData>) {
+    let camp = tally(participants, last_moves);
+
+    let by_methodology = METHODOLOGIES
+        .iter()
+        .map(|&m| (m, tally(participants.iter().filter(|p| p.person.methodology == m), last_moves)))
+        .filter(|(_, t)| t.inside + t.outside > 0)
+        .map(|(m, t)| t.to_data("", m.as_color()))
+        .collect();
+
+    let by_id: HashMap<i32, &Participant> = participants.iter().map(|p| (p.person.id, p)).collect();
+    let by_group = groups
+        .iter()
+        .filter(|g| !g.kind.is_built_in())
+        .map(|g| (g, tally(g.members.iter().filter_map(|m| by_id.get(&m.id).copied()), last_moves)))
+        .filter(|(_, t)| t.inside + t.outside > 0)
+        .map(|(g, t)| t.to_data(&g.name, Color::default()))
+        .collect();
+
+    (camp.to_data("", Color::default()), by_methodology, by_group)
+}
+
+fn tally<'a>(persons: impl IntoIterator<Item = &'a Participant>, last_moves: &HashMap<i32, DateTime<Utc>>) -> Tally {
+    let mut tally = Tally::default();
+    for p in persons {
+        tally.add(p.is_inside, last_moves.get(&p.person.id).copied());
+    }
+    tally
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use super::*;
+    use crate::db_operations::test_support::memory_db;
+    use crate::db_operations::{Group, GroupKind, Person, RankLevel, SqliteRepository};
+
+    #[test]
+    fn counts_the_camp_methodologies_and_user_groups() {
+        let repo: &dyn Repository = &SqliteRepository::new(Rc::new(RefCell::new(memory_db())));
+        let add = |name: &str, methodology| {
+            let id = repo.add_person(&Person::new(name.to_string(), "Nowak".to_string(), RankLevel::RankNone, methodology)).unwrap();
+            repo.add_participant(1, id).unwrap();
+            id
+        };
+        let (a, b, c) = (add("Jan", Methodology::Scout), add("Ola", Methodology::Scout), add("Piotr", Methodology::Rover));
+        let wolves = Group {
+            id: 0,
+            name: "Wolves".to_string(),
+            kind: GroupKind::User,
+            methodology: None,
+            archived_at: None,
+        };
+        let wolves = repo.add_group(&wolves).unwrap();
+        repo.add_member(wolves, a).unwrap();
+        repo.add_member(wolves, c).unwrap();
+        // Left the camp already, so it doesn't count for the group either.
+        let gone = add("Ewa", Methodology::Cub);
+        repo.add_member(wolves, gone).unwrap();
+        repo.remove_participant(1, gone).unwrap();
+        repo.set_presence(1, &[a, b, c], IsInside::In, None).unwrap();
+        repo.set_presence(1, &[b], IsInside::Out, None).unwrap();
+
+        let moves = last_moves(repo, 1).unwrap();
+        let (camp, by_methodology, by_group) = headcount(&repo.participants(1).unwrap(), &repo.groups_with_members().unwrap(), &moves);
+
+        assert_eq!((camp.inside, camp.outside, camp.total), (2, 1, 3));
+        assert_eq!(camp.last_movement, format_recent(moves[&b]));
+        let counts: Vec<(Color, i32, i32)> = by_methodology.iter().map(|m| (m.color, m.inside, m.total)).collect();
+        assert_eq!(counts, [(Methodology::Scout.as_color(), 1, 2), (Methodology::Rover.as_color(), 1, 1)]);
+        let groups: Vec<(&str, i32, i32)> = by_group.iter().map(|g| (g.label.as_str(), g.inside, g.total)).collect();
+        assert_eq!(groups, [("Wolves", 2, 2)]);
+    }
+
+    #[test]
+    fn no_event_means_no_movements() {
+        let (camp, by_methodology, by_group) = headcount(&[], &[], &HashMap::new());
+        assert_eq!((camp.total, camp.last_movement.as_str()), (0, ""));
+        assert!(by_methodology.is_empty() && by_group.is_empty());
+    }
+}
@@ -0,0 +1,185 @@
+use std::{cell::RefCell, rc::Rc};
+
+use rusqlite::Connection;
+use slint::{ComponentHandle, ModelRc, SharedString, VecModel};
+
+use crate::db_operations::{self, BackupReason, IsInside, Person, Problem, ReconcileWith};
+use crate::{MainWindow, ProblemData};
+
+use super::backups::Backups;
+use super::format::full_name;
+use super::messages::{report_error, report_message};
+
+/// Result of the last check, as listed on the Settings screen.
+#[derive(Default)]
+pub(super) struct Checked {
+    problems: Vec<Problem>,
+    /// Persons offered for orphaned log rows, in the order of `integrity_person_names`.
+    person_ids: Vec<i32>,
+}
+
+/// Checks the camp just opened. Problems fill the Settings list and, if there are any,
+/// the error banner points there.
+pub(super) fn check_camp(app: &MainWindow, conn: &Connection, checked: &RefCell<Checked>) {
+    if check(app, conn, checked) && !checked.borrow().problems.is_empty() {
+        report_message(
+            app,
+            &format!(
+                "The database check found {} problem(s). See Settings → INTEGRITY.",
+                checked.borrow().problems.len()
+            ),
+        );
+    }
+}
+
+/// Settings screen: CHECK_NOW, the repairs and reattaching orphaned log rows to the person
+/// the operator picks. The camp is backed up before either changes anything.
+pub(super) fn wire_integrity_requests(
+    app: &MainWindow,
+    conn: Rc<RefCell<Connection>>,
+    backups: Backups,
+    checked: Rc<RefCell<Checked>>,
+    refresh_groups: impl Fn() + Clone + 'static,
+) {
+    let app_weak = app.as_weak();
+    let (check_conn, check_checked) = (conn.clone(), checked.clone());
+    app.on_integrity_check_requested(move || {
+        if let Some(app) = app_weak.upgrade() {
+            check(&app, &check_conn.borrow(), &check_checked);
+        }
+    });
+
+    // Index into the list, index into `integrity_person_names`.
+    let app_weak = app.as_weak();
+    let (reattach_conn, reattach_checked, reattach_backups) = (conn.clone(), checked.clone(), backups.clone());
+    let refresh = refresh_groups.clone();
+    app.on_reattach_orphaned_logs(move |index, person_index| {
+        let Some(app) = app_weak.upgrade() else {
+            return;
+        };
+        let picked = {
+            let checked = reattach_checked.borrow();
+            let problem = usize::try_from(index).ok().and_then(|i| checked.problems.get(i));
+            let person_id = usize::try_from(person_index).ok().and_then(|i| checked.person_ids.get(i));
+            match (problem, person_id) {
+                (Some(Problem::OrphanedLogs { entity_id, .. }), Some(&person_id)) => Some((*entity_id, person_id)),
+                _ => None,
+            }
+        };
+        let Some((entity_id, person_id)) = picked else {
+            return;
+        };
+        if !reattach_backups.take(&app, BackupReason::BeforeRepair) {
+            return;
+        }
+        if let Err(e) = db_operations::reattach_orphaned_logs(&reattach_conn.borrow(), entity_id, person_id) {
+            report_error(&app, "Error reattaching the log entries", &e);
+        }
+        check(&app, &reattach_conn.borrow(), &reattach_checked);
+        refresh();
+    });
+
+    // Index into the list; -1 repairs everything repairable.
+    let app_weak = app.as_weak();
+    app.on_repair_problems(move |index| {
+        let Some(app) = app_weak.upgrade() else {
+            return;
+        };
+        let mut selected: Vec<Problem> = match usize::try_from(index) {
+            Ok(i) => checked.borrow().problems.get(i).cloned().into_iter().collect(),
+            Err(_) => checked.borrow().problems.clone(),
+        };
+        // The list may be stale (someone checked in since): only repair what is still found.
+        let current = match db_operations::check_database(&conn.borrow()) {
+            Ok(current) => current,
+            Err(e) => {
+                report_error(&app, "Error checking the database", &e);
+                return;
+            }
+        };
+        selected.retain(|p| current.contains(p));
+        if !selected.iter().any(Problem::is_repairable) {
+            check(&app, &conn.borrow(), &checked);
+            return;
+        }
+        if !backups.take(&app, BackupReason::BeforeRepair) {
+            return;
+        }
+        if let Err(e) = db_operations::repair(&conn.borrow(), &selected) {
+            report_error(&app, "Error repairing the database", &e);
+        }
+        check(&app, &conn.borrow(), &checked);
+        refresh_groups();
+    });
+}
+
+// Runs the check and shows the result; `false` if it couldn't run.
+fn check(app: &MainWindow, conn: &Connection, checked: &RefCell<Checked>) -> bool {
+    let found = match db_operations::check_database(conn) {
+        Ok(found) => found,
+        Err(e) => {
+            report_error(app, "Error checking the database", &e);
+            return false;
+        }
+    };
+    // Anybody, archived included, can be who the orphaned rows were about.
+    let mut persons: Vec<Person> = Vec::new();
+    if found.iter().any(|p| matches!(p, Problem::OrphanedLogs { .. })) {
+        match db_operations::get_person(conn).and_then(|active| Ok((active, db_operations::get_archived_persons(conn)?))) {
+            Ok((active, archived)) => persons = active.into_iter().chain(archived).collect(),
+            Err(e) => report_error(app, "Error loading persons", &e),
+        }
+        persons.sort_by_cached_key(|p| (p.surname.to_lowercase(), p.name.to_lowercase(), p.id));
+    }
+    let names: Vec<SharedString> = persons
+        .iter()
+        .map(|p| match p.archived_at {
+            Some(_) => SharedString::from(format!("{} (archived)", full_name(p))),
+            None => SharedString::from(full_name(p)),
+        })
+        .collect();
+
+    let items: Vec<ProblemData> = found.iter().map(problem_to_data).collect();
+    app.set_integrity_problems(ModelRc::new(VecModel::from(items)));
+    app.set_integrity_person_names(ModelRc::new(VecModel::from(names)));
+    app.set_integrity_checked(true);
+    *checked.borrow_mut() = Checked {
+        problems: found,
+        person_ids: persons.iter().map(|p| p.id).collect(),
+    };
+    true
+}
+
+fn problem_to_data(p: &Problem) -> ProblemData {
+    let description = match p {
+        Problem::Damaged(message) => format!("Damaged database: {message}. Restore a backup."),
+        Problem::DanglingReference { table, rowid, parent } => {
+            format!("{table} row {rowid} points at a missing {parent} row.")
+        }
+        Problem::MissingBuiltInGroups => "The all-members group or a methodology group is missing.".to_string(),
+        Problem::BuiltInMemberships { name, surname, .. } => {
+            format!("{name} {surname} is missing from the camp group or their methodology group.")
+        }
+        Problem::OrphanedLogs { entity_id, rows } => {
+            format!("{rows} log entries point at deleted person #{entity_id} and don't say who it was. Pick who it was to reattach them.")
+        }
+        Problem::PresenceMismatch(m) => {
+            let side = |is_inside| if is_inside == IsInside::In { "inside" } else { "outside" };
+            match m.default_fix() {
+                ReconcileWith::Log => format!(
+                    "{} {} is marked {} in {}, but the log says {}; the log will be followed.",
+                    m.name, m.surname, side(m.stored), m.event_name, side(m.logged)
+                ),
+                ReconcileWith::Stored => format!(
+                    "{} {} is marked {} in {} but was never logged there; a log entry will be added.",
+                    m.name, m.surname, side(m.stored), m.event_name
+                ),
+            }
+        }
+    };
+    ProblemData {
+        description: SharedString::from(description),
+        repairable: p.is_repairable(),
+        orphaned: matches!(p, Problem::OrphanedLogs { .. }),
+    }
+}
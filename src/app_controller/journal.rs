@@ -0,0 +1,446 @@
+use std::{cell::RefCell, fs, rc::Rc};
+
+use chrono::{DateTime, Days, Local, NaiveDate, NaiveTime, TimeZone, Utc};
+use slint::{ComponentHandle, Model, ModelRc, SharedString, VecModel};
+
+use crate::db_operations::{self, Incident, MorningReport, NewIncident, Person, Repository, Severity};
+use crate::{CheckpointData, JournalEntryData, JournalShiftData, MainWindow};
+
+use super::events::active_event_or_report;
+use super::format::{format_local, format_time, full_name, html_escape};
+use super::handlers::operator_name;
+use super::messages::{report_error, report_message};
+
+/// Sentry journal page state: the ids behind its ComboBoxes and the pending tags.
+#[derive(Default)]
+struct JournalView {
+    person_ids: Vec<i32>,
+    group_ids: Vec<i32>,
+    // Tagged for the next incident, with their names, in the order tagged.
+    tagged_persons: Vec<(i32, String)>,
+    tagged_groups: Vec<(i32, String)>,
+}
+
+/// Sentry journal modal on the main screen. The journal doesn't change presence, so
+/// nothing else is refreshed.
+pub(super) fn wire_journal_requests(app: &MainWindow, repo: Rc<dyn Repository>) {
+    let view = Rc::new(RefCell::new(JournalView::default()));
+
+    let app_weak = app.as_weak();
+    let (open_repo, open_view) = (repo.clone(), view.clone());
+    app.on_journal_opened(move || {
+        if let Some(app) = app_weak.upgrade() {
+            clear_tags(&open_view);
+            show(&app, open_repo.as_ref(), &open_view);
+        }
+    });
+
+    let app_weak = app.as_weak();
+    let (start_repo, start_view) = (repo.clone(), view.clone());
+    app.on_start_sentry_shift(move |guards| {
+        let Some(app) = app_weak.upgrade() else {
+            return false;
+        };
+        let Some(event_id) = active_event_or_report(&app, start_repo.as_ref()) else {
+            return false;
+        };
+        match start_repo.start_sentry_shift(event_id, &guards, operator_name(&app).as_deref()) {
+            Ok(_) => {
+                show(&app, start_repo.as_ref(), &start_view);
+                true
+            }
+            Err(e) => {
+                report_error(&app, "Error starting the sentry shift", &e);
+                false
+            }
+        }
+    });
+
+    let app_weak = app.as_weak();
+    let (end_repo, end_view) = (repo.clone(), view.clone());
+    app.on_end_sentry_shift(move || {
+        let Some(app) = app_weak.upgrade() else {
+            return;
+        };
+        if let Err(e) = end_repo.end_sentry_shift(app.get_journal_shift().id) {
+            report_error(&app, "Error ending the sentry shift", &e);
+        }
+        show(&app, end_repo.as_ref(), &end_view);
+    });
+
+    let app_weak = app.as_weak();
+    let (checkpoint_repo, checkpoint_view) = (repo.clone(), view.clone());
+    app.on_add_checkpoint(move |name| {
+        let Some(app) = app_weak.upgrade() else {
+            return false;
+        };
+        let Some(event_id) = active_event_or_report(&app, checkpoint_repo.as_ref()) else {
+            return false;
+        };
+        match checkpoint_repo.add_checkpoint(event_id, &name) {
+            Ok(_) => {
+                show(&app, checkpoint_repo.as_ref(), &checkpoint_view);
+                true
+            }
+            Err(e) => {
+                report_error(&app, "Error adding the checkpoint", &e);
+                false
+            }
+        }
+    });
+
+    let app_weak = app.as_weak();
+    let (visit_repo, visit_view) = (repo.clone(), view.clone());
+    app.on_visit_checkpoint(move |checkpoint_id| {
+        let Some(app) = app_weak.upgrade() else {
+            return;
+        };
+        if let Err(e) = visit_repo.visit_checkpoint(app.get_journal_shift().id, checkpoint_id) {
+            report_error(&app, "Error recording the round", &e);
+        }
+        show(&app, visit_repo.as_ref(), &visit_view);
+    });
+
+    let app_weak = app.as_weak();
+    let person_view = view.clone();
+    app.on_journal_tag_person(move |index| {
+        let Some(app) = app_weak.upgrade() else {
+            return;
+        };
+        let mut view = person_view.borrow_mut();
+        let Some((index, &person_id)) = usize::try_from(index).ok().and_then(|i| Some((i, view.person_ids.get(i)?))) else {
+            return;
+        };
+        if !view.tagged_persons.iter().any(|(id, _)| *id == person_id) {
+            let name = app.get_journal_person_names().row_data(index).unwrap_or_default();
+            view.tagged_persons.push((person_id, name.to_string()));
+        }
+        app.set_journal_tags(tags_text(&view));
+    });
+
+    let app_weak = app.as_weak();
+    let group_view = view.clone();
+    app.on_journal_tag_group(move |index| {
+        let Some(app) = app_weak.upgrade() else {
+            return;
+        };
+        let mut view = group_view.borrow_mut();
+        let Some((index, &group_id)) = usize::try_from(index).ok().and_then(|i| Some((i, view.group_ids.get(i)?))) else {
+            return;
+        };
+        if !view.tagged_groups.iter().any(|(id, _)| *id == group_id) {
+            let name = app.get_journal_group_names().row_data(index).unwrap_or_default();
+            view.tagged_groups.push((group_id, name.to_string()));
+        }
+        app.set_journal_tags(tags_text(&view));
+    });
+
+    let app_weak = app.as_weak();
+    let clear_view = view.clone();
+    app.on_journal_clear_tags(move || {
+        if let Some(app) = app_weak.upgrade() {
+            clear_tags(&clear_view);
+            app.set_journal_tags(SharedString::new());
+        }
+    });
+
+    let app_weak = app.as_weak();
+    let (incident_repo, incident_view) = (repo.clone(), view.clone());
+    app.on_record_incident(move |severity, description| {
+        let Some(app) = app_weak.upgrade() else {
+            return false;
+        };
+        let Some(event_id) = active_event_or_report(&app, incident_repo.as_ref()) else {
+            return false;
+        };
+        let incident = {
+            let view = incident_view.borrow();
+            NewIncident {
+                occurred_at: Utc::now(),
+                severity: Severity::try_from(severity).unwrap_or_default(),
+                description: description.to_string(),
+                person_ids: view.tagged_persons.iter().map(|(id, _)| *id).collect(),
+                group_ids: view.tagged_groups.iter().map(|(id, _)| *id).collect(),
+            }
+        };
+        match incident_repo.record_incident(event_id, &incident, operator_name(&app).as_deref()) {
+            Ok(_) => {
+                clear_tags(&incident_view);
+                show(&app, incident_repo.as_ref(), &incident_view);
+                true
+            }
+            Err(e) => {
+                report_error(&app, "Error recording the incident", &e);
+                false
+            }
+        }
+    });
+
+    let app_weak = app.as_weak();
+    let (delete_repo, delete_view) = (repo.clone(), view.clone());
+    app.on_delete_incident(move |incident_id| {
+        let Some(app) = app_weak.upgrade() else {
+            return;
+        };
+        if let Err(e) = delete_repo.delete_incident(incident_id) {
+            report_error(&app, "Error deleting the incident", &e);
+        }
+        show(&app, delete_repo.as_ref(), &delete_view);
+    });
+
+    let app_weak = app.as_weak();
+    app.on_morning_report(move |day| {
+        let Some(app) = app_weak.upgrade() else {
+            return SharedString::new();
+        };
+        let Some(event_id) = active_event_or_report(&app, repo.as_ref()) else {
+            return SharedString::new();
+        };
+        let day = day.trim();
+        let day = if day.is_empty() {
+            Local::now().date_naive()
+        } else {
+            match NaiveDate::parse_from_str(day, "%Y-%m-%d") {
+                Ok(day) => day,
+                Err(_) => {
+                    report_message(&app, &format!("Invalid day '{day}', use YYYY-MM-DD."));
+                    return SharedString::new();
+                }
+            }
+        };
+        let window = day.checked_sub_days(Days::new(1)).and_then(|eve| Some((noon(eve)?, noon(day)?)));
+        let Some((from, to)) = window else {
+            report_message(&app, &format!("No night before {day}."));
+            return SharedString::new();
+        };
+        let report = match repo.morning_report(event_id, from, to) {
+            Ok(report) => report,
+            Err(e) => {
+                report_error(&app, "Error building the morning report", &e);
+                return SharedString::new();
+            }
+        };
+
+        let dir = db_operations::data_dir().join("exports");
+        let path = dir.join(format!("morning-report-{day}-{}.html", Local::now().format("%Y%m%d-%H%M%S")));
+        match fs::create_dir_all(&dir).and_then(|()| fs::write(&path, report_html(&report))) {
+            Ok(()) => SharedString::from(path.display().to_string()),
+            Err(e) => {
+                report_message(&app, &format!("Couldn't write the morning report to {}: {e}", dir.display()));
+                SharedString::new()
+            }
+        }
+    });
+}
+
+// Reloads the page for the active event: the shift in progress and everything since noon.
+fn show(app: &MainWindow, repo: &dyn Repository, view: &RefCell<JournalView>) {
+    let mut view = view.borrow_mut();
+    let loaded = repo.active_event().and_then(|event_id| match event_id {
+        Some(event_id) => Ok((
+            repo.sentry_shifts(event_id)?,
+            repo.checkpoints(event_id)?,
+            repo.incidents(event_id)?,
+            repo.participants(event_id)?,
+            repo.groups()?,
+        )),
+        None => Ok(Default::default()),
+    });
+    let (shifts, checkpoints, incidents, participants, groups) = match loaded {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            report_error(app, "Error loading the sentry journal", &e);
+            return;
+        }
+    };
+
+    let mut persons: Vec<&Person> = participants.iter().map(|p| &p.person).collect();
+    persons.sort_by_cached_key(|p| (p.surname.to_lowercase(), p.name.to_lowercase(), p.id));
+    view.person_ids = persons.iter().map(|p| p.id).collect();
+    let person_names: Vec<SharedString> = persons.iter().map(|p| SharedString::from(full_name(p))).collect();
+    view.group_ids = groups.iter().map(|g| g.id).collect();
+    let group_names: Vec<SharedString> = groups.iter().map(|g| SharedString::from(g.name.as_str())).collect();
+
+    let current = shifts.iter().find(|s| !s.is_over());
+    let checkpoint_data: Vec<CheckpointData> = checkpoints
+        .iter()
+        .map(|c| CheckpointData {
+            id: c.id,
+            name: SharedString::from(c.name.as_str()),
+            last_visit: current
+                .and_then(|s| s.visits.iter().rev().find(|v| v.checkpoint_id == c.id))
+                .map(|v| SharedString::from(format_time(v.visited_at)))
+                .unwrap_or_default(),
+        })
+        .collect();
+
+    let since = last_noon();
+    let mut entries: Vec<(DateTime<Utc>, JournalEntryData)> = Vec::new();
+    let mut push = |time: DateTime<Utc>, kind: i32, text: String| {
+        if time >= since {
+            let entry = JournalEntryData { time: format_time(time).into(), kind, text: text.into(), ..Default::default() };
+            entries.push((time, entry));
+        }
+    };
+    for shift in &shifts {
+        push(shift.started_at, 0, shift.guards.clone());
+        for visit in &shift.visits {
+            push(visit.visited_at, 2, visit.checkpoint.clone());
+        }
+        if let Some(ended_at) = shift.ended_at {
+            push(ended_at, 1, shift.guards.clone());
+        }
+    }
+    for incident in incidents.iter().filter(|i| i.occurred_at >= since) {
+        let entry = JournalEntryData {
+            time: format_time(incident.occurred_at).into(),
+            kind: 3,
+            text: incident_text(incident).into(),
+            severity: incident.severity as i32,
+            incident_id: incident.id,
+        };
+        entries.push((incident.occurred_at, entry));
+    }
+    // Newest first.
+    entries.sort_by(|(a, _), (b, _)| b.cmp(a));
+    let entries: Vec<JournalEntryData> = entries.into_iter().map(|(_, entry)| entry).collect();
+
+    // Tags of persons or groups no longer listed are dropped.
+    let (person_ids, group_ids) = (view.person_ids.clone(), view.group_ids.clone());
+    view.tagged_persons.retain(|(id, _)| person_ids.contains(id));
+    view.tagged_groups.retain(|(id, _)| group_ids.contains(id));
+
+    app.set_journal_shift(
+        current
+            .map(|s| JournalShiftData {
+                id: s.id,
+                started: format_time(s.started_at).into(),
+                guards: SharedString::from(s.guards.as_str()),
+            })
+            .unwrap_or_default(),
+    );
+    app.set_journal_checkpoints(ModelRc::new(VecModel::from(checkpoint_data)));
+    app.set_journal_entries(ModelRc::new(VecModel::from(entries)));
+    app.set_journal_person_names(ModelRc::new(VecModel::from(person_names)));
+    app.set_journal_group_names(ModelRc::new(VecModel::from(group_names)));
+    app.set_journal_tags(tags_text(&view));
+}
+
+fn clear_tags(view: &RefCell<JournalView>) {
+    let mut view = view.borrow_mut();
+    view.tagged_persons.clear();
+    view.tagged_groups.clear();
+}
+
+fn tags_text(view: &JournalView) -> SharedString {
+    let names: Vec<&str> = view
+        .tagged_persons
+        .iter()
+        .chain(&view.tagged_groups)
+        .map(|(_, name)| name.as_str())
+        .collect();
+    SharedString::from(names.join(", "))
+}
+
+// The description, then the tagged persons and groups in brackets.
+fn incident_text(incident: &Incident) -> String {
+    let tags: Vec<String> = incident
+        .persons
+        .iter()
+        .map(full_name)
+        .chain(incident.groups.iter().map(|g| g.name.clone()))
+        .collect();
+    if tags.is_empty() {
+        incident.description.clone()
+    } else {
+        format!("{} [{}]", incident.description, tags.join(", "))
+    }
+}
+
+// The journal's day starts at noon: before it, "tonight" began yesterday.
+fn last_noon() -> DateTime<Utc> {
+    let now = Local::now();
+    let today = now.date_naive();
+    let day = if now.time() < NaiveTime::from_hms_opt(12, 0, 0).unwrap_or_default() {
+        today.checked_sub_days(Days::new(1)).unwrap_or(today)
+    } else {
+        today
+    };
+    noon(day).unwrap_or_else(|| now.with_timezone(&Utc))
+}
+
+fn noon(day: NaiveDate) -> Option<DateTime<Utc>> {
+    let noon = day.and_hms_opt(12, 0, 0)?;
+    Local.from_local_datetime(&noon).earliest().map(|t| t.with_timezone(&Utc))
+}
+
+fn severity_name(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Info => "Info",
+        Severity::Minor => "Minor",
+        Severity::Serious => "Serious",
+        Severity::Emergency => "Emergency",
+    }
+}
+
+// A page for the commandant: the shifts with their rounds and missed checkpoints, then the
+// incidents, most severe first.
+fn report_html(report: &MorningReport) -> String {
+    let title = html_escape(&format!("Sentry journal {}–{}", format_local(report.from), format_local(report.to)));
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>{title}</title>\n\
+         <style>body{{font-family:sans-serif}}table{{border-collapse:collapse}}td,th{{border:1px solid #000;padding:6px 10px;text-align:left}}\
+         .serious{{color:#b3261e}}.emergency{{color:#b3261e;font-weight:700}}</style>\n\
+         </head><body>\n<h1>{title}</h1>\n<h2>Shifts</h2>\n"
+    );
+    if report.shifts.is_empty() {
+        html.push_str("<p>No sentry shifts.</p>\n");
+    } else {
+        html.push_str("<table>\n<tr><th>Shift</th><th>Guards</th><th>Rounds</th><th>Missed checkpoints</th><th>Started by</th></tr>\n");
+        for shift in &report.shifts {
+            let end = shift.ended_at.map(format_local).unwrap_or_else(|| "in progress".to_string());
+            let visits: Vec<String> = shift
+                .visits
+                .iter()
+                .map(|v| format!("{} {}", format_time(v.visited_at), html_escape(&v.checkpoint)))
+                .collect();
+            let missed: Vec<String> = report.missed_checkpoints(shift).iter().map(|c| html_escape(&c.name)).collect();
+            html.push_str(&format!(
+                "<tr><td>{}–{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                format_local(shift.started_at),
+                end,
+                html_escape(&shift.guards),
+                visits.join("<br>"),
+                missed.join(", "),
+                html_escape(shift.operator.as_deref().unwrap_or("")),
+            ));
+        }
+        html.push_str("</table>\n");
+    }
+
+    html.push_str("<h2>Incidents</h2>\n");
+    if report.incidents.is_empty() {
+        html.push_str("<p>No incidents.</p>\n");
+    } else {
+        let mut incidents: Vec<&Incident> = report.incidents.iter().collect();
+        incidents.sort_by(|a, b| b.severity.cmp(&a.severity).then(a.occurred_at.cmp(&b.occurred_at)));
+        html.push_str("<table>\n<tr><th>Time</th><th>Severity</th><th>Description</th><th>Written by</th></tr>\n");
+        for incident in incidents {
+            let class = match incident.severity {
+                Severity::Emergency => " class=\"emergency\"",
+                Severity::Serious => " class=\"serious\"",
+                Severity::Info | Severity::Minor => "",
+            };
+            html.push_str(&format!(
+                "<tr{class}><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                format_local(incident.occurred_at),
+                severity_name(incident.severity),
+                html_escape(&incident_text(incident)),
+                html_escape(incident.operator.as_deref().unwrap_or("")),
+            ));
+        }
+        html.push_str("</table>\n");
+    }
+    html.push_str(&format!("<p><small>{}</small></p>\n</body></html>\n", format_local(Utc::now())));
+    html
+}
@@ -0,0 +1,72 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Days, Local, NaiveDateTime, NaiveTime, TimeZone, Utc};
+use slint::SharedString;
+
+use crate::db_operations::{LeaveDetails, LeaveReason, Repository};
+use crate::{LeavePassData, MainWindow, PersonData};
+
+use super::format::format_recent;
+use super::messages::{report_error, report_message};
+
+/// Fills `expected_return` of the persons out on a leave pass in event `event_id`.
+pub(super) fn set_expected_returns(app: &MainWindow, repo: &dyn Repository, event_id: i32, persons: &mut [PersonData]) {
+    let passes = match repo.open_leave_passes(event_id) {
+        Ok(passes) => passes,
+        Err(e) => {
+            report_error(app, "Error loading leave passes", &e);
+            return;
+        }
+    };
+    // Oldest first, so the newest pass of a person wins.
+    let expected: HashMap<i32, DateTime<Utc>> = passes
+        .into_iter()
+        .filter_map(|p| Some((p.person_id, p.details.expected_return?)))
+        .collect();
+    for person in persons.iter_mut() {
+        if let Some(&time) = expected.get(&person.id) {
+            person.expected_return = SharedString::from(format_recent(time));
+        }
+    }
+}
+
+/// The pass to write for the GET_OUT form; `Some(None)` for an empty form (no pass), `None`
+/// (and an error banner) when the return time can't be read.
+pub(super) fn details_from_data(app: &MainWindow, data: &LeavePassData) -> Option<Option<LeaveDetails>> {
+    let text = data.expected_return.trim();
+    let expected_return = if text.is_empty() {
+        None
+    } else {
+        match parse_expected_return(text, Local::now()) {
+            Some(time) => Some(time),
+            None => {
+                report_message(app, &format!("Invalid return time '{text}', use HH:MM or YYYY-MM-DD HH:MM."));
+                return None;
+            }
+        }
+    };
+    let details = LeaveDetails {
+        destination: data.destination.trim().to_string(),
+        reason: LeaveReason::try_from(data.reason).unwrap_or_default(),
+        companion: data.companion.trim().to_string(),
+        expected_return,
+    };
+    Some((details != LeaveDetails::default()).then_some(details))
+}
+
+// "YYYY-MM-DD HH:MM", or "HH:MM" for the next time the local clock shows it after `now`.
+fn parse_expected_return(text: &str, now: DateTime<Local>) -> Option<DateTime<Utc>> {
+    let local = match NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M") {
+        Ok(time) => Local.from_local_datetime(&time).earliest()?,
+        Err(_) => {
+            let time = NaiveTime::parse_from_str(text, "%H:%M").ok()?;
+            let today = now.date_naive();
+            [Some(today), today.checked_add_days(Days::new(1))]
+                .into_iter()
+                .flatten()
+                .filter_map(|day| Local.from_local_datetime(&day.and_time(time)).earliest())
+                .find(|&t| t > now)?
+        }
+    };
+    Some(local.with_timezone(&Utc))
+}
@@ -0,0 +1,363 @@
+use std::{
+    cell::RefCell,
+    rc::Rc,
+};
+
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use slint::{ComponentHandle, ModelRc, SharedString, VecModel};
+
+use crate::{GroupData, LogData, LogDayGroupData, LogMemberData, LogMinuteGroupData, MainWindow, PersonData};
+
+use crate::db_operations::{self, LogCursor, LogEntry, LogFilter, Repository};
+
+use super::messages::{report_error, report_message};
+
+/// `MainWindow::current_screen` of the logs screen.
+pub(super) const LOGS_SCREEN: i32 = 2;
+
+/// Top-level entries fetched per "load more".
+const LOG_PAGE_SIZE: usize = 50;
+
+/// Logs screen state: the active filter and the pages loaded so far.
+#[derive(Default)]
+pub(super) struct LogView {
+    filter: LogFilter,
+    entries: Vec<LogEntry>,
+    next: Option<LogCursor>,
+    // Ids behind the filter ComboBoxes; index 0 is "all".
+    person_ids: Vec<Option<i32>>,
+    group_ids: Vec<Option<i32>>,
+}
+
+/// Refills the person/group filter ComboBoxes, keeping the current choice selected.
+pub(super) fn set_filter_options(app: &MainWindow, view: &RefCell<LogView>, persons: &[PersonData], groups: &[GroupData]) {
+    let mut view = view.borrow_mut();
+
+    let mut person_names = vec![SharedString::from("—")];
+    view.person_ids = vec![None];
+    for p in persons {
+        person_names.push(SharedString::from(format!("{} {}", p.surname, p.name)));
+        view.person_ids.push(Some(p.id));
+    }
+
+    let mut group_names = vec![SharedString::from("—")];
+    view.group_ids = vec![None];
+    for g in groups {
+        group_names.push(g.name.clone());
+        view.group_ids.push(Some(g.id));
+    }
+
+    let person_index = view.person_ids.iter().position(|id| *id == view.filter.person_id).unwrap_or(0);
+    let group_index = view.group_ids.iter().position(|id| *id == view.filter.group_id).unwrap_or(0);
+
+    app.set_log_filter_persons(ModelRc::new(VecModel::from(person_names)));
+    app.set_log_filter_groups(ModelRc::new(VecModel::from(group_names)));
+    app.set_log_filter_person_index(person_index as i32);
+    app.set_log_filter_group_index(group_index as i32);
+}
+
+/// Drops loaded pages and fetches the first one for the current filter.
+pub(super) fn reload_logs(app: &MainWindow, repo: &dyn Repository, view: &RefCell<LogView>) {
+    let mut view = view.borrow_mut();
+
+    // Logs follow the active event, like the main screen.
+    view.filter.event_id = match repo.active_event() {
+        Ok(event_id) => event_id,
+        Err(e) => {
+            report_error(app, "Error loading the active event", &e);
+            None
+        }
+    };
+
+    match repo.log_page(&view.filter, None, LOG_PAGE_SIZE) {
+        Ok(page) => {
+            view.entries = page.entries;
+            view.next = page.next;
+        }
+        Err(e) => {
+            report_error(app, "Error loading logs", &e);
+            view.entries.clear();
+            view.next = None;
+        }
+    }
+
+    set_logs_model(app, &view);
+}
+
+pub(super) fn wire_log_requests(app: &MainWindow, repo: Rc<dyn Repository>, view: Rc<RefCell<LogView>>) {
+    let app_weak = app.as_weak();
+    let (opened_repo, opened_view) = (repo.clone(), view.clone());
+    app.on_logs_opened(move || {
+        let Some(app) = app_weak.upgrade() else {
+            return;
+        };
+        reload_logs(&app, opened_repo.as_ref(), &opened_view);
+    });
+
+    let app_weak = app.as_weak();
+    let (filter_repo, filter_view) = (repo.clone(), view.clone());
+    app.on_logs_filter_changed(move |from, to, person_index, group_index, direction, operator| {
+        let Some(app) = app_weak.upgrade() else {
+            return;
+        };
+
+        let (Some(from), Some(to)) = (parse_day_start(&app, &from, 0), parse_day_start(&app, &to, 1)) else {
+            return;
+        };
+
+        {
+            let mut view = filter_view.borrow_mut();
+            let person_id = view.person_ids.get(person_index as usize).copied().flatten();
+            let group_id = view.group_ids.get(group_index as usize).copied().flatten();
+            let operator = operator.trim();
+            view.filter = LogFilter {
+                event_id: view.filter.event_id,
+                from,
+                to,
+                person_id,
+                group_id,
+                is_inside: match direction {
+                    1 => Some(db_operations::IsInside::In),
+                    2 => Some(db_operations::IsInside::Out),
+                    _ => None,
+                },
+                operator: (!operator.is_empty()).then(|| operator.to_string()),
+            };
+        }
+
+        reload_logs(&app, filter_repo.as_ref(), &filter_view);
+    });
+
+    let app_weak = app.as_weak();
+    let inside_repo = repo.clone();
+    app.on_inside_at_requested(move |text| {
+        let Some(app) = app_weak.upgrade() else {
+            return false;
+        };
+        let Some(at) = parse_local_time(&app, &text) else {
+            return false;
+        };
+        let event_id = match inside_repo.active_event() {
+            Ok(Some(event_id)) => event_id,
+            Ok(None) => {
+                report_message(&app, "Choose an active event first.");
+                return false;
+            }
+            Err(e) => {
+                report_error(&app, "Error loading the active event", &e);
+                return false;
+            }
+        };
+
+        let states = match inside_repo.presence_at(event_id, at) {
+            Ok(states) => states,
+            Err(e) => {
+                report_error(&app, "Error reading presence from the log", &e);
+                return false;
+            }
+        };
+        // Shown as the log row that put them inside describes them.
+        let mut inside: Vec<LogMemberData> = states
+            .iter()
+            .filter(|s| s.is_inside == db_operations::IsInside::In)
+            .filter_map(|s| s.last.as_ref().map(log_to_member_data))
+            .collect();
+        inside.sort_by(|a, b| a.surname.cmp(&b.surname).then_with(|| a.name.cmp(&b.name)));
+        app.set_inside_at_persons(ModelRc::new(VecModel::from(inside)));
+        true
+    });
+
+    let app_weak = app.as_weak();
+    app.on_logs_load_more(move || {
+        let Some(app) = app_weak.upgrade() else {
+            return;
+        };
+
+        let mut view = view.borrow_mut();
+        let Some(after) = view.next else {
+            return;
+        };
+
+        match repo.log_page(&view.filter, Some(after), LOG_PAGE_SIZE) {
+            Ok(page) => {
+                view.entries.extend(page.entries);
+                view.next = page.next;
+            }
+            Err(e) => {
+                report_error(&app, "Error loading logs", &e);
+                return;
+            }
+        }
+
+        set_logs_model(&app, &view);
+    });
+}
+
+// "YYYY-MM-DD" in local time -> start of that day plus `days_after`, in UTC. Empty means no bound.
+// `None` (and an error banner) when the text can't be parsed.
+fn parse_day_start(app: &MainWindow, text: &str, days_after: u64) -> Option<Option<DateTime<Utc>>> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Some(None);
+    }
+
+    let start = NaiveDate::parse_from_str(text, "%Y-%m-%d")
+        .ok()
+        .and_then(|d| d.checked_add_days(chrono::Days::new(days_after)))
+        .and_then(|d| Local.from_local_datetime(&d.and_time(chrono::NaiveTime::MIN)).earliest());
+
+    match start {
+        Some(start) => Some(Some(start.with_timezone(&Utc))),
+        None => {
+            report_message(app, &format!("Invalid date '{text}', use YYYY-MM-DD."));
+            None
+        }
+    }
+}
+
+// "YYYY-MM-DD HH:MM" in local time -> UTC, counting the whole minute (the log has seconds).
+// `None` (and an error banner) when the text can't be parsed.
+fn parse_local_time(app: &MainWindow, text: &str) -> Option<DateTime<Utc>> {
+    let text = text.trim();
+    let time = NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M")
+        .ok()
+        .and_then(|t| Local.from_local_datetime(&t).latest())
+        .map(|t| t.with_timezone(&Utc) + chrono::Duration::seconds(59));
+
+    if time.is_none() {
+        report_message(app, &format!("Invalid time '{text}', use YYYY-MM-DD HH:MM."));
+    }
+    time
+}
+
+// Groups the loaded entries into day / minute folders for the logs screen.
+fn set_logs_model(app: &MainWindow, view: &LogView) {
+    let mut day_groups: Vec<LogDayGroupData> = Vec::new();
+    let mut current_day: Option<SharedString> = None;
+    let mut current_minutes: Vec<LogMinuteGroupData> = Vec::new();
+    let mut current_minute: Option<SharedString> = None;
+    let mut current_entries: Vec<LogData> = Vec::new();
+
+    let flush_minute = |minute: Option<SharedString>, minutes: &mut Vec<LogMinuteGroupData>, entries: &mut Vec<LogData>| {
+        if let Some(m) = minute
+            && !entries.is_empty()
+        {
+            minutes.push(LogMinuteGroupData {
+                minute: m,
+                entries: ModelRc::new(VecModel::from(std::mem::take(entries))),
+            });
+        }
+    };
+
+    let flush_day = |day: Option<SharedString>, days: &mut Vec<LogDayGroupData>, minutes: &mut Vec<LogMinuteGroupData>| {
+        if let Some(d) = day
+            && !minutes.is_empty()
+        {
+            days.push(LogDayGroupData {
+                day: d,
+                minutes: ModelRc::new(VecModel::from(std::mem::take(minutes))),
+            });
+        }
+    };
+
+    for entry in &view.entries {
+        let local_time = entry.log().time.with_timezone(&Local);
+        let day = SharedString::from(local_time.format("%Y-%m-%d").to_string());
+        let minute = SharedString::from(local_time.format("%H:%M").to_string());
+        let seconds = SharedString::from(local_time.format("%H:%M:%S").to_string());
+
+        match &current_day {
+            Some(cur) if *cur == day => {}
+            Some(_) => {
+                flush_minute(current_minute.take(), &mut current_minutes, &mut current_entries);
+                flush_day(current_day.take(), &mut day_groups, &mut current_minutes);
+                current_day = Some(day.clone());
+                current_minute = None;
+            }
+            None => {
+                current_day = Some(day.clone());
+            }
+        }
+
+        match &current_minute {
+            Some(cur) if *cur == minute => {}
+            Some(_) => {
+                flush_minute(current_minute.take(), &mut current_minutes, &mut current_entries);
+                current_minute = Some(minute.clone());
+            }
+            None => {
+                current_minute = Some(minute.clone());
+            }
+        }
+
+        current_entries.push(log_entry_to_log_data(entry, seconds));
+    }
+
+    flush_minute(current_minute.take(), &mut current_minutes, &mut current_entries);
+    flush_day(current_day.take(), &mut day_groups, &mut current_minutes);
+
+    app.set_logs(ModelRc::new(VecModel::from(day_groups)));
+    app.set_logs_has_more(view.next.is_some());
+}
+
+fn log_entry_to_log_data(entry: &db_operations::LogEntry, timestamp: SharedString) -> LogData {
+    match entry {
+        db_operations::LogEntry::Person(l) => {
+            let m = log_to_member_data(l);
+            LogData {
+                id: m.id,
+                name: m.name,
+                surname: m.surname,
+                rank: m.rank,
+                methodology: m.methodology,
+                groups: m.groups,
+                is_in: l.is_inside == db_operations::IsInside::In,
+                operator: SharedString::from(l.operator.as_deref().unwrap_or_default()),
+                timestamp,
+                is_group: false,
+                member_count: 0,
+                members: ModelRc::default(),
+            }
+        }
+        db_operations::LogEntry::Group { log, group_name, members } => LogData {
+            id: log.entity_id,
+            name: SharedString::from(group_name.as_str()),
+            surname: SharedString::new(),
+            rank: SharedString::new(),
+            methodology: slint::Color::from_rgb_u8(128, 128, 128),
+            groups: SharedString::new(),
+            is_in: log.is_inside == db_operations::IsInside::In,
+            operator: SharedString::from(log.operator.as_deref().unwrap_or_default()),
+            timestamp,
+            is_group: true,
+            member_count: members.len() as i32,
+            members: ModelRc::new(VecModel::from(
+                members.iter().map(log_to_member_data).collect::<Vec<_>>(),
+            )),
+        },
+    }
+}
+
+// Logs are rendered from their own snapshot, never from the current roster,
+// so renames, rank changes and purges don't rewrite history.
+fn log_to_member_data(l: &db_operations::Log) -> LogMemberData {
+    match &l.snapshot {
+        Some(s) => LogMemberData {
+            id: l.entity_id,
+            name: SharedString::from(s.name.as_str()),
+            surname: SharedString::from(s.surname.as_str()),
+            rank: SharedString::from(s.rank_level.as_str()),
+            methodology: s.methodology.as_color(),
+            groups: SharedString::from(s.groups.join(", ")),
+        },
+        // Pre-snapshot row of a person deleted before the upgrade: only the id is left.
+        None => LogMemberData {
+            id: l.entity_id,
+            name: SharedString::from(format!("#{}", l.entity_id)),
+            surname: SharedString::new(),
+            rank: SharedString::new(),
+            methodology: slint::Color::from_rgb_u8(128, 128, 128),
+            groups: SharedString::new(),
+        },
+    }
+}
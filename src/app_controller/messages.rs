@@ -0,0 +1,46 @@
+use slint::SharedString;
+
+use crate::db_operations::DbError;
+use crate::MainWindow;
+
+// Wording shown in the error banner. The technical `Display` text still goes to stderr.
+pub(super) fn user_message(e: &DbError) -> String {
+    match e {
+        DbError::NotFound { entity, .. } => format!("This {entity} no longer exists."),
+        DbError::Duplicate(_) => "This entry already exists.".to_string(),
+        DbError::ConstraintViolation(_) => "The change conflicts with existing data and was not saved.".to_string(),
+        DbError::InvalidEnumValue { .. } | DbError::Corrupt(_) => {
+            "The database contains damaged data. Restore a backup or contact the administrator.".to_string()
+        }
+        DbError::SchemaTooNew { .. } => {
+            "This database was created by a newer version of eWartownik. Please update the app.".to_string()
+        }
+        DbError::BatchFailed(failures) => format!(
+            "Nothing was saved: {} of the selected people could not be updated.",
+            failures.len()
+        ),
+        DbError::PassphraseRequired => "This camp is encrypted. Enter its passphrase.".to_string(),
+        DbError::WrongPassphrase => "Wrong passphrase.".to_string(),
+        DbError::EncryptionUnavailable => {
+            "This camp is encrypted, but this copy of eWartownik was built without encryption support.".to_string()
+        }
+        DbError::Io { path, .. } => format!("Could not access the file {}. Check the disk and its permissions.", path.display()),
+        DbError::Sqlite(_) => "Database error. The change was not saved.".to_string(),
+    }
+}
+
+// Input problems caught before reaching the database.
+pub(super) fn report_message(app: &MainWindow, message: &str) {
+    eprintln!("{message}");
+    app.set_error_message(SharedString::from(message));
+}
+
+pub(super) fn report_error(app: &MainWindow, context: &str, e: &DbError) {
+    eprintln!("{context}: {e}");
+    if let DbError::BatchFailed(failures) = e {
+        for (id, reason) in failures {
+            eprintln!("  id {id}: {reason}");
+        }
+    }
+    app.set_error_message(SharedString::from(user_message(e)));
+}
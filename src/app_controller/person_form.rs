@@ -0,0 +1,139 @@
+use std::rc::Rc;
+
+use chrono::NaiveDate;
+use slint::{ComponentHandle, SharedString};
+
+use crate::{GuardianData, MainWindow, PersonProfileData};
+
+use crate::db_operations::{self, Repository};
+
+use super::events::parse_date;
+use super::handlers::{parse_methodology, parse_rank};
+use super::messages::{report_error, report_message};
+
+/// Profile form on the Persons screen: `load_person_profile` fills it, `save_person_profile`
+/// writes it back. Sensitive fields are only shown there after the user asks for them.
+pub(super) fn wire_person_profile_requests(
+    app: &MainWindow,
+    repo: Rc<dyn Repository>,
+    refresh_groups: impl Fn() + Clone + 'static,
+) {
+    let app_weak = app.as_weak();
+    let load_repo = repo.clone();
+    app.on_load_person_profile(move |person_id| {
+        let Some(app) = app_weak.upgrade() else {
+            return PersonProfileData::default();
+        };
+        match load_repo.person(person_id) {
+            Ok(person) => person_to_profile_data(&person),
+            Err(e) => {
+                report_error(&app, "Error loading person", &e);
+                PersonProfileData::default()
+            }
+        }
+    });
+
+    let app_weak = app.as_weak();
+    app.on_save_person_profile(move |data| {
+        let Some(app) = app_weak.upgrade() else {
+            return false;
+        };
+
+        let (name, surname) = (data.name.trim(), data.surname.trim());
+        if name.is_empty() || surname.is_empty() {
+            report_message(&app, "A person needs a name and a surname.");
+            return false;
+        }
+        let (Some(rank_level), Some(methodology)) = (parse_rank(data.rank), parse_methodology(data.methodology)) else {
+            eprintln!("Invalid rank or methodology value: {} / {}", data.rank, data.methodology);
+            return false;
+        };
+        let Some(date_of_birth) = parse_date(&app, &data.date_of_birth) else {
+            return false;
+        };
+        if date_of_birth.is_some_and(|d| d > chrono::Local::now().date_naive()) {
+            report_message(&app, "The date of birth can't be in the future.");
+            return false;
+        }
+
+        let existing = match repo.person(data.id) {
+            Ok(person) => person,
+            Err(e) => {
+                report_error(&app, "Error loading person", &e);
+                return false;
+            }
+        };
+
+        // The form edits the first two guardians; any further ones are kept.
+        let mut guardians: Vec<db_operations::Guardian> = [&data.guardian1, &data.guardian2]
+            .into_iter()
+            .map(guardian_from_data)
+            .filter(|g| *g != db_operations::Guardian::default())
+            .collect();
+        guardians.extend(existing.guardians.iter().skip(2).cloned());
+
+        let person = db_operations::Person {
+            name: name.to_string(),
+            surname: surname.to_string(),
+            nickname: data.nickname.trim().to_string(),
+            rank_level,
+            methodology,
+            patrol: data.patrol.trim().to_string(),
+            date_of_birth,
+            address: data.address.trim().to_string(),
+            guardians,
+            allergies: data.allergies.trim().to_string(),
+            diet: data.diet.trim().to_string(),
+            medical_notes: data.medical_notes.trim().to_string(),
+            note: data.note.trim().to_string(),
+            ..existing
+        };
+
+        if let Err(e) = repo.update_person(&person) {
+            report_error(&app, "Error updating person", &e);
+            return false;
+        }
+        refresh_groups();
+        true
+    });
+}
+
+fn person_to_profile_data(p: &db_operations::Person) -> PersonProfileData {
+    let guardian = |i: usize| p.guardians.get(i).map(guardian_to_data).unwrap_or_default();
+    let date = |d: Option<NaiveDate>| d.map(|d| SharedString::from(d.format("%Y-%m-%d").to_string())).unwrap_or_default();
+    PersonProfileData {
+        id: p.id,
+        name: SharedString::from(p.name.as_str()),
+        surname: SharedString::from(p.surname.as_str()),
+        nickname: SharedString::from(p.nickname.as_str()),
+        rank: p.rank_level as i32,
+        methodology: p.methodology as i32,
+        patrol: SharedString::from(p.patrol.as_str()),
+        date_of_birth: date(p.date_of_birth),
+        address: SharedString::from(p.address.as_str()),
+        guardian1: guardian(0),
+        guardian2: guardian(1),
+        allergies: SharedString::from(p.allergies.as_str()),
+        diet: SharedString::from(p.diet.as_str()),
+        medical_notes: SharedString::from(p.medical_notes.as_str()),
+        note: SharedString::from(p.note.as_str()),
+    }
+}
+
+fn guardian_to_data(g: &db_operations::Guardian) -> GuardianData {
+    GuardianData {
+        name: SharedString::from(g.name.as_str()),
+        relation: SharedString::from(g.relation.as_str()),
+        phone: SharedString::from(g.phone.as_str()),
+        email: SharedString::from(g.email.as_str()),
+    }
+}
+
+fn guardian_from_data(g: &GuardianData) -> db_operations::Guardian {
+    db_operations::Guardian {
+        name: g.name.trim().to_string(),
+        relation: g.relation.trim().to_string(),
+        phone: g.phone.trim().to_string(),
+        email: g.email.trim().to_string(),
+    }
+}
@@ -0,0 +1,230 @@
+use std::fs;
+use std::rc::Rc;
+
+use chrono::Local;
+use slint::{ComponentHandle, SharedString};
+
+use crate::MainWindow;
+
+use crate::db_operations::{self, BackupReason, PersonExport, Repository};
+
+use super::backups::Backups;
+use super::events::parse_days;
+use super::format::{format_local, full_name, html_escape};
+use super::messages::{report_error, report_message};
+
+/// Settings screen retention values; called on every refresh so the due count stays current.
+pub(super) fn set_privacy_models(app: &MainWindow, repo: &dyn Repository) {
+    match repo.retention_days() {
+        Ok(days) => app.set_retention_days(days.map(|d| SharedString::from(d.to_string())).unwrap_or_default()),
+        Err(e) => report_error(app, "Error loading the retention period", &e),
+    }
+    match repo.persons_past_retention(Local::now().date_naive()) {
+        Ok(due) => app.set_retention_due_count(due.len() as i32),
+        Err(e) => report_error(app, "Error checking the retention period", &e),
+    }
+}
+
+/// Retention setting, anonymization (all due persons, or one from the profile form; the
+/// camp is backed up first and every backup deleted once it succeeded) and per-person
+/// exports, JSON and a printable HTML page, written under `data_dir()/exports` (plaintext,
+/// even for encrypted camps; the profile form says so).
+pub(super) fn wire_privacy_requests(
+    app: &MainWindow,
+    repo: Rc<dyn Repository>,
+    backups: Backups,
+    refresh_groups: impl Fn() + Clone + 'static,
+) {
+    let app_weak = app.as_weak();
+    let settings_repo = repo.clone();
+    let refresh = refresh_groups.clone();
+    app.on_save_retention_days(move |text| {
+        let Some(app) = app_weak.upgrade() else {
+            return false;
+        };
+        let Some(days) = parse_days(&app, &text) else {
+            return false;
+        };
+        if let Err(e) = settings_repo.set_retention_days(days) {
+            report_error(&app, "Error saving the retention period", &e);
+            return false;
+        }
+        refresh();
+        true
+    });
+
+    let app_weak = app.as_weak();
+    let due_repo = repo.clone();
+    let due_backups = backups.clone();
+    let refresh = refresh_groups.clone();
+    app.on_anonymize_due(move || {
+        let Some(app) = app_weak.upgrade() else {
+            return;
+        };
+        let due = match due_repo.persons_past_retention(Local::now().date_naive()) {
+            Ok(due) => due,
+            Err(e) => {
+                report_error(&app, "Error checking the retention period", &e);
+                return;
+            }
+        };
+        if due.is_empty() || !due_backups.take(&app, BackupReason::BeforeDelete) {
+            return;
+        }
+        let mut anonymized = 0;
+        for person in &due {
+            if let Err(e) = due_repo.anonymize_person(person.id) {
+                report_error(&app, "Error anonymizing person", &e);
+                break;
+            }
+            anonymized += 1;
+        }
+        if anonymized > 0 {
+            due_backups.delete_all(&app);
+        }
+        refresh();
+    });
+
+    let app_weak = app.as_weak();
+    let anonymize_repo = repo.clone();
+    app.on_anonymize_person_request(move |person_id| {
+        let Some(app) = app_weak.upgrade() else {
+            return;
+        };
+        if !backups.take(&app, BackupReason::BeforeDelete) {
+            return;
+        }
+        match anonymize_repo.anonymize_person(person_id) {
+            Ok(()) => backups.delete_all(&app),
+            Err(e) => report_error(&app, "Error anonymizing person", &e),
+        }
+        refresh_groups();
+    });
+
+    let app_weak = app.as_weak();
+    app.on_export_person_data(move |person_id| {
+        let Some(app) = app_weak.upgrade() else {
+            return SharedString::new();
+        };
+        let export = match repo.export_person(person_id) {
+            Ok(export) => export,
+            Err(e) => {
+                report_error(&app, "Error exporting person data", &e);
+                return SharedString::new();
+            }
+        };
+
+        let dir = db_operations::data_dir().join("exports");
+        let stem = format!("person-{person_id}-{}", Local::now().format("%Y%m%d-%H%M%S"));
+        let (json_path, html_path) = (dir.join(format!("{stem}.json")), dir.join(format!("{stem}.html")));
+        let written = serde_json::to_string_pretty(&export)
+            .map_err(std::io::Error::other)
+            .and_then(|text| {
+                fs::create_dir_all(&dir)?;
+                fs::write(&json_path, text)
+            })
+            .and_then(|()| fs::write(&html_path, export_html(&export)));
+        match written {
+            Ok(()) => SharedString::from(html_path.display().to_string()),
+            Err(e) => {
+                report_message(&app, &format!("Couldn't write the export to {}: {e}", dir.display()));
+                SharedString::new()
+            }
+        }
+    });
+}
+
+// A page to print or hand over: the profile, then groups, events, presence rows, leave
+// passes and incidents, with the same content as the JSON file.
+fn export_html(export: &PersonExport) -> String {
+    let person = &export.person;
+    let title = html_escape(&format!("{} – personal data", full_name(person)));
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>{title}</title>\n\
+         <style>body{{font-family:sans-serif}}table{{border-collapse:collapse;margin-bottom:1em}}td,th{{border:1px solid #000;padding:4px 8px;text-align:left}}</style>\n\
+         </head><body>\n<h1>{title}</h1>\n"
+    );
+
+    let guardians: Vec<String> = person
+        .guardians
+        .iter()
+        .map(|g| [g.name.as_str(), g.relation.as_str(), g.phone.as_str(), g.email.as_str()].join(", "))
+        .collect();
+    let date_of_birth = person.date_of_birth.map(|d| d.to_string()).unwrap_or_default();
+    let archived = person.archived_at.map(format_local).unwrap_or_default();
+    let anonymized = person.anonymized_at.map(format_local).unwrap_or_default();
+    let profile = [
+        ("Id", person.id.to_string()),
+        ("Name", person.name.clone()),
+        ("Surname", person.surname.clone()),
+        ("Nickname", person.nickname.clone()),
+        ("Rank", person.rank_level.as_str().to_string()),
+        ("Methodology", format!("{:?}", person.methodology)),
+        ("Patrol", person.patrol.clone()),
+        ("Date of birth", date_of_birth),
+        ("Address", person.address.clone()),
+        ("Guardians", guardians.join("; ")),
+        ("Allergies", person.allergies.clone()),
+        ("Diet", person.diet.clone()),
+        ("Medical notes", person.medical_notes.clone()),
+        ("Note", person.note.clone()),
+        ("Archived", archived),
+        ("Anonymized", anonymized),
+    ];
+    html.push_str("<table>\n");
+    for (label, value) in profile {
+        html.push_str(&format!("<tr><th>{label}</th><td>{}</td></tr>\n", html_escape(&value)));
+    }
+    html.push_str("</table>\n");
+
+    let groups: Vec<String> = export.groups.iter().map(|g| html_escape(g)).collect();
+    html.push_str(&format!("<h2>Groups</h2>\n<p>{}</p>\n", groups.join(", ")));
+
+    html.push_str("<h2>Events</h2>\n<table>\n<tr><th>Event</th><th>Presence</th></tr>\n");
+    for p in &export.events {
+        html.push_str(&format!("<tr><td>{}</td><td>{:?}</td></tr>\n", html_escape(&p.event.name), p.is_inside));
+    }
+    html.push_str("</table>\n");
+
+    html.push_str("<h2>Presence</h2>\n<table>\n<tr><th>Time</th><th>Direction</th><th>Operator</th></tr>\n");
+    for log in &export.logs {
+        html.push_str(&format!(
+            "<tr><td>{}</td><td>{:?}</td><td>{}</td></tr>\n",
+            format_local(log.time),
+            log.is_inside,
+            html_escape(log.operator.as_deref().unwrap_or("")),
+        ));
+    }
+    html.push_str("</table>\n");
+
+    html.push_str(
+        "<h2>Leave passes</h2>\n<table>\n\
+         <tr><th>Left</th><th>Destination</th><th>Reason</th><th>Companion</th><th>Expected back</th><th>Returned</th></tr>\n",
+    );
+    for pass in &export.leave_passes {
+        html.push_str(&format!(
+            "<tr><td>{}</td><td>{}</td><td>{:?}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+            format_local(pass.left),
+            html_escape(&pass.details.destination),
+            pass.details.reason,
+            html_escape(&pass.details.companion),
+            pass.details.expected_return.map(format_local).unwrap_or_default(),
+            pass.returned.map(format_local).unwrap_or_default(),
+        ));
+    }
+    html.push_str("</table>\n");
+
+    html.push_str("<h2>Incidents</h2>\n<table>\n<tr><th>Time</th><th>Severity</th><th>Description</th></tr>\n");
+    for incident in &export.incidents {
+        html.push_str(&format!(
+            "<tr><td>{}</td><td>{:?}</td><td>{}</td></tr>\n",
+            format_local(incident.occurred_at),
+            incident.severity,
+            html_escape(&incident.description),
+        ));
+    }
+    html.push_str("</table>\n");
+
+    html.push_str(&format!("<p><small>{}</small></p>\n</body></html>\n", format_local(export.exported_at)));
+    html
+}
@@ -0,0 +1,276 @@
+use std::{
+    cell::RefCell,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use rusqlite::Connection;
+use slint::{ComponentHandle, ModelRc, SharedString, VecModel};
+
+use crate::db_operations::{self, DbError, DbResult};
+use crate::settings::{self, Settings};
+use crate::{MainWindow, ProfileChooser, ProfileData};
+
+use super::backups::Backups;
+use super::format::format_local;
+use super::messages::{report_error, report_message, user_message};
+
+/// A camp database opened at startup.
+pub struct OpenedCamp {
+    pub path: PathBuf,
+    pub conn: Connection,
+    /// What unlocked it; `None` for plaintext camps.
+    pub passphrase: Option<String>,
+}
+
+/// Shows the startup camp chooser until a database opens successfully.
+/// `locked` is an encrypted camp picked before (CLI or default): it is filled in, waiting
+/// for the passphrase. `Ok(None)` when the user closes the window without choosing.
+pub fn choose_camp(
+    settings: &mut Settings,
+    error: Option<String>,
+    locked: Option<PathBuf>,
+) -> Result<Option<OpenedCamp>, slint::PlatformError> {
+    let chooser = ProfileChooser::new()?;
+    let settings_cell = Rc::new(RefCell::new(std::mem::take(settings)));
+    let chosen: Rc<RefCell<Option<OpenedCamp>>> = Rc::new(RefCell::new(None));
+
+    chooser.set_profiles(profile_models(&startup_profiles(&settings_cell.borrow()), None));
+    let error = match &locked {
+        Some(path) => {
+            chooser.set_file_path(SharedString::from(path.display().to_string()));
+            Some(user_message(&DbError::PassphraseRequired))
+        }
+        None => error,
+    };
+    chooser.set_error_message(SharedString::from(error.unwrap_or_default()));
+
+    let try_open = {
+        let chooser_weak = chooser.as_weak();
+        let settings_cell = settings_cell.clone();
+        let chosen = chosen.clone();
+        move |name: Option<String>, path: PathBuf, passphrase: SharedString| {
+            let Some(chooser) = chooser_weak.upgrade() else {
+                return;
+            };
+            let mut settings = settings_cell.borrow_mut();
+            match open_camp(&mut settings, name.as_deref(), &path, &passphrase) {
+                Ok(conn) => {
+                    if chooser.get_open_at_startup() {
+                        settings.default_db = Some(path.clone());
+                        save_settings(&settings);
+                    }
+                    *chosen.borrow_mut() = Some(OpenedCamp {
+                        passphrase: camp_passphrase(&path, &passphrase),
+                        path,
+                        conn,
+                    });
+                    let _ = chooser.hide();
+                }
+                Err(e) => {
+                    eprintln!("Error opening camp {}: {e}", path.display());
+                    chooser.set_error_message(SharedString::from(user_message(&e)));
+                }
+            }
+        }
+    };
+
+    let open = try_open.clone();
+    chooser.on_open_profile(move |path, passphrase| open(None, PathBuf::from(path.as_str()), passphrase));
+    let open = try_open.clone();
+    chooser.on_open_path(move |path, passphrase| open(None, PathBuf::from(path.trim()), passphrase));
+    let chooser_weak = chooser.as_weak();
+    chooser.on_create_profile(move |name, passphrase| match settings::new_profile_path(&name) {
+        Some(path) => try_open(Some(name.trim().to_string()), path, passphrase),
+        None => {
+            if let Some(chooser) = chooser_weak.upgrade() {
+                chooser.set_error_message(SharedString::from(INVALID_CAMP_NAME));
+            }
+        }
+    });
+
+    chooser.run()?;
+
+    *settings = settings_cell.take();
+    Ok(chosen.take())
+}
+
+/// Settings screen: current camp, switching and creating camps, default camp, encryption.
+///
+/// Switching (and encrypting) replaces the connection inside `conn`, so every
+/// `SqliteRepository` sharing it follows along; `on_switched` then resets UI state and reloads.
+/// `on_opened` runs after that for the new camp (backup and integrity check).
+pub(super) fn wire_camp_requests(
+    app: &MainWindow,
+    conn: Rc<RefCell<Connection>>,
+    settings: Rc<RefCell<Settings>>,
+    current: Rc<RefCell<PathBuf>>,
+    backups: Backups,
+    on_switched: impl Fn() + Clone + 'static,
+    on_opened: impl Fn() + Clone + 'static,
+) {
+    set_camp_properties(app, &settings.borrow(), &current.borrow());
+
+    let switch = {
+        let app_weak = app.as_weak();
+        let settings = settings.clone();
+        let current = current.clone();
+        let conn = conn.clone();
+        let backups = backups.clone();
+        move |name: Option<String>, path: PathBuf, passphrase: SharedString| {
+            let Some(app) = app_weak.upgrade() else {
+                return;
+            };
+            if path == *current.borrow() {
+                return;
+            }
+
+            let opened = open_camp(&mut settings.borrow_mut(), name.as_deref(), &path, &passphrase);
+            match opened {
+                Ok(new_conn) => {
+                    *conn.borrow_mut() = new_conn;
+                    backups.set_passphrase(camp_passphrase(&path, &passphrase));
+                    *current.borrow_mut() = path;
+                }
+                Err(e) => {
+                    report_error(&app, &format!("Error opening camp {}", path.display()), &e);
+                    return;
+                }
+            }
+
+            set_camp_properties(&app, &settings.borrow(), &current.borrow());
+            on_switched();
+            on_opened();
+        }
+    };
+
+    let open = switch.clone();
+    app.on_switch_camp(move |path, passphrase| open(None, PathBuf::from(path.trim()), passphrase));
+
+    let app_weak = app.as_weak();
+    app.on_create_camp(move |name, passphrase| match settings::new_profile_path(&name) {
+        Some(path) => switch(Some(name.trim().to_string()), path, passphrase),
+        None => {
+            if let Some(app) = app_weak.upgrade() {
+                report_message(&app, INVALID_CAMP_NAME);
+            }
+        }
+    });
+
+    let app_weak = app.as_weak();
+    let (default_settings, default_current) = (settings.clone(), current.clone());
+    app.on_set_default_camp(move |is_default| {
+        let Some(app) = app_weak.upgrade() else {
+            return;
+        };
+        let mut settings = default_settings.borrow_mut();
+        settings.default_db = is_default.then(|| default_current.borrow().clone());
+        save_settings(&settings);
+        set_camp_properties(&app, &settings, &default_current.borrow());
+    });
+
+    // Encrypts a plaintext camp, or changes the passphrase of an encrypted one.
+    let app_weak = app.as_weak();
+    app.on_set_passphrase(move |passphrase, repeated| {
+        let Some(app) = app_weak.upgrade() else {
+            return false;
+        };
+        if passphrase.is_empty() {
+            report_message(&app, "The passphrase can't be empty.");
+            return false;
+        }
+        if passphrase != repeated {
+            report_message(&app, "The passphrases don't match.");
+            return false;
+        }
+
+        let result = if db_operations::is_encrypted(&current.borrow()) {
+            db_operations::change_passphrase(&conn.borrow(), &passphrase).map(|()| Vec::new())
+        } else {
+            db_operations::encrypt_database(&mut conn.borrow_mut(), &passphrase)
+        };
+        let plaintext_copies = match result {
+            Ok(copies) => copies,
+            Err(e) => {
+                report_error(&app, "Error setting the passphrase", &e);
+                return false;
+            }
+        };
+        if !plaintext_copies.is_empty() {
+            let list: Vec<String> = plaintext_copies.iter().map(|p| p.display().to_string()).collect();
+            report_message(
+                &app,
+                &format!(
+                    "The camp is encrypted, but these backups couldn't be and are still readable without the passphrase. Delete them:\n{}",
+                    list.join("\n")
+                ),
+            );
+        }
+        backups.set_passphrase(Some(passphrase.to_string()));
+        set_camp_properties(&app, &settings.borrow(), &current.borrow());
+        true
+    });
+}
+
+const INVALID_CAMP_NAME: &str = "The camp name must contain at least one letter or digit.";
+
+// Opens the camp database and moves it to the top of the recent list. An empty `passphrase`
+// opens plaintext camps and creates new ones unencrypted.
+fn open_camp(settings: &mut Settings, name: Option<&str>, path: &Path, passphrase: &str) -> DbResult<Connection> {
+    let conn = db_operations::open_db(path, Some(passphrase))?;
+    let name = name.map(str::to_string).unwrap_or_else(|| settings.profile_name(path));
+    settings.touch_recent(&name, path);
+    save_settings(settings);
+    Ok(conn)
+}
+
+// Kept for backups, which must be encrypted like the camp; plaintext camps need none.
+fn camp_passphrase(path: &Path, passphrase: &str) -> Option<String> {
+    db_operations::is_encrypted(path).then(|| passphrase.to_string())
+}
+
+fn save_settings(settings: &Settings) {
+    if let Err(e) = settings.save() {
+        eprintln!("Couldn't save settings: {e}");
+    }
+}
+
+// Recent profiles, plus the pre-profiles database on first start after upgrading.
+fn startup_profiles(settings: &Settings) -> Vec<settings::CampProfile> {
+    let mut profiles = settings.recent.clone();
+    let legacy = db_operations::legacy_db_path();
+    if legacy.exists() && !profiles.iter().any(|p| p.path == legacy) {
+        profiles.push(settings::CampProfile {
+            name: "eWartownik".to_string(),
+            path: legacy,
+            last_opened: None,
+        });
+    }
+    profiles
+}
+
+fn set_camp_properties(app: &MainWindow, settings: &Settings, current: &Path) {
+    app.set_current_camp_name(SharedString::from(settings.profile_name(current)));
+    app.set_current_camp_path(SharedString::from(current.display().to_string()));
+    app.set_current_camp_is_default(settings.default_db.as_deref() == Some(current));
+    app.set_current_camp_encrypted(db_operations::is_encrypted(current));
+    app.set_encryption_available(db_operations::encryption_available());
+    app.set_camp_profiles(profile_models(&settings.recent, Some(current)));
+}
+
+fn profile_models(profiles: &[settings::CampProfile], current: Option<&Path>) -> ModelRc<ProfileData> {
+    let models: Vec<ProfileData> = profiles
+        .iter()
+        .map(|p| ProfileData {
+            name: SharedString::from(p.name.as_str()),
+            path: SharedString::from(p.path.display().to_string()),
+            last_opened: p
+                .last_opened
+                .map(|t| SharedString::from(format_local(t)))
+                .unwrap_or_default(),
+            // The open database is there even if `exists()` can't see it (e.g. permissions).
+            exists: Some(p.path.as_path()) == current || p.path.exists(),
+        })
+        .collect();
+    ModelRc::new(VecModel::from(models))
+}
@@ -0,0 +1,191 @@
+use std::rc::Rc;
+
+use slint::{ComponentHandle, ModelRc, SharedString, VecModel};
+
+use crate::db_operations::{IsInside, Repository, RollCall, RollCallDiscrepancy, RollCallEntry};
+use crate::{MainWindow, RollCallData, RollCallDiscrepancyData, RollCallEntryData, RollCallPatrolData};
+
+use super::events::active_event_or_report;
+use super::format::format_local;
+use super::handlers::operator_name;
+use super::messages::report_error;
+
+/// Roll call modal on the main screen. Fixing a discrepancy changes presence, so it
+/// refreshes the main screen lists too.
+pub(super) fn wire_roll_call_requests(app: &MainWindow, repo: Rc<dyn Repository>, refresh_groups: impl Fn() + Clone + 'static) {
+    let app_weak = app.as_weak();
+    let open_repo = repo.clone();
+    app.on_roll_call_opened(move || {
+        if let Some(app) = app_weak.upgrade() {
+            show_latest(&app, open_repo.as_ref());
+        }
+    });
+
+    let app_weak = app.as_weak();
+    let start_repo = repo.clone();
+    app.on_start_roll_call(move || {
+        let Some(app) = app_weak.upgrade() else {
+            return;
+        };
+        let Some(event_id) = active_event_or_report(&app, start_repo.as_ref()) else {
+            return;
+        };
+        if let Err(e) = start_repo.start_roll_call(event_id, operator_name(&app).as_deref()) {
+            report_error(&app, "Error starting the roll call", &e);
+        }
+        show_latest(&app, start_repo.as_ref());
+    });
+
+    let app_weak = app.as_weak();
+    let tick_repo = repo.clone();
+    app.on_roll_call_ticked(move |person_id, present| {
+        let Some(app) = app_weak.upgrade() else {
+            return;
+        };
+        let roll_call_id = app.get_roll_call().id;
+        if let Err(e) = tick_repo.tick_roll_call(roll_call_id, person_id, present) {
+            report_error(&app, "Error ticking off the roll call", &e);
+        }
+        show(&app, tick_repo.as_ref(), roll_call_id);
+    });
+
+    let app_weak = app.as_weak();
+    let patrol_repo = repo.clone();
+    app.on_roll_call_patrol_ticked(move |patrol| {
+        let Some(app) = app_weak.upgrade() else {
+            return;
+        };
+        let roll_call_id = app.get_roll_call().id;
+        let ticked = patrol_repo.roll_call(roll_call_id).and_then(|roll_call| {
+            roll_call
+                .entries
+                .iter()
+                .filter(|e| e.patrol == patrol.as_str() && !e.present)
+                .try_for_each(|e| patrol_repo.tick_roll_call(roll_call_id, e.person_id, true))
+        });
+        if let Err(e) = ticked {
+            report_error(&app, "Error ticking off the roll call", &e);
+        }
+        show(&app, patrol_repo.as_ref(), roll_call_id);
+    });
+
+    let app_weak = app.as_weak();
+    let finish_repo = repo.clone();
+    app.on_finish_roll_call(move || {
+        let Some(app) = app_weak.upgrade() else {
+            return;
+        };
+        let roll_call_id = app.get_roll_call().id;
+        if let Err(e) = finish_repo.finish_roll_call(roll_call_id) {
+            report_error(&app, "Error finishing the roll call", &e);
+        }
+        show(&app, finish_repo.as_ref(), roll_call_id);
+    });
+
+    let app_weak = app.as_weak();
+    app.on_fix_roll_call(move |person_id| {
+        let Some(app) = app_weak.upgrade() else {
+            return;
+        };
+        let roll_call_id = app.get_roll_call().id;
+        let person_ids: Vec<i32> = if person_id < 0 {
+            match repo.roll_call_discrepancies(roll_call_id) {
+                Ok(discrepancies) => discrepancies.iter().map(|d| d.person_id).collect(),
+                Err(e) => {
+                    report_error(&app, "Error loading the roll call discrepancies", &e);
+                    return;
+                }
+            }
+        } else {
+            vec![person_id]
+        };
+        if let Err(e) = repo.fix_roll_call(roll_call_id, &person_ids, operator_name(&app).as_deref()) {
+            report_error(&app, "Error fixing the presence list", &e);
+        }
+        refresh_groups();
+        show(&app, repo.as_ref(), roll_call_id);
+    });
+}
+
+// The active event's roll call in progress, else its last one; empty without any.
+fn show_latest(app: &MainWindow, repo: &dyn Repository) {
+    let latest = repo.active_event().and_then(|event_id| match event_id {
+        Some(event_id) => Ok(repo.roll_calls(event_id)?.into_iter().next()),
+        None => Ok(None),
+    });
+    match latest {
+        Ok(Some(roll_call)) => set_roll_call_models(app, repo, &roll_call),
+        Ok(None) => {
+            app.set_roll_call(RollCallData::default());
+            app.set_roll_call_discrepancies(ModelRc::default());
+        }
+        Err(e) => report_error(app, "Error loading the roll call", &e),
+    }
+}
+
+fn show(app: &MainWindow, repo: &dyn Repository, roll_call_id: i32) {
+    match repo.roll_call(roll_call_id) {
+        Ok(roll_call) => set_roll_call_models(app, repo, &roll_call),
+        Err(e) => report_error(app, "Error loading the roll call", &e),
+    }
+}
+
+// Discrepancies are only listed once the roll call is finished.
+fn set_roll_call_models(app: &MainWindow, repo: &dyn Repository, roll_call: &RollCall) {
+    app.set_roll_call(roll_call_to_data(roll_call));
+    let discrepancies: Vec<RollCallDiscrepancyData> = if roll_call.is_finished() {
+        repo.roll_call_discrepancies(roll_call.id)
+            .unwrap_or_else(|e| {
+                report_error(app, "Error loading the roll call discrepancies", &e);
+                Vec::new()
+            })
+            .iter()
+            .map(discrepancy_to_data)
+            .collect()
+    } else {
+        Vec::new()
+    };
+    app.set_roll_call_discrepancies(ModelRc::new(VecModel::from(discrepancies)));
+}
+
+fn roll_call_to_data(roll_call: &RollCall) -> RollCallData {
+    // Entries come ordered by patrol.
+    let patrols: Vec<RollCallPatrolData> = roll_call
+        .entries
+        .chunk_by(|a, b| a.patrol == b.patrol)
+        .map(|entries| RollCallPatrolData {
+            patrol: SharedString::from(entries[0].patrol.as_str()),
+            present: entries.iter().filter(|e| e.present).count() as i32,
+            total: entries.len() as i32,
+            entries: ModelRc::new(VecModel::from(entries.iter().map(entry_to_data).collect::<Vec<_>>())),
+        })
+        .collect();
+
+    RollCallData {
+        id: roll_call.id,
+        started: SharedString::from(format_local(roll_call.started_at)),
+        finished: roll_call.finished_at.map(format_local).unwrap_or_default().into(),
+        operator: SharedString::from(roll_call.operator.as_deref().unwrap_or_default()),
+        present: roll_call.entries.iter().filter(|e| e.present).count() as i32,
+        expected: roll_call.entries.iter().filter(|e| e.expected == IsInside::In).count() as i32,
+        patrols: ModelRc::new(VecModel::from(patrols)),
+    }
+}
+
+fn entry_to_data(entry: &RollCallEntry) -> RollCallEntryData {
+    RollCallEntryData {
+        person_id: entry.person_id,
+        name: SharedString::from(format!("{} {}", entry.surname, entry.name)),
+        expected_in: entry.expected == IsInside::In,
+        present: entry.present,
+    }
+}
+
+fn discrepancy_to_data(d: &RollCallDiscrepancy) -> RollCallDiscrepancyData {
+    RollCallDiscrepancyData {
+        person_id: d.person_id,
+        name: SharedString::from(format!("{} {}", d.surname, d.name)),
+        patrol: SharedString::from(d.patrol.as_str()),
+        present: d.counted == IsInside::In,
+    }
+}
@@ -0,0 +1,353 @@
+use std::{cell::RefCell, fs, rc::Rc};
+
+use chrono::{DateTime, Days, Local, NaiveDate, NaiveTime, TimeZone, Utc};
+use slint::{ComponentHandle, ModelRc, SharedString, VecModel};
+
+use crate::db_operations::{self, Methodology, Person, Repository, Rota, RotaRequest, RotaRules};
+use crate::{MainWindow, RotaData, RotaRequestData, RotaShiftData, RotaSlotData};
+
+use super::events::active_event_or_report;
+use super::format::{format_local, format_time, full_name, html_escape};
+use super::messages::{report_error, report_message};
+
+const RANK_NUMERALS: [&str; 7] = ["", "I", "II", "III", "IV", "V", "VI"];
+
+/// Sentry duty page state: the ids behind its ComboBoxes.
+#[derive(Default)]
+struct RotaView {
+    // Latest night first, like `rota_names`.
+    rota_ids: Vec<i32>,
+    // Index 0 is "everyone".
+    group_ids: Vec<Option<i32>>,
+    candidate_ids: Vec<i32>,
+}
+
+/// Sentry duty modal on the main screen. Rotas don't change presence, so nothing else
+/// is refreshed.
+pub(super) fn wire_rota_requests(app: &MainWindow, repo: Rc<dyn Repository>) {
+    let view = Rc::new(RefCell::new(RotaView::default()));
+
+    let app_weak = app.as_weak();
+    let (open_repo, open_view) = (repo.clone(), view.clone());
+    app.on_rota_opened(move || {
+        if let Some(app) = app_weak.upgrade() {
+            show(&app, open_repo.as_ref(), &open_view, None);
+        }
+    });
+
+    let app_weak = app.as_weak();
+    let (generate_repo, generate_view) = (repo.clone(), view.clone());
+    app.on_generate_rota(move |data| {
+        let Some(app) = app_weak.upgrade() else {
+            return false;
+        };
+        let Some(event_id) = active_event_or_report(&app, generate_repo.as_ref()) else {
+            return false;
+        };
+        let group_id = generate_view
+            .borrow()
+            .group_ids
+            .get(usize::try_from(data.group_index).unwrap_or(0))
+            .copied()
+            .flatten();
+        let Some(request) = request_from_data(&app, &data, group_id) else {
+            return false;
+        };
+        match generate_repo.generate_rota(event_id, &request) {
+            Ok(rota_id) => {
+                show(&app, generate_repo.as_ref(), &generate_view, Some(rota_id));
+                true
+            }
+            Err(e) => {
+                report_error(&app, "Error generating the rota", &e);
+                false
+            }
+        }
+    });
+
+    let app_weak = app.as_weak();
+    let (select_repo, select_view) = (repo.clone(), view.clone());
+    app.on_rota_selected(move |index| {
+        if let Some(app) = app_weak.upgrade() {
+            let rota_id = usize::try_from(index).ok().and_then(|i| select_view.borrow().rota_ids.get(i).copied());
+            show(&app, select_repo.as_ref(), &select_view, rota_id);
+        }
+    });
+
+    let app_weak = app.as_weak();
+    let (swap_repo, swap_view) = (repo.clone(), view.clone());
+    app.on_swap_rota_slots(move |shift_a, slot_a, shift_b, slot_b| {
+        let Some(app) = app_weak.upgrade() else {
+            return;
+        };
+        let rota_id = app.get_rota().id;
+        let (Some(a), Some(b)) = (slot_position(shift_a, slot_a), slot_position(shift_b, slot_b)) else {
+            return;
+        };
+        if let Err(e) = swap_repo.swap_rota_slots(rota_id, a, b) {
+            report_error(&app, "Error swapping the guards", &e);
+        }
+        show(&app, swap_repo.as_ref(), &swap_view, Some(rota_id));
+    });
+
+    let app_weak = app.as_weak();
+    let (set_repo, set_view) = (repo.clone(), view.clone());
+    app.on_set_rota_slot(move |shift, slot, candidate| {
+        let Some(app) = app_weak.upgrade() else {
+            return;
+        };
+        let rota_id = app.get_rota().id;
+        let Some(at) = slot_position(shift, slot) else {
+            return;
+        };
+        // -1 (no candidate picked) clears the slot.
+        let person_id = usize::try_from(candidate).ok().and_then(|i| set_view.borrow().candidate_ids.get(i).copied());
+        if let Err(e) = set_repo.set_rota_slot(rota_id, at, person_id) {
+            report_error(&app, "Error changing the guard", &e);
+        }
+        show(&app, set_repo.as_ref(), &set_view, Some(rota_id));
+    });
+
+    let app_weak = app.as_weak();
+    let export_repo = repo.clone();
+    app.on_export_rota(move || {
+        let Some(app) = app_weak.upgrade() else {
+            return SharedString::new();
+        };
+        let rota_id = app.get_rota().id;
+        let rota = match export_repo.rota(rota_id) {
+            Ok(rota) => rota,
+            Err(e) => {
+                report_error(&app, "Error exporting the rota", &e);
+                return SharedString::new();
+            }
+        };
+
+        let dir = db_operations::data_dir().join("exports");
+        let stem = format!("rota-{rota_id}-{}", Local::now().format("%Y%m%d-%H%M%S"));
+        let (csv_path, html_path) = (dir.join(format!("{stem}.csv")), dir.join(format!("{stem}.html")));
+        let written = fs::create_dir_all(&dir)
+            .and_then(|()| fs::write(&csv_path, rota_csv(&rota)))
+            .and_then(|()| fs::write(&html_path, rota_html(&rota)));
+        match written {
+            Ok(()) => SharedString::from(html_path.display().to_string()),
+            Err(e) => {
+                report_message(&app, &format!("Couldn't write the rota to {}: {e}", dir.display()));
+                SharedString::new()
+            }
+        }
+    });
+
+    let app_weak = app.as_weak();
+    app.on_delete_rota(move || {
+        let Some(app) = app_weak.upgrade() else {
+            return;
+        };
+        if let Err(e) = repo.delete_rota(app.get_rota().id) {
+            report_error(&app, "Error deleting the rota", &e);
+        }
+        show(&app, repo.as_ref(), &view, None);
+    });
+}
+
+// Reloads the page for the active event with `rota_id` selected, else its latest night.
+fn show(app: &MainWindow, repo: &dyn Repository, view: &RefCell<RotaView>, rota_id: Option<i32>) {
+    let mut view = view.borrow_mut();
+    let loaded = repo.active_event().and_then(|event_id| match event_id {
+        Some(event_id) => Ok((repo.rotas(event_id)?, repo.participants(event_id)?, repo.groups()?)),
+        None => Ok(Default::default()),
+    });
+    let (rotas, participants, groups) = match loaded {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            report_error(app, "Error loading the rotas", &e);
+            return;
+        }
+    };
+
+    let mut group_names = vec![SharedString::from("—")];
+    view.group_ids = vec![None];
+    for g in &groups {
+        group_names.push(SharedString::from(g.name.as_str()));
+        view.group_ids.push(Some(g.id));
+    }
+
+    let mut candidates: Vec<&Person> = participants.iter().map(|p| &p.person).collect();
+    candidates.sort_by_cached_key(|p| (p.surname.to_lowercase(), p.name.to_lowercase(), p.id));
+    view.candidate_ids = candidates.iter().map(|p| p.id).collect();
+    let candidate_names: Vec<SharedString> = candidates.iter().map(|p| SharedString::from(full_name(p))).collect();
+
+    view.rota_ids = rotas.iter().map(|r| r.id).collect();
+    let index = rota_id.and_then(|id| view.rota_ids.iter().position(|&r| r == id)).unwrap_or(0);
+    let rota_names: Vec<SharedString> = rotas.iter().map(|r| SharedString::from(night(r))).collect();
+    let group_name = |id: i32| groups.iter().find(|g| g.id == id).map(|g| g.name.clone());
+
+    app.set_rota_group_names(ModelRc::new(VecModel::from(group_names)));
+    app.set_rota_candidate_names(ModelRc::new(VecModel::from(candidate_names)));
+    app.set_rota_names(ModelRc::new(VecModel::from(rota_names)));
+    app.set_rota_index(index as i32);
+    app.set_rota(rotas.get(index).map(|r| rota_to_data(r, group_name)).unwrap_or_default());
+}
+
+// (shift, slot) of a slot the UI picked; `None` for a negative index.
+fn slot_position(shift: i32, slot: i32) -> Option<(u32, u32)> {
+    Some((u32::try_from(shift).ok()?, u32::try_from(slot).ok()?))
+}
+
+fn rota_to_data(rota: &Rota, group_name: impl Fn(i32) -> Option<String>) -> RotaData {
+    let rules = &rota.request.rules;
+    let summary: Vec<String> = [
+        rules.min_methodology.map(|m| format!("{}+", methodology_key(m))),
+        (rules.min_rank_level > 0).then(|| {
+            let level = rules.min_rank_level;
+            let numeral = RANK_NUMERALS.get(usize::from(level)).map_or_else(|| level.to_string(), |n| n.to_string());
+            format!("{numeral}+")
+        }),
+        rules.group_id.and_then(group_name),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+
+    let shifts: Vec<RotaShiftData> = rota
+        .shifts
+        .iter()
+        .map(|shift| RotaShiftData {
+            time: SharedString::from(format!("{}–{}", format_time(shift.starts_at), format_time(shift.ends_at))),
+            slots: ModelRc::new(VecModel::from(
+                shift
+                    .guards
+                    .iter()
+                    .map(|guard| RotaSlotData {
+                        person_id: guard.as_ref().map_or(0, |p| p.id),
+                        name: guard.as_ref().map(full_name).unwrap_or_default().into(),
+                    })
+                    .collect::<Vec<_>>(),
+            )),
+        })
+        .collect();
+
+    RotaData {
+        id: rota.id,
+        night: SharedString::from(night(rota)),
+        shift_minutes: rota.request.shift_minutes as i32,
+        per_shift: rota.request.per_shift as i32,
+        rules: SharedString::from(summary.join(" · ")),
+        empty_slots: rota.empty_slots() as i32,
+        shifts: ModelRc::new(VecModel::from(shifts)),
+    }
+}
+
+// The GENERATE form as a request; `None` (and an error banner) when it can't be read.
+fn request_from_data(app: &MainWindow, data: &RotaRequestData, group_id: Option<i32>) -> Option<RotaRequest> {
+    let night = data.night.trim();
+    let night = if night.is_empty() {
+        Local::now().date_naive()
+    } else {
+        match NaiveDate::parse_from_str(night, "%Y-%m-%d") {
+            Ok(day) => day,
+            Err(_) => {
+                report_message(app, &format!("Invalid night '{night}', use YYYY-MM-DD."));
+                return None;
+            }
+        }
+    };
+    let Some((starts_at, ends_at)) = night_window(night, data.from.trim(), data.to.trim()) else {
+        report_message(app, &format!("Invalid night window '{}'–'{}', use HH:MM.", data.from.trim(), data.to.trim()));
+        return None;
+    };
+    let (Ok(shift_minutes), Ok(per_shift)) = (data.shift_minutes.trim().parse::<u32>(), data.per_shift.trim().parse::<u32>()) else {
+        report_message(app, "Shift length and persons per shift must be whole numbers.");
+        return None;
+    };
+    Some(RotaRequest {
+        starts_at,
+        ends_at,
+        shift_minutes,
+        per_shift,
+        rules: RotaRules {
+            min_methodology: Methodology::try_from(data.min_methodology - 1).ok(),
+            min_rank_level: data.min_rank.clamp(0, 6) as u8,
+            group_id,
+            no_back_to_back: data.no_back_to_back,
+        },
+    })
+}
+
+// From `from` on `night` to `to`, the next day if it isn't later.
+fn night_window(night: NaiveDate, from: &str, to: &str) -> Option<(DateTime<Utc>, DateTime<Utc>)> {
+    let from = NaiveTime::parse_from_str(from, "%H:%M").ok()?;
+    let to = NaiveTime::parse_from_str(to, "%H:%M").ok()?;
+    let end_day = if to > from { night } else { night.checked_add_days(Days::new(1))? };
+    let starts_at = Local.from_local_datetime(&night.and_time(from)).earliest()?;
+    let ends_at = Local.from_local_datetime(&end_day.and_time(to)).earliest()?;
+    Some((starts_at.with_timezone(&Utc), ends_at.with_timezone(&Utc)))
+}
+
+fn rota_csv(rota: &Rota) -> String {
+    let mut csv = String::from("shift_start,shift_end,slot,surname,name,patrol\n");
+    for shift in &rota.shifts {
+        for (slot, guard) in shift.guards.iter().enumerate() {
+            let (surname, name, patrol) = guard
+                .as_ref()
+                .map_or(("", "", ""), |p| (p.surname.as_str(), p.name.as_str(), p.patrol.as_str()));
+            csv.push_str(&format!(
+                "{},{},{},{},{},{}\n",
+                format_local(shift.starts_at),
+                format_local(shift.ends_at),
+                slot + 1,
+                csv_field(surname),
+                csv_field(name),
+                csv_field(patrol),
+            ));
+        }
+    }
+    csv
+}
+
+fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\n']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+// A page to print and pin up: one table row per shift, with when the rota was generated.
+fn rota_html(rota: &Rota) -> String {
+    let title = html_escape(&night(rota));
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>{title}</title>\n\
+         <style>body{{font-family:sans-serif}}table{{border-collapse:collapse}}td,th{{border:1px solid #000;padding:6px 10px}}</style>\n\
+         </head><body>\n<h1>{title}</h1>\n<table>\n"
+    );
+    for shift in &rota.shifts {
+        let guards: Vec<String> = shift
+            .guards
+            .iter()
+            .map(|guard| guard.as_ref().map(|p| html_escape(&full_name(p))).unwrap_or_default())
+            .collect();
+        html.push_str(&format!(
+            "<tr><th>{}–{}</th><td>{}</td></tr>\n",
+            format_time(shift.starts_at),
+            format_time(shift.ends_at),
+            guards.join("</td><td>"),
+        ));
+    }
+    html.push_str(&format!("</table>\n<p><small>{}</small></p>\n</body></html>\n", format_local(rota.created_at)));
+    html
+}
+
+fn methodology_key(methodology: Methodology) -> &'static str {
+    match methodology {
+        Methodology::Cub => "CUB",
+        Methodology::Scout => "SCOUT",
+        Methodology::VentureScout => "VENTURESCOUT",
+        Methodology::Rover => "ROVER",
+    }
+}
+
+// "YYYY-MM-DD HH:MM–HH:MM", local.
+fn night(rota: &Rota) -> String {
+    format!("{}–{}", format_local(rota.request.starts_at), format_time(rota.request.ends_at))
+}
@@ -0,0 +1,68 @@
+use std::{
+    cell::RefCell,
+    collections::HashSet,
+    rc::Rc,
+};
+
+use slint::ComponentHandle;
+
+use crate::{MainWindow, PersonData};
+
+use crate::db_operations::Repository;
+
+use super::handlers::set_main_people_models;
+use super::messages::report_error;
+
+/// Ids of the persons matching the main screen search field; `None` while it is empty.
+pub(super) fn search_matches(app: &MainWindow, repo: &dyn Repository) -> Option<HashSet<i32>> {
+    let query = app.get_person_search();
+    if query.trim().is_empty() {
+        return None;
+    }
+
+    match repo.search_persons(&query) {
+        Ok(persons) => Some(persons.iter().map(|p| p.id).collect()),
+        Err(e) => {
+            report_error(app, "Error searching persons", &e);
+            None
+        }
+    }
+}
+
+/// Filters the IN/OUT lists while the user types. Persons the filter hides are unchecked, so
+/// GET_IN/GET_OUT only ever move people the operator can see.
+pub(super) fn wire_person_search(
+    app: &MainWindow,
+    repo: Rc<dyn Repository>,
+    all_persons_for_main: Rc<RefCell<Vec<PersonData>>>,
+    checked_person_ids: Rc<RefCell<HashSet<i32>>>,
+    out_person_ids: Rc<RefCell<HashSet<i32>>>,
+    selected_group_id: Rc<RefCell<Option<i32>>>,
+    matching_person_ids: Rc<RefCell<Option<HashSet<i32>>>>,
+) {
+    let app_weak = app.as_weak();
+    app.on_person_search_changed(move || {
+        let Some(app) = app_weak.upgrade() else {
+            return;
+        };
+
+        let matches = search_matches(&app, repo.as_ref());
+        if let Some(matches) = &matches {
+            let mut checked = checked_person_ids.borrow_mut();
+            let before = checked.len();
+            checked.retain(|id| matches.contains(id));
+            // Part of a group is no longer "the group".
+            if checked.len() != before {
+                selected_group_id.borrow_mut().take();
+            }
+        }
+        *matching_person_ids.borrow_mut() = matches;
+        set_main_people_models(
+            &app,
+            &all_persons_for_main.borrow(),
+            &out_person_ids.borrow(),
+            &checked_person_ids.borrow(),
+            matching_person_ids.borrow().as_ref(),
+        );
+    });
+}
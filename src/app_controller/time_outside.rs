@@ -0,0 +1,189 @@
+use std::{cell::RefCell, rc::Rc};
+
+use chrono::{DateTime, Days, Local, TimeDelta, TimeZone, Utc};
+use slint::{ComponentHandle, ModelRc, SharedString, StandardListViewItem, VecModel};
+
+use crate::db_operations::{Event, Repository, TimeOutside};
+use crate::{DayOutsideData, MainWindow, TimeOutsideData};
+
+use super::messages::report_error;
+
+// Columns of the logs screen table, as in `MainWindow`.
+const PERSON_COLUMN: i32 = 0;
+const LEAVES_COLUMN: i32 = 1;
+const TOTAL_COLUMN: i32 = 2;
+const LONGEST_COLUMN: i32 = 3;
+const UNMATCHED_COLUMN: i32 = 4;
+
+/// Logs screen table state: the rows last loaded and how they are sorted.
+struct TimeOutsideTable {
+    rows: Vec<TimeOutside>,
+    column: i32,
+    ascending: bool,
+}
+
+/// Time outside during the active event: the summary in the profile form and the
+/// sortable table on the logs screen. The table starts sorted by most time outside.
+pub(super) fn wire_time_outside_requests(app: &MainWindow, repo: Rc<dyn Repository>) {
+    let table = Rc::new(RefCell::new(TimeOutsideTable {
+        rows: Vec::new(),
+        column: TOTAL_COLUMN,
+        ascending: false,
+    }));
+
+    let app_weak = app.as_weak();
+    let profile_repo = repo.clone();
+    app.on_load_time_outside(move |person_id| {
+        let Some(app) = app_weak.upgrade() else {
+            return TimeOutsideData::default();
+        };
+        let Some((event, rows)) = load(&app, profile_repo.as_ref()) else {
+            return TimeOutsideData::default();
+        };
+        match rows.iter().find(|t| t.person_id == person_id) {
+            Some(t) => time_outside_to_data(&event, t),
+            // Never logged in this event.
+            None => TimeOutsideData {
+                event_name: SharedString::from(event.name.as_str()),
+                total: SharedString::from(format_duration(TimeDelta::zero())),
+                ..Default::default()
+            },
+        }
+    });
+
+    let app_weak = app.as_weak();
+    let load_table = table.clone();
+    app.on_time_outside_requested(move || {
+        let Some(app) = app_weak.upgrade() else {
+            return;
+        };
+        let mut table = load_table.borrow_mut();
+        table.rows = load(&app, repo.as_ref()).map(|(_, rows)| rows).unwrap_or_default();
+        sort_rows(&mut table);
+        set_table_model(&app, &table);
+    });
+
+    let app_weak = app.as_weak();
+    app.on_sort_time_outside(move |column, ascending| {
+        let Some(app) = app_weak.upgrade() else {
+            return;
+        };
+        let mut table = table.borrow_mut();
+        table.column = column;
+        table.ascending = ascending;
+        sort_rows(&mut table);
+        set_table_model(&app, &table);
+    });
+}
+
+// The active event and its time outside until now, or until the end of its last day once
+// it is over (someone never logged back in shouldn't keep adding up). `None` without an
+// active event or after an error banner.
+fn load(app: &MainWindow, repo: &dyn Repository) -> Option<(Event, Vec<TimeOutside>)> {
+    let loaded = repo.active_event().and_then(|event_id| {
+        let Some(event_id) = event_id else {
+            return Ok(None);
+        };
+        let Some(event) = repo.events()?.into_iter().find(|e| e.id == event_id) else {
+            return Ok(None);
+        };
+        let now = Utc::now();
+        let until = event.ends_on.and_then(day_after).map_or(now, |end| end.min(now));
+        Ok(Some((event, repo.time_outside(event_id, until)?)))
+    });
+    loaded.unwrap_or_else(|e| {
+        report_error(app, "Error computing time outside", &e);
+        None
+    })
+}
+
+// Start of the local day after `day`, in UTC.
+fn day_after(day: chrono::NaiveDate) -> Option<DateTime<Utc>> {
+    let next = day.checked_add_days(Days::new(1))?;
+    Local
+        .from_local_datetime(&next.and_time(chrono::NaiveTime::MIN))
+        .earliest()
+        .map(|t| t.with_timezone(&Utc))
+}
+
+fn sort_rows(table: &mut TimeOutsideTable) {
+    let column = table.column;
+    table.rows.sort_by(|a, b| {
+        let order = match column {
+            PERSON_COLUMN => display_name(a).to_lowercase().cmp(&display_name(b).to_lowercase()),
+            LEAVES_COLUMN => a.leaves().cmp(&b.leaves()),
+            LONGEST_COLUMN => a.longest.cmp(&b.longest),
+            UNMATCHED_COLUMN => a.unmatched.cmp(&b.unmatched),
+            _ => a.total.cmp(&b.total),
+        };
+        order.then(a.person_id.cmp(&b.person_id))
+    });
+    if !table.ascending {
+        table.rows.reverse();
+    }
+}
+
+fn set_table_model(app: &MainWindow, table: &TimeOutsideTable) {
+    let rows: Vec<ModelRc<StandardListViewItem>> = table
+        .rows
+        .iter()
+        .map(|t| {
+            let total = if t.is_outside() {
+                format!("{} …", format_duration(t.total))
+            } else {
+                format_duration(t.total)
+            };
+            let cells: Vec<StandardListViewItem> = [
+                display_name(t),
+                t.leaves().to_string(),
+                total,
+                format_duration(t.longest),
+                t.unmatched.to_string(),
+            ]
+            .into_iter()
+            .map(|text| StandardListViewItem::from(SharedString::from(text)))
+            .collect();
+            ModelRc::new(VecModel::from(cells))
+        })
+        .collect();
+    app.set_time_outside_rows(ModelRc::new(VecModel::from(rows)));
+}
+
+fn time_outside_to_data(event: &Event, t: &TimeOutside) -> TimeOutsideData {
+    TimeOutsideData {
+        event_name: SharedString::from(event.name.as_str()),
+        leaves: t.leaves() as i32,
+        total: SharedString::from(format_duration(t.total)),
+        longest: SharedString::from(format_duration(t.longest)),
+        still_outside: t.is_outside(),
+        unmatched: t.unmatched as i32,
+        days: ModelRc::new(VecModel::from(
+            t.days
+                .iter()
+                .map(|d| DayOutsideData {
+                    day: SharedString::from(d.day.format("%Y-%m-%d").to_string()),
+                    leaves: d.leaves as i32,
+                    outside: SharedString::from(format_duration(d.outside)),
+                })
+                .collect::<Vec<_>>(),
+        )),
+    }
+}
+
+// Named as the log last saw them, like the logs screen.
+fn display_name(t: &TimeOutside) -> String {
+    match &t.snapshot {
+        Some(s) => format!("{} {}", s.surname, s.name),
+        None => format!("#{}", t.person_id),
+    }
+}
+
+/// "2 h 05 min", or "35 min" under an hour.
+pub(super) fn format_duration(d: TimeDelta) -> String {
+    let minutes = d.num_minutes().max(0);
+    if minutes < 60 {
+        format!("{minutes} min")
+    } else {
+        format!("{} h {:02} min", minutes / 60, minutes % 60)
+    }
+}
@@ -4,33 +4,106 @@
 //! while the implementation is split across smaller files in `src/db_operations/`.
 //!
 //! Split overview:
+//! - `error.rs`: `DbError` / `DbResult` used by every public function
 //! - `types.rs`: DB-facing domain types (Person/Group/...) and enum ↔ SQL glue
-//! - `schema.rs`: schema creation + initial seed data
+//! - `schema.rs`: ordered schema migration steps + initial seed data
+//! - `migrations.rs`: `user_version` tracking, transactional migration runner, pre-migration backup
 //! - `queries.rs`: read/query helpers
 //! - `insert.rs`, `update.rs`, `delete.rs`: write helpers
-//! - `path.rs`: DB location
+//! - `app_setting.rs`: per-database key/value settings (active event, ...)
+//! - `leave_pass.rs`: leave passes written with an OUT and closed by the next IN
+//! - `alerts.rs`: participants out past their methodology's limit or the camp curfew
+//! - `privacy.rs`: retention period, anonymization and per-person data export
+//! - `archive.rs`: soft delete (archive/restore) for persons and groups; purge lives in `delete.rs`
+//! - `repository.rs`: `PersonRepository` / `GroupRepository` / `EventRepository` / `PresenceLogRepository` traits
+//!   with the SQLite backend
+//! - `path.rs`: app data directory (the DB path itself comes from `crate::settings`)
 //! - `connection.rs`: open connection + run schema
+//! - `encryption.rs`: SQLCipher passphrase handling (unlock, encrypt a plaintext camp, change passphrase)
+//! - `backup.rs`: timestamped backups (online backup API), rotation and restore
+//! - `integrity.rs`: consistency check (SQLite and domain invariants) and automatic repairs
+//! - `presence.rs`: presence at any moment derived from the log, and reconciling the stored copy with it
+//! - `time_outside.rs`: time spent outside per person and day, from OUT/IN pairs in the log
+//! - `roll_call.rs`: roll call sessions, their discrepancies with the stored presence and fixing them
+//! - `rota.rs`: sentry duty rotas planned from the participants, with manual swaps
+//! - `journal.rs`: sentry shifts, checkpoint rounds and incidents, and the morning report
+//! - `test_support.rs`: temporary directories and migrated in-memory databases for the unit tests
 
+mod alerts;
+mod app_setting;
+mod archive;
+mod backup;
 mod connection;
 mod delete;
+mod encryption;
+mod error;
 mod insert;
+mod integrity;
+mod journal;
+mod leave_pass;
+mod migrations;
 mod path;
+mod presence;
+mod privacy;
 mod queries;
+mod repository;
+mod roll_call;
+mod rota;
 mod schema;
+#[cfg(test)]
+pub(crate) mod test_support;
+mod time_outside;
 mod types;
 mod update;
 
-pub use connection::get_db;
 #[allow(unused_imports)]
-pub use delete::delete_from_db;
+pub use alerts::{overdue_persons, AlertLimits, Overdue, OverdueReason};
+#[allow(unused_imports)]
+pub use app_setting::{get_active_event_id, get_alert_limits, get_retention_days, set_active_event_id, set_alert_limits, set_retention_days};
+#[allow(unused_imports)]
+pub use archive::{archive_group, archive_person, restore_group, restore_person};
+pub use backup::{backup_dir, create_backup, delete_backups, list_backups, restore_backup, rotate_backups, BackupInfo, BackupReason};
+pub use connection::open_db;
+pub use encryption::{change_passphrase, encrypt_database, encryption_available, is_encrypted};
+pub use migrations::delete_migration_backups;
+pub use path::{data_dir, legacy_db_path};
+#[allow(unused_imports)]
+pub use presence::{inside_at, presence_at, presence_mismatches, reconcile_presence, PresenceMismatch, PresenceState, ReconcileWith};
+#[allow(unused_imports)]
+pub use privacy::{anonymize_person, export_person, persons_past_retention, ANONYMIZED_NAME, REDACTED_DESCRIPTION};
+#[allow(unused_imports)]
+pub use delete::{delete_from_db, purge_group, purge_person};
+pub use error::{DbError, DbResult, InvalidEnumValue};
+#[allow(unused_imports)]
 pub use insert::insert_to_db;
+pub use integrity::{check_database, reattach_orphaned_logs, repair, Problem};
+#[allow(unused_imports)]
+pub use journal::{
+    add_checkpoint, delete_incident, end_sentry_shift, get_checkpoints, get_incidents, get_sentry_shifts, morning_report, record_incident,
+    start_sentry_shift, visit_checkpoint, Checkpoint, CheckpointVisit, Incident, MorningReport, NewIncident, SentryShift,
+};
+#[allow(unused_imports)]
+pub use leave_pass::open_leave_passes;
+#[allow(unused_imports)]
+pub use queries::{get_archived_groups, get_archived_persons, get_group, get_group_member, get_group_with_members, get_person, search_persons, get_log, get_events, get_event_participants, query_log};
+#[allow(unused_imports)]
+pub use repository::{
+    AlertRepository, EventRepository, GroupRepository, JournalRepository, PersonRepository, PresenceLogRepository, PrivacyRepository, Repository,
+    RollCallRepository, RotaRepository, SqliteRepository,
+};
+#[allow(unused_imports)]
+pub use roll_call::{
+    finish_roll_call, fix_roll_call, get_roll_call, get_roll_calls, roll_call_discrepancies, start_roll_call, tick_roll_call, RollCall,
+    RollCallDiscrepancy, RollCallEntry,
+};
+#[allow(unused_imports)]
+pub use rota::{delete_rota, generate_rota, get_rota, get_rotas, set_rota_slot, swap_rota_slots, Rota, RotaRequest, RotaRules, RotaShift, MAX_SHIFTS};
 #[allow(unused_imports)]
-pub use queries::{get_group, get_group_member, get_group_with_members, get_person, get_log};
-pub use types::{IsInside, Methodology, RankLevel, Person, Group, GroupWithMembers, DatabaseRecord};
+pub use time_outside::{time_outside, DayOutside, Outing, TimeOutside};
+pub use types::{IsInside, LeaveDetails, LeavePass, LeaveReason, Methodology, RankLevel, Severity, Person, Guardian, Group, GroupKind, GroupWithMembers, Event, Participant, Participation, PersonExport, Log, LogCursor, LogEntry, LogFilter, LogPage, LogSnapshot, DatabaseRecord};
 #[allow(unused_imports)]
-pub use update::update_db;
-pub use update::set_person_is_inside;
+pub use update::{update_db, leave_with_pass, set_group_is_inside, set_person_is_inside, set_persons_is_inside, PresenceBatch};
 
 // Internal-only items shared across db submodules.
-pub(in crate::db_operations) use types::Log;
-pub(in crate::db_operations) use types::parse_db_datetime;
+pub(in crate::db_operations) use error::expect_row;
+pub(in crate::db_operations) use types::{format_db_date, format_db_datetime, format_guardians, parse_db_date, parse_db_datetime, parse_guardians};
@@ -0,0 +1,227 @@
+//! Overdue alerts: participants out longer than their methodology allows, or out at all
+//! during the camp curfew.
+//!
+//! The curfew is a nightly window from `AlertLimits::curfew` to `curfew_end` (local times;
+//! an end at or before the start is the next morning). Anyone out while it lasts is overdue,
+//! whenever they left, and stops being overdue for it when it ends.
+//!
+//! All limits are camp settings (`AppSetting`, see `app_setting.rs`). Time out runs from
+//! the participant's newest log row, which is the OUT that took them out (see `presence.rs`).
+//! Participants whose stored presence disagrees with the log are left to the integrity check.
+
+use chrono::{DateTime, Days, Local, NaiveTime, TimeDelta, TimeZone, Utc};
+use rusqlite::Connection;
+
+use super::app_setting::get_alert_limits;
+use super::queries::{person_from_row, PERSON_COLUMN_COUNT, PERSON_LIST_COLUMNS};
+use super::types::EntityType;
+use super::{parse_db_datetime, DbResult, IsInside, Methodology, Person};
+
+/// Camp limits on time out. The default has none.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AlertLimits {
+    // Minutes, indexed by `Methodology as usize`.
+    max_out_minutes: [Option<u32>; 4],
+    /// Local time after which nobody should be out.
+    pub curfew: Option<NaiveTime>,
+    /// Local time the curfew ends; `None` for `DEFAULT_CURFEW_END`.
+    pub curfew_end: Option<NaiveTime>,
+}
+
+/// End of the curfew when none is set.
+const DEFAULT_CURFEW_END: NaiveTime = NaiveTime::from_hms_opt(6, 0, 0).expect("valid time");
+
+impl AlertLimits {
+    /// Longest time out allowed for `methodology`, in minutes; `None` for no limit.
+    pub fn max_out_minutes(&self, methodology: Methodology) -> Option<u32> {
+        self.max_out_minutes[methodology as usize]
+    }
+
+    pub fn set_max_out_minutes(&mut self, methodology: Methodology, minutes: Option<u32>) {
+        self.max_out_minutes[methodology as usize] = minutes;
+    }
+
+    /// `true` when nothing can ever be overdue.
+    pub fn is_empty(&self) -> bool {
+        *self == AlertLimits::default()
+    }
+}
+
+/// Why someone is overdue.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverdueReason {
+    /// Out longer than their methodology's limit.
+    MaxOut { limit: TimeDelta },
+    /// Out during the curfew that started at `curfew`.
+    Curfew { curfew: DateTime<Utc> },
+}
+
+/// A participant out past a limit.
+#[derive(Debug, Clone)]
+pub struct Overdue {
+    pub person: Person,
+    pub left: DateTime<Utc>,
+    /// Never empty.
+    pub reasons: Vec<OverdueReason>,
+}
+
+/// Participants of event `event_id` overdue at `now` under the camp's limits, out the
+/// longest first. Archived persons are left out.
+pub fn overdue_persons(conn: &Connection, event_id: i32, now: DateTime<Utc>) -> DbResult<Vec<Overdue>> {
+    let limits = get_alert_limits(conn)?;
+    if limits.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut stmt = conn.prepare_cached(&format!(
+        "WITH `last` AS (
+            SELECT `l`.`entity_id`, `l`.`is_inside`, `l`.`timestamp`, ROW_NUMBER() OVER (
+                PARTITION BY `l`.`entity_id` ORDER BY `l`.`timestamp` DESC, `l`.`id` DESC) AS `n`
+            FROM `Log` `l`
+            WHERE `l`.`entity_type` = ?2 AND `l`.`event_id` = ?1)
+         SELECT {PERSON_LIST_COLUMNS}, `last`.`timestamp`
+         FROM `EventParticipants` `ep`
+         JOIN `Person` `p` ON `p`.`id` = `ep`.`person_id` AND `p`.`archived_at` IS NULL
+         JOIN `last` ON `last`.`entity_id` = `ep`.`person_id` AND `last`.`n` = 1 AND `last`.`is_inside` = ?3
+         WHERE `ep`.`event_id` = ?1 AND `ep`.`is_inside` = ?3;"
+    ))?;
+    let out = stmt
+        .query_map((event_id, EntityType::Person, IsInside::Out), |row| {
+            let left: String = row.get(PERSON_COLUMN_COUNT)?;
+            Ok((person_from_row(row, 0)?, parse_db_datetime(&left)?))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    Ok(collect_overdue(&limits, out, now))
+}
+
+/// Keeps the out participants (`(person, left)`) overdue at `now`, out the longest first.
+pub(super) fn collect_overdue(
+    limits: &AlertLimits,
+    out: impl IntoIterator<Item = (Person, DateTime<Utc>)>,
+    now: DateTime<Utc>,
+) -> Vec<Overdue> {
+    let curfew = limits
+        .curfew
+        .and_then(|start| curfew_in_progress(start, limits.curfew_end.unwrap_or(DEFAULT_CURFEW_END), now));
+    let mut overdue: Vec<Overdue> = out
+        .into_iter()
+        .filter_map(|(person, left)| {
+            let mut reasons = Vec::new();
+            if let Some(minutes) = limits.max_out_minutes(person.methodology) {
+                let limit = TimeDelta::minutes(i64::from(minutes));
+                if now - left > limit {
+                    reasons.push(OverdueReason::MaxOut { limit });
+                }
+            }
+            if let Some(curfew) = curfew {
+                reasons.push(OverdueReason::Curfew { curfew });
+            }
+            (!reasons.is_empty()).then_some(Overdue { person, left, reasons })
+        })
+        .collect();
+    overdue.sort_by_key(|o| (o.left, o.person.id));
+    overdue
+}
+
+// Start of the curfew `now` falls in, if any: it began on the local evening of today or
+// yesterday and ends at the first `end` after that.
+fn curfew_in_progress(start: NaiveTime, end: NaiveTime, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    let today = now.with_timezone(&Local).date_naive();
+    [Some(today), today.checked_sub_days(Days::new(1))]
+        .into_iter()
+        .flatten()
+        .find_map(|day| {
+            let end_day = if end > start { Some(day) } else { day.checked_add_days(Days::new(1)) }?;
+            let from = Local.from_local_datetime(&day.and_time(start)).earliest()?.with_timezone(&Utc);
+            let to = Local.from_local_datetime(&end_day.and_time(end)).earliest()?.with_timezone(&Utc);
+            (from <= now && now < to).then_some(from)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDateTime;
+
+    use super::*;
+    use crate::db_operations::RankLevel;
+
+    fn at(time: &str) -> DateTime<Utc> {
+        let time = NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M").unwrap();
+        Local.from_local_datetime(&time).earliest().unwrap().with_timezone(&Utc)
+    }
+
+    fn time(text: &str) -> NaiveTime {
+        NaiveTime::parse_from_str(text, "%H:%M").unwrap()
+    }
+
+    fn curfew(start: &str, end: Option<&str>) -> AlertLimits {
+        AlertLimits {
+            curfew: Some(time(start)),
+            curfew_end: end.map(time),
+            ..Default::default()
+        }
+    }
+
+    fn scout(id: i32) -> Person {
+        let mut person = Person::new("Jan".to_string(), "Nowak".to_string(), RankLevel::RankNone, Methodology::Scout);
+        person.id = id;
+        person
+    }
+
+    // Ids of the persons overdue at `now`; person `n + 1` left at `left[n]`.
+    fn overdue_at(limits: &AlertLimits, left: &[&str], now: &str) -> Vec<i32> {
+        let out = left.iter().zip(1..).map(|(&left, id)| (scout(id), at(left)));
+        collect_overdue(limits, out, at(now)).iter().map(|o| o.person.id).collect()
+    }
+
+    #[test]
+    fn anyone_out_during_the_curfew_is_overdue() {
+        let limits = curfew("22:00", Some("06:00"));
+        let left = ["2026-07-17 15:00", "2026-07-17 22:30", "2026-07-18 01:00"];
+        // Left before the curfew, and after it started.
+        assert_eq!(overdue_at(&limits, &left[..2], "2026-07-17 23:00"), [1, 2]);
+        assert_eq!(overdue_at(&limits, &left, "2026-07-18 05:59"), [1, 2, 3]);
+
+        let overdue = collect_overdue(&limits, [(scout(3), at(left[2]))], at("2026-07-18 02:00"));
+        assert_eq!(overdue[0].reasons, [OverdueReason::Curfew { curfew: at("2026-07-17 22:00") }]);
+    }
+
+    #[test]
+    fn nobody_is_overdue_outside_the_curfew() {
+        let limits = curfew("22:00", Some("06:00"));
+        let left = ["2026-07-17 15:00", "2026-07-17 23:00"];
+        assert_eq!(overdue_at(&limits, &left[..1], "2026-07-17 21:59"), Vec::<i32>::new());
+        // Still out in the morning: the curfew is over, so they are no longer flagged for it.
+        assert_eq!(overdue_at(&limits, &left, "2026-07-18 06:00"), Vec::<i32>::new());
+        assert_eq!(overdue_at(&limits, &left, "2026-07-18 14:00"), Vec::<i32>::new());
+    }
+
+    #[test]
+    fn the_curfew_ends_at_its_end_time() {
+        // Default end: 06:00 the next morning.
+        let limits = curfew("23:00", None);
+        assert_eq!(overdue_at(&limits, &["2026-07-17 20:00"], "2026-07-18 05:00"), [1]);
+        assert_eq!(overdue_at(&limits, &["2026-07-17 20:00"], "2026-07-18 07:00"), Vec::<i32>::new());
+
+        // A window within one day.
+        let limits = curfew("13:00", Some("15:00"));
+        assert_eq!(overdue_at(&limits, &["2026-07-17 09:00"], "2026-07-17 14:00"), [1]);
+        assert_eq!(overdue_at(&limits, &["2026-07-17 09:00"], "2026-07-17 16:00"), Vec::<i32>::new());
+        assert_eq!(overdue_at(&limits, &["2026-07-17 09:00"], "2026-07-18 12:00"), Vec::<i32>::new());
+    }
+
+    #[test]
+    fn both_reasons_are_given() {
+        let mut limits = curfew("22:00", Some("06:00"));
+        limits.set_max_out_minutes(Methodology::Scout, Some(60));
+        let overdue = collect_overdue(&limits, [(scout(1), at("2026-07-17 21:00"))], at("2026-07-17 22:30"));
+        assert_eq!(
+            overdue[0].reasons,
+            [
+                OverdueReason::MaxOut { limit: TimeDelta::hours(1) },
+                OverdueReason::Curfew { curfew: at("2026-07-17 22:00") }
+            ]
+        );
+    }
+}
@@ -0,0 +1,121 @@
+use chrono::NaiveTime;
+use rusqlite::{Connection, OptionalExtension};
+
+use super::alerts::AlertLimits;
+use super::{DbError, DbResult, Methodology};
+
+// Keys of the `AppSetting` table. Per-database settings live there; per-machine ones
+// (recent camps, default camp) are in `crate::settings`.
+const ACTIVE_EVENT_ID: &str = "active_event_id";
+const RETENTION_DAYS: &str = "retention_days";
+const MAX_OUT_MINUTES: [(Methodology, &str); 4] = [
+    (Methodology::Cub, "max_out_minutes_cub"),
+    (Methodology::Scout, "max_out_minutes_scout"),
+    (Methodology::VentureScout, "max_out_minutes_venturescout"),
+    (Methodology::Rover, "max_out_minutes_rover"),
+];
+// Local time, "HH:MM".
+const CURFEW: &str = "curfew";
+const CURFEW_END: &str = "curfew_end";
+const CURFEW_FORMAT: &str = "%H:%M";
+
+pub(in crate::db_operations) fn get_app_setting(conn: &Connection, key: &str) -> DbResult<Option<String>> {
+    Ok(conn
+        .query_row("SELECT `value` FROM `AppSetting` WHERE `key` = ?1;", (key,), |row| row.get(0))
+        .optional()?)
+}
+
+pub(in crate::db_operations) fn set_app_setting(conn: &Connection, key: &str, value: &str) -> DbResult<()> {
+    conn.execute(
+        "INSERT INTO `AppSetting`(`key`, `value`) VALUES(?1, ?2)
+         ON CONFLICT(`key`) DO UPDATE SET `value` = excluded.`value`;",
+        (key, value),
+    )?;
+    Ok(())
+}
+
+/// Event the main screen works on. `None` if it was never set or no longer exists.
+pub fn get_active_event_id(conn: &Connection) -> DbResult<Option<i32>> {
+    let Some(value) = get_app_setting(conn, ACTIVE_EVENT_ID)? else {
+        return Ok(None);
+    };
+    let id: i32 = value
+        .parse()
+        .map_err(|_| DbError::Corrupt(format!("{ACTIVE_EVENT_ID} is not an id: '{value}'")))?;
+
+    let exists: bool = conn.query_row("SELECT EXISTS(SELECT 1 FROM `Event` WHERE `id` = ?1);", (id,), |row| row.get(0))?;
+    Ok(exists.then_some(id))
+}
+
+pub fn set_active_event_id(conn: &Connection, event_id: i32) -> DbResult<()> {
+    let exists: bool = conn.query_row("SELECT EXISTS(SELECT 1 FROM `Event` WHERE `id` = ?1);", (event_id,), |row| {
+        row.get(0)
+    })?;
+    if !exists {
+        return Err(DbError::NotFound { entity: "event", id: event_id });
+    }
+    set_app_setting(conn, ACTIVE_EVENT_ID, &event_id.to_string())
+}
+
+/// Camp-wide retention period for events without their own; `None` = keep data until removed by hand.
+pub fn get_retention_days(conn: &Connection) -> DbResult<Option<u32>> {
+    let Some(value) = get_app_setting(conn, RETENTION_DAYS)? else {
+        return Ok(None);
+    };
+    value
+        .parse()
+        .map(Some)
+        .map_err(|_| DbError::Corrupt(format!("{RETENTION_DAYS} is not a number of days: '{value}'")))
+}
+
+pub fn set_retention_days(conn: &Connection, days: Option<u32>) -> DbResult<()> {
+    set_or_clear(conn, RETENTION_DAYS, days.map(|d| d.to_string()))
+}
+
+/// Limits for overdue alerts, see `alerts.rs`.
+pub fn get_alert_limits(conn: &Connection) -> DbResult<AlertLimits> {
+    let mut limits = AlertLimits::default();
+    for (methodology, key) in MAX_OUT_MINUTES {
+        let minutes = match get_app_setting(conn, key)? {
+            None => None,
+            Some(value) => Some(
+                value
+                    .parse()
+                    .map_err(|_| DbError::Corrupt(format!("{key} is not a number of minutes: '{value}'")))?,
+            ),
+        };
+        limits.set_max_out_minutes(methodology, minutes);
+    }
+    for (key, time) in [(CURFEW, &mut limits.curfew), (CURFEW_END, &mut limits.curfew_end)] {
+        *time = match get_app_setting(conn, key)? {
+            None => None,
+            Some(value) => Some(
+                NaiveTime::parse_from_str(&value, CURFEW_FORMAT)
+                    .map_err(|_| DbError::Corrupt(format!("{key} is not a time: '{value}'")))?,
+            ),
+        };
+    }
+    Ok(limits)
+}
+
+/// Saves all limits at once; `None`s are removed.
+pub fn set_alert_limits(conn: &Connection, limits: &AlertLimits) -> DbResult<()> {
+    let tx = conn.unchecked_transaction()?;
+    for (methodology, key) in MAX_OUT_MINUTES {
+        set_or_clear(&tx, key, limits.max_out_minutes(methodology).map(|m| m.to_string()))?;
+    }
+    set_or_clear(&tx, CURFEW, limits.curfew.map(|c| c.format(CURFEW_FORMAT).to_string()))?;
+    set_or_clear(&tx, CURFEW_END, limits.curfew_end.map(|c| c.format(CURFEW_FORMAT).to_string()))?;
+    tx.commit()?;
+    Ok(())
+}
+
+fn set_or_clear(conn: &Connection, key: &str, value: Option<String>) -> DbResult<()> {
+    match value {
+        Some(value) => set_app_setting(conn, key, &value),
+        None => {
+            conn.execute("DELETE FROM `AppSetting` WHERE `key` = ?1;", (key,))?;
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,141 @@
+use chrono::Utc;
+use rusqlite::{Connection, OptionalExtension};
+
+use super::{expect_row, format_db_datetime, DbError, DbResult, GroupKind};
+
+/// Hides the person from every list. Memberships and `Log` rows are kept.
+pub fn archive_person(conn: &Connection, person_id: i32) -> DbResult<()> {
+    let archived = conn.execute(
+        "UPDATE `Person` SET `archived_at` = ?2 WHERE `id` = ?1 AND `archived_at` IS NULL;",
+        (person_id, format_db_datetime(&Utc::now())),
+    )?;
+    expect_row(archived, "active person", person_id)
+}
+
+pub fn restore_person(conn: &Connection, person_id: i32) -> DbResult<()> {
+    let restored = conn.execute(
+        "UPDATE `Person` SET `archived_at` = NULL WHERE `id` = ?1 AND `archived_at` IS NOT NULL;",
+        (person_id,),
+    )?;
+    expect_row(restored, "archived person", person_id)
+}
+
+/// Hides a user group. Built-in groups ("Camp" and methodology groups) are refused.
+pub fn archive_group(conn: &Connection, group_id: i32) -> DbResult<()> {
+    ensure_not_built_in(conn, group_id)?;
+    let archived = conn.execute(
+        "UPDATE `Group` SET `archived_at` = ?2 WHERE `id` = ?1 AND `archived_at` IS NULL;",
+        (group_id, format_db_datetime(&Utc::now())),
+    )?;
+    expect_row(archived, "active group", group_id)
+}
+
+pub fn restore_group(conn: &Connection, group_id: i32) -> DbResult<()> {
+    let restored = conn.execute(
+        "UPDATE `Group` SET `archived_at` = NULL WHERE `id` = ?1 AND `archived_at` IS NOT NULL;",
+        (group_id,),
+    )?;
+    expect_row(restored, "archived group", group_id)
+}
+
+/// `ConstraintViolation` for built-in groups (see `GroupKind::is_built_in`).
+/// A missing group passes; the operation itself reports it.
+pub(in crate::db_operations) fn ensure_not_built_in(conn: &Connection, group_id: i32) -> DbResult<()> {
+    let kind: Option<GroupKind> = conn
+        .query_row("SELECT `kind` FROM `Group` WHERE `id` = ?1;", (group_id,), |row| row.get(0))
+        .optional()?;
+    check_not_built_in(group_id, kind)
+}
+
+pub(in crate::db_operations) fn check_not_built_in(group_id: i32, kind: Option<GroupKind>) -> DbResult<()> {
+    if kind.is_some_and(|k| k.is_built_in()) {
+        return Err(DbError::ConstraintViolation(format!(
+            "group {group_id} is built in; it can't be archived or purged and its members follow the roster"
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use rusqlite::Connection;
+
+    use super::{archive_group, archive_person, restore_group, restore_person};
+    use crate::db_operations::test_support::{memory_db, person};
+    use crate::db_operations::{
+        purge_group, purge_person, DbError, EventRepository, Group, GroupKind, GroupRepository, IsInside, Methodology, PersonRepository,
+        SqliteRepository,
+    };
+
+    const EVENT_ID: i32 = 1;
+    const CAMP_GROUP: i32 = 1;
+
+    // A participant who was logged in once, in a user group.
+    fn camp() -> (Rc<RefCell<Connection>>, SqliteRepository, i32, i32) {
+        let conn = Rc::new(RefCell::new(memory_db()));
+        let repo = SqliteRepository::new(conn.clone());
+        let id = repo.add_person(&person("Jan", "Kowalski", Methodology::Scout)).unwrap();
+        let wolves = Group {
+            id: 0,
+            name: "Wolves".to_string(),
+            kind: GroupKind::User,
+            methodology: None,
+            archived_at: None,
+        };
+        let wolves = repo.add_group(&wolves).unwrap();
+        repo.add_member(wolves, id).unwrap();
+        repo.add_participant(EVENT_ID, id).unwrap();
+        repo.set_presence(EVENT_ID, &[id], IsInside::In, None).unwrap();
+        (conn, repo, id, wolves)
+    }
+
+    fn count(conn: &Connection, sql: &str, id: i32) -> i32 {
+        conn.query_row(sql, (id,), |row| row.get(0)).unwrap()
+    }
+
+    #[test]
+    fn archiving_hides_and_restoring_brings_back_with_memberships() {
+        let (conn, repo, id, wolves) = camp();
+        let conn = conn.borrow();
+        archive_person(&conn, id).unwrap();
+        archive_group(&conn, wolves).unwrap();
+        assert!(repo.persons().unwrap().is_empty());
+        assert!(repo.participants(EVENT_ID).unwrap().is_empty());
+        assert!(repo.groups().unwrap().iter().all(|g| g.id != wolves));
+        assert_eq!(repo.archived_persons().unwrap()[0].id, id);
+        assert_eq!(repo.archived_groups().unwrap()[0].id, wolves);
+        assert!(matches!(archive_person(&conn, id), Err(DbError::NotFound { entity: "active person", .. })));
+        assert!(matches!(archive_group(&conn, wolves), Err(DbError::NotFound { entity: "active group", .. })));
+
+        restore_person(&conn, id).unwrap();
+        restore_group(&conn, wolves).unwrap();
+        assert!(matches!(restore_person(&conn, id), Err(DbError::NotFound { entity: "archived person", .. })));
+        assert!(matches!(restore_group(&conn, wolves), Err(DbError::NotFound { entity: "archived group", .. })));
+        let group = repo.groups_with_members().unwrap().into_iter().find(|g| g.id == wolves).unwrap();
+        assert_eq!(group.members.iter().map(|p| p.id).collect::<Vec<_>>(), [id]);
+        let participants = repo.participants(EVENT_ID).unwrap();
+        assert_eq!((participants[0].person.id, participants[0].is_inside), (id, IsInside::In));
+    }
+
+    #[test]
+    fn purge_is_refused_while_active_and_keeps_the_log() {
+        let (conn, _repo, id, wolves) = camp();
+        let conn = conn.borrow();
+        assert!(matches!(purge_person(&conn, id), Err(DbError::ConstraintViolation(_))));
+        assert!(matches!(purge_group(&conn, wolves), Err(DbError::ConstraintViolation(_))));
+        assert!(matches!(purge_person(&conn, 999), Err(DbError::NotFound { entity: "person", id: 999 })));
+        assert!(matches!(archive_group(&conn, CAMP_GROUP), Err(DbError::ConstraintViolation(_))));
+        assert!(matches!(purge_group(&conn, CAMP_GROUP), Err(DbError::ConstraintViolation(_))));
+
+        archive_group(&conn, wolves).unwrap();
+        purge_group(&conn, wolves).unwrap();
+        assert_eq!(count(&conn, "SELECT COUNT(*) FROM `GroupMembers` WHERE `group_id` = ?1;", wolves), 0);
+        archive_person(&conn, id).unwrap();
+        purge_person(&conn, id).unwrap();
+        assert_eq!(count(&conn, "SELECT COUNT(*) FROM `Person` WHERE `id` = ?1;", id), 0);
+        assert_eq!(count(&conn, "SELECT COUNT(*) FROM `GroupMembers` WHERE `person_id` = ?1;", id), 0);
+        assert_eq!(count(&conn, "SELECT COUNT(*) FROM `Log` WHERE `entity_id` = ?1;", id), 1);
+    }
+}
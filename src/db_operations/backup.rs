@@ -0,0 +1,337 @@
+//! Backups of a camp database through SQLite's online backup API.
+//!
+//! Backups live in `<camp>.backups/` next to the camp file, one file per backup named after
+//! when and why it was taken and how many persons and log rows it holds
+//! (`20260717-143000-123-scheduled-p42-l1337.db`), so listing them opens none of them.
+//! They are copied from the open connection, so the app keeps running meanwhile. Backups of
+//! an encrypted camp are encrypted with its passphrase at the time (encrypting a camp
+//! encrypts the backups it already has); after a passphrase change older ones still need
+//! the old one. Pre-migration backups (`*.bak`, see `migrations.rs`) are separate and
+//! never rotated. Anonymizing a person deletes both kinds.
+
+use std::cmp::Reverse;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+use chrono::{Local, NaiveDateTime};
+use rusqlite::backup::Backup;
+use rusqlite::{Connection, OpenFlags};
+
+use super::encryption::{apply_key, is_encrypted};
+use super::migrations::{latest_version, schema_version};
+use super::{schema, DbError, DbResult};
+
+// Local time; sorts like the time itself, so file names sort oldest first.
+const STAMP_FORMAT: &str = "%Y%m%d-%H%M%S-%3f";
+const STAMP_LEN: usize = "20260717-143000-123".len();
+const EXTENSION: &str = "db";
+const PAGES_PER_STEP: i32 = 256;
+
+/// Why a backup was taken; part of its file name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BackupReason {
+    /// The camp was opened, at startup or by switching camps.
+    Opened,
+    /// The periodic backup.
+    Scheduled,
+    /// Right before purging or anonymizing data.
+    BeforeDelete,
+    /// Right before a restore replaced the data.
+    BeforeRestore,
+    /// Asked for on the Settings screen.
+    Manual,
+    /// Right before automatic repairs (see `integrity.rs`).
+    BeforeRepair,
+}
+
+impl BackupReason {
+    const ALL: [BackupReason; 6] = [
+        BackupReason::Opened,
+        BackupReason::Scheduled,
+        BackupReason::BeforeDelete,
+        BackupReason::BeforeRestore,
+        BackupReason::Manual,
+        BackupReason::BeforeRepair,
+    ];
+
+    fn as_str(self) -> &'static str {
+        match self {
+            BackupReason::Opened => "opened",
+            BackupReason::Scheduled => "scheduled",
+            BackupReason::BeforeDelete => "before-delete",
+            BackupReason::BeforeRestore => "before-restore",
+            BackupReason::Manual => "manual",
+            BackupReason::BeforeRepair => "before-repair",
+        }
+    }
+}
+
+/// One backup file, as listed on the restore screen.
+#[derive(Debug, Clone)]
+pub struct BackupInfo {
+    pub path: PathBuf,
+    /// Local time.
+    pub taken_at: NaiveDateTime,
+    pub reason: BackupReason,
+    pub size: u64,
+    /// Rows in `Person` and `Log` when the backup was taken.
+    pub persons: i64,
+    pub logs: i64,
+}
+
+/// Folder with the backups of the camp at `db_path`.
+pub fn backup_dir(db_path: &Path) -> PathBuf {
+    let stem = db_path.file_stem().map_or_else(|| "database".into(), |s| s.to_string_lossy());
+    db_path.with_file_name(format!("{stem}.backups"))
+}
+
+/// Copies the database behind `conn` into a new backup file and returns its path.
+///
+/// `passphrase` is the camp's current one; plaintext camps ignore it. The copy is written
+/// under a temporary name first, so an interrupted backup never shows up in the list.
+pub fn create_backup(conn: &Connection, passphrase: Option<&str>, reason: BackupReason) -> DbResult<PathBuf> {
+    let db_path = database_file(conn)?;
+    let dir = backup_dir(&db_path);
+    fs::create_dir_all(&dir).map_err(|e| file_error(&dir, e))?;
+
+    let (persons, logs): (i64, i64) = conn.query_row(
+        "SELECT (SELECT COUNT(*) FROM `Person`), (SELECT COUNT(*) FROM `Log`);",
+        [],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )?;
+    let path = loop {
+        let path = dir.join(format!(
+            "{}-{}-p{persons}-l{logs}.{EXTENSION}",
+            Local::now().format(STAMP_FORMAT),
+            reason.as_str()
+        ));
+        if !path.exists() {
+            break path;
+        }
+        // Another backup this millisecond; the name must stay unique or it would be replaced.
+        thread::sleep(Duration::from_millis(1));
+    };
+    let partial = path.with_extension(format!("{EXTENSION}.partial"));
+    let _ = fs::remove_file(&partial);
+
+    if let Err(e) = write_backup(conn, &db_path, &partial, passphrase) {
+        let _ = fs::remove_file(&partial);
+        return Err(e);
+    }
+    fs::rename(&partial, &path).map_err(|e| file_error(&path, e))?;
+    Ok(path)
+}
+
+/// Deletes all but the `keep` newest backups of each reason of the camp at `db_path`;
+/// returns how many went. Counting per reason keeps the scheduled backups from pushing out
+/// the ones taken before a delete, restore or repair.
+pub fn rotate_backups(db_path: &Path, keep: usize) -> DbResult<usize> {
+    let files = backup_files(db_path)?;
+    let mut deleted = 0;
+    for reason in BackupReason::ALL {
+        // `backup_files` lists newest first.
+        for BackupInfo { path, .. } in files.iter().filter(|b| b.reason == reason).skip(keep) {
+            fs::remove_file(path).map_err(|e| file_error(path, e))?;
+            deleted += 1;
+        }
+    }
+    Ok(deleted)
+}
+
+/// Deletes every backup of the camp at `db_path`; returns how many went. Used after an
+/// anonymization, since each of them still holds the removed data.
+pub fn delete_backups(db_path: &Path) -> DbResult<usize> {
+    let files = backup_files(db_path)?;
+    for BackupInfo { path, .. } in &files {
+        fs::remove_file(path).map_err(|e| file_error(path, e))?;
+    }
+    Ok(files.len())
+}
+
+/// Backups of the camp at `db_path`, newest first.
+pub fn list_backups(db_path: &Path) -> DbResult<Vec<BackupInfo>> {
+    backup_files(db_path)
+}
+
+/// Replaces the data behind `conn` with the backup at `backup`, migrating it if it was
+/// taken by an older version. The data it replaces is backed up first (`BeforeRestore`),
+/// and that backup's path is returned, so a restore can itself be undone.
+pub fn restore_backup(conn: &mut Connection, backup: &Path, passphrase: Option<&str>) -> DbResult<PathBuf> {
+    let db_path = database_file(conn)?;
+    if is_encrypted(backup) != is_encrypted(&db_path) {
+        return Err(DbError::ConstraintViolation(
+            "the backup was taken before the camp was encrypted; open it as a camp instead".to_string(),
+        ));
+    }
+
+    let source = open_backup(backup, passphrase)?;
+    let check: String = source.query_row("PRAGMA quick_check;", [], |row| row.get(0))?;
+    if check != "ok" {
+        return Err(DbError::Corrupt(format!("backup {}: {check}", backup.display())));
+    }
+    let version = schema_version(&source)?;
+    if version > latest_version() {
+        return Err(DbError::SchemaTooNew {
+            found: version,
+            supported: latest_version(),
+        });
+    }
+
+    let safety = create_backup(conn, passphrase, BackupReason::BeforeRestore)?;
+    Backup::new(&source, conn)?.run_to_completion(PAGES_PER_STEP, Duration::ZERO, None)?;
+    conn.flush_prepared_statement_cache();
+    schema::ensure_schema(conn)?;
+    Ok(safety)
+}
+
+fn write_backup(conn: &Connection, db_path: &Path, target: &Path, passphrase: Option<&str>) -> DbResult<()> {
+    let mut target = Connection::open(target)?;
+    // SQLCipher only copies pages between databases under the same key.
+    if is_encrypted(db_path) {
+        apply_key(&target, passphrase.filter(|p| !p.is_empty()).ok_or(DbError::PassphraseRequired)?)?;
+    }
+    Backup::new(conn, &mut target)?.run_to_completion(PAGES_PER_STEP, Duration::ZERO, None)?;
+    Ok(())
+}
+
+fn open_backup(path: &Path, passphrase: Option<&str>) -> DbResult<Connection> {
+    let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX)?;
+    if is_encrypted(path) {
+        apply_key(&conn, passphrase.filter(|p| !p.is_empty()).ok_or(DbError::PassphraseRequired)?)?;
+    }
+    Ok(conn)
+}
+
+// Files in the backup folder named like backups, newest first. Anything else there is left alone.
+fn backup_files(db_path: &Path) -> DbResult<Vec<BackupInfo>> {
+    let dir = backup_dir(db_path);
+    let entries = match fs::read_dir(&dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(file_error(&dir, e)),
+    };
+    let mut files: Vec<_> = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let (taken_at, reason, persons, logs) = parse_file_name(&entry.file_name().to_string_lossy())?;
+            Some(BackupInfo {
+                size: entry.metadata().map_or(0, |m| m.len()),
+                path: entry.path(),
+                taken_at,
+                reason,
+                persons,
+                logs,
+            })
+        })
+        .collect();
+    files.sort_by_key(|b| Reverse(b.taken_at));
+    Ok(files)
+}
+
+// `<stamp>-<reason>-p<persons>-l<logs>.db`, see `create_backup`.
+fn parse_file_name(name: &str) -> Option<(NaiveDateTime, BackupReason, i64, i64)> {
+    let stem = name.strip_suffix(&format!(".{EXTENSION}"))?;
+    let (stamp, rest) = (stem.get(..STAMP_LEN)?, stem.get(STAMP_LEN..)?.strip_prefix('-')?);
+    let taken_at = NaiveDateTime::parse_from_str(stamp, STAMP_FORMAT).ok()?;
+    let (rest, logs) = rest.rsplit_once("-l")?;
+    let (reason, persons) = rest.rsplit_once("-p")?;
+    let reason = BackupReason::ALL.into_iter().find(|r| r.as_str() == reason)?;
+    Some((taken_at, reason, persons.parse().ok()?, logs.parse().ok()?))
+}
+
+fn database_file(conn: &Connection) -> DbResult<PathBuf> {
+    conn.path().filter(|p| !p.is_empty()).map(PathBuf::from).ok_or_else(|| DbError::Io {
+        path: PathBuf::from(":memory:"),
+        source: io::Error::new(io::ErrorKind::Unsupported, "only a database file can be backed up"),
+    })
+}
+
+fn file_error(path: &Path, source: io::Error) -> DbError {
+    DbError::Io { path: path.to_path_buf(), source }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db_operations::test_support::TempDir;
+
+    fn at(stamp: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(stamp, STAMP_FORMAT).unwrap()
+    }
+
+    #[test]
+    fn file_names_parse() {
+        assert_eq!(
+            parse_file_name("20260717-143000-123-scheduled-p42-l1337.db"),
+            Some((at("20260717-143000-123"), BackupReason::Scheduled, 42, 1337))
+        );
+        assert_eq!(
+            parse_file_name("20260717-143000-123-before-delete-p0-l0.db"),
+            Some((at("20260717-143000-123"), BackupReason::BeforeDelete, 0, 0))
+        );
+        for name in [
+            "20260717-143000-123-scheduled-p42-l1337.db.partial",
+            "20260717-143000-123-scheduled-p42-l1337.bak",
+            "20260717-143000-123-weekly-p42-l1337.db",
+            "20260717-143000-123-scheduled-p42.db",
+            "20260717-143000-123-scheduled-px-l1.db",
+            "20261317-143000-123-scheduled-p42-l1337.db",
+            "2026-scheduled-p42-l1337.db",
+            "notes.txt",
+            "",
+        ] {
+            assert_eq!(parse_file_name(name), None, "{name}");
+        }
+    }
+
+    #[test]
+    fn rotation_keeps_the_newest_of_each_reason() {
+        let dir = TempDir::new("rotate");
+        let db_path = dir.path().join("camp.db");
+        let backups = backup_dir(&db_path);
+        fs::create_dir_all(&backups).unwrap();
+        let names = [
+            "20260717-080000-000-before-delete-p5-l9.db",
+            "20260717-090000-000-scheduled-p5-l9.db",
+            "20260717-091500-000-scheduled-p5-l9.db",
+            "20260717-093000-000-scheduled-p5-l9.db",
+            "20260717-094500-000-scheduled-p5-l9.db",
+            "20260717-095000-000-before-restore-p5-l9.db",
+            "20260717-095500-000-opened-p5-l9.db",
+            "20260717-100000-000-before-delete-p5-l9.db",
+            "20260717-101000-000-before-delete-p5-l9.db",
+        ];
+        for name in names.iter().chain(&["notes.txt", "20260717-102000-000-scheduled-p5-l9.db.partial"]) {
+            fs::write(backups.join(name), b"").unwrap();
+        }
+
+        assert_eq!(rotate_backups(&db_path, 2).unwrap(), 3);
+        let mut left: Vec<String> = fs::read_dir(&backups)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        left.sort();
+        assert_eq!(
+            left,
+            [
+                "20260717-093000-000-scheduled-p5-l9.db",
+                "20260717-094500-000-scheduled-p5-l9.db",
+                "20260717-095000-000-before-restore-p5-l9.db",
+                "20260717-095500-000-opened-p5-l9.db",
+                "20260717-100000-000-before-delete-p5-l9.db",
+                "20260717-101000-000-before-delete-p5-l9.db",
+                "20260717-102000-000-scheduled-p5-l9.db.partial",
+                "notes.txt",
+            ]
+        );
+        assert_eq!(rotate_backups(&db_path, 2).unwrap(), 0);
+        assert_eq!(rotate_backups(&dir.path().join("new.db"), 2).unwrap(), 0);
+
+        // After an anonymization every backup goes; other files stay.
+        assert_eq!(delete_backups(&db_path).unwrap(), 6);
+        assert_eq!(fs::read_dir(&backups).unwrap().count(), 2);
+    }
+}
@@ -1,14 +1,34 @@
 use std::{
-    cell::RefCell,
-    rc::Rc,
+    fs::{self, create_dir_all},
+    path::Path,
 };
 
 use rusqlite::Connection;
 
-use super::{path, schema};
+use super::encryption::{apply_key, encryption_available, is_encrypted};
+use super::{schema, DbError, DbResult};
 
-pub fn get_db() -> rusqlite::Result<Rc<RefCell<Connection>>> {
-    let conn = Connection::open(path::db_path())?;
-    schema::ensure_schema(&conn)?;
-    Ok(Rc::new(RefCell::new(conn)))
+/// Opens (creating it if needed) the database at `path` and migrates it to the current schema.
+///
+/// `passphrase` unlocks an encrypted camp, and a new file opened with one is created
+/// encrypted. Plaintext camps open as they are, whatever `passphrase` says.
+pub fn open_db(path: &Path, passphrase: Option<&str>) -> DbResult<Connection> {
+    if let Some(dir) = path.parent()
+        && let Err(e) = create_dir_all(dir)
+    {
+        eprintln!("Couldn't create directory: {e}");
+    }
+
+    let encrypted = is_encrypted(path);
+    let new_file = fs::metadata(path).map_or(true, |m| m.len() == 0);
+
+    let mut conn = Connection::open(path)?;
+    match passphrase.filter(|p| !p.is_empty()) {
+        Some(passphrase) if encrypted || new_file => apply_key(&conn, passphrase)?,
+        None if encrypted && encryption_available() => return Err(DbError::PassphraseRequired),
+        None if encrypted => return Err(DbError::EncryptionUnavailable),
+        _ => {}
+    }
+    schema::ensure_schema(&mut conn)?;
+    Ok(conn)
 }
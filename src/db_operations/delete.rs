@@ -1,38 +1,134 @@
-use std::error::Error;
-
 use rusqlite::Connection;
 
-use super::{DatabaseRecord, Group, Log, Person};
+use super::archive::{archive_group, archive_person, ensure_not_built_in};
+use super::{expect_row, DatabaseRecord, DbError, DbResult, Log};
 
+/// Removes a record. Persons and groups are only archived (see `archive.rs`);
+/// use `purge_person` / `purge_group` to delete them for good.
 #[allow(dead_code)]
-pub fn delete_from_db(conn: &Connection, record: DatabaseRecord) -> Result<(), Box<dyn Error>> {
+pub fn delete_from_db(conn: &Connection, record: DatabaseRecord) -> DbResult<()> {
     match record {
-        DatabaseRecord::Person(p) => delete_person(conn, &p),
-        DatabaseRecord::Group(g) => delete_group(conn, &g),
+        DatabaseRecord::Person(p) => archive_person(conn, p.id),
+        DatabaseRecord::Group(g) => archive_group(conn, g.id),
         DatabaseRecord::GroupMembers(gid, pid) => delete_group_member(conn, gid, pid),
         DatabaseRecord::Log(l) => delete_log(conn, &l),
+        DatabaseRecord::Event(e) => delete_event(conn, e.id),
+        DatabaseRecord::EventParticipants(eid, pid) => delete_event_participant(conn, eid, pid),
     }
 }
 
-fn delete_person(conn: &Connection, person: &Person) -> Result<(), Box<dyn Error>> {
-    conn.execute("DELETE FROM `Person` WHERE `id` = ?1;", (&person.id,))?;
+/// Permanently deletes an archived person and their memberships.
+/// `Log` rows stay, so presence history is not lost.
+pub fn purge_person(conn: &Connection, person_id: i32) -> DbResult<()> {
+    let deleted = conn.execute(
+        "DELETE FROM `Person` WHERE `id` = ?1 AND `archived_at` IS NOT NULL;",
+        (&person_id,),
+    )?;
+    if deleted == 0 {
+        return Err(not_purgeable(conn, "Person", "person", person_id));
+    }
     Ok(())
 }
 
-fn delete_group(conn: &Connection, group: &Group) -> Result<(), Box<dyn Error>> {
-    conn.execute("DELETE FROM `Group` WHERE `id` = ?1;", (&group.id,))?;
+/// Permanently deletes an archived user group and its memberships.
+pub fn purge_group(conn: &Connection, group_id: i32) -> DbResult<()> {
+    ensure_not_built_in(conn, group_id)?;
+    let deleted = conn.execute(
+        "DELETE FROM `Group` WHERE `id` = ?1 AND `archived_at` IS NOT NULL;",
+        (&group_id,),
+    )?;
+    if deleted == 0 {
+        return Err(not_purgeable(conn, "Group", "group", group_id));
+    }
     Ok(())
 }
 
-fn delete_group_member(conn: &Connection, group_id: i32, person_id: i32) -> Result<(), Box<dyn Error>> {
-    conn.execute(
+/// Only for user groups; built-in memberships follow the roster.
+pub(in crate::db_operations) fn delete_group_member(conn: &Connection, group_id: i32, person_id: i32) -> DbResult<()> {
+    ensure_not_built_in(conn, group_id)?;
+    let deleted = conn.execute(
         "DELETE FROM `GroupMembers` WHERE `group_id` = ?1 AND `person_id` = ?2;",
         (group_id, person_id),
     )?;
-    Ok(())
+    expect_row(deleted, "group member", person_id)
 }
 
-fn delete_log(conn: &Connection, log: &Log) -> Result<(), Box<dyn Error>> {
-    conn.execute("DELETE FROM `Log` WHERE `id` = ?1;", (&log.id,))?;
-    Ok(())
+/// Removes the person from the event. Their `Log` rows for it stay.
+pub(in crate::db_operations) fn delete_event_participant(conn: &Connection, event_id: i32, person_id: i32) -> DbResult<()> {
+    let deleted = conn.execute(
+        "DELETE FROM `EventParticipants` WHERE `event_id` = ?1 AND `person_id` = ?2;",
+        (event_id, person_id),
+    )?;
+    expect_row(deleted, "participant", person_id)
+}
+
+// Only events without any logged presence: `Log.event_id` has no ON DELETE action, so the
+// foreign key turns an attempt on a used event into `ConstraintViolation`.
+fn delete_event(conn: &Connection, event_id: i32) -> DbResult<()> {
+    let deleted = conn.execute("DELETE FROM `Event` WHERE `id` = ?1;", (event_id,))?;
+    expect_row(deleted, "event", event_id)
+}
+
+fn delete_log(conn: &Connection, log: &Log) -> DbResult<()> {
+    let deleted = conn.execute("DELETE FROM `Log` WHERE `id` = ?1;", (&log.id,))?;
+    expect_row(deleted, "log", log.id)
+}
+
+// Tells "doesn't exist" apart from "exists but is still active".
+fn not_purgeable(conn: &Connection, table: &str, entity: &'static str, id: i32) -> DbError {
+    let exists = conn.query_row(
+        &format!("SELECT COUNT(*) FROM `{table}` WHERE `id` = ?1;"),
+        (id,),
+        |row| row.get::<_, i32>(0),
+    );
+    match exists {
+        Ok(0) => DbError::NotFound { entity, id },
+        Ok(_) => DbError::ConstraintViolation(format!("{entity} {id} must be archived before it can be purged")),
+        Err(e) => e.into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use super::delete_from_db;
+    use crate::db_operations::test_support::{memory_db, person};
+    use crate::db_operations::{DatabaseRecord, DbError, Event, EventRepository, IsInside, Methodology, PersonRepository, SqliteRepository};
+
+    fn event(name: &str) -> Event {
+        Event {
+            id: 0,
+            name: name.to_string(),
+            starts_on: None,
+            ends_on: None,
+            location: String::new(),
+            retention_days: None,
+        }
+    }
+
+    #[test]
+    fn only_events_without_logs_can_be_deleted() {
+        let conn = Rc::new(RefCell::new(memory_db()));
+        let repo = SqliteRepository::new(conn.clone());
+        let id = repo.add_person(&person("Jan", "Kowalski", Methodology::Scout)).unwrap();
+        let used = repo.add_event(&event("Used")).unwrap();
+        let unused = repo.add_event(&event("Unused")).unwrap();
+        for event_id in [used, unused] {
+            repo.add_participant(event_id, id).unwrap();
+        }
+        repo.set_presence(used, &[id], IsInside::In, None).unwrap();
+
+        let delete = |event_id| {
+            let mut event = event("");
+            event.id = event_id;
+            delete_from_db(&conn.borrow(), DatabaseRecord::Event(event))
+        };
+        assert!(matches!(delete(used), Err(DbError::ConstraintViolation(_))));
+        delete(unused).unwrap();
+        assert!(matches!(delete(unused), Err(DbError::NotFound { entity: "event", .. })));
+        let events: Vec<i32> = repo.events().unwrap().iter().map(|e| e.id).collect();
+        assert!(events.contains(&used) && !events.contains(&unused));
+        assert_eq!(repo.participants(used).unwrap().len(), 1);
+    }
 }
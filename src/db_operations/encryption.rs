@@ -0,0 +1,197 @@
+//! Optional encryption at rest (SQLCipher).
+//!
+//! Whether a camp file is encrypted is read from its header, so the UI can ask for the
+//! passphrase before opening it. Builds without the `encryption` feature link plain SQLite:
+//! they still open plaintext camps and report encrypted ones as `EncryptionUnavailable`.
+
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use rusqlite::{Connection, OptionalExtension};
+
+use super::backup::list_backups;
+use super::connection::open_db;
+use super::migrations::{migration_backups, schema_version};
+use super::{DbError, DbResult};
+
+// First 16 bytes of every plaintext SQLite file; SQLCipher files start with random salt.
+const PLAINTEXT_HEADER: &[u8; 16] = b"SQLite format 3\0";
+
+/// `true` when `path` holds data that isn't a plaintext SQLite database.
+/// Missing and empty files are not encrypted: they become whatever the opener makes of them.
+pub fn is_encrypted(path: &Path) -> bool {
+    let Ok(mut file) = File::open(path) else {
+        return false;
+    };
+    let mut header = [0u8; 16];
+    match file.read_exact(&mut header) {
+        Ok(()) => header != *PLAINTEXT_HEADER,
+        // Shorter than a header: empty (new) or not a database at all; opening will tell.
+        Err(_) => false,
+    }
+}
+
+/// `true` if this build links SQLCipher.
+pub fn encryption_available() -> bool {
+    Connection::open_in_memory()
+        .and_then(|conn| conn.query_row("PRAGMA cipher_version;", [], |row| row.get::<_, String>(0)).optional())
+        .is_ok_and(|version| version.is_some())
+}
+
+/// Unlocks a connection opened on an encrypted (or new, to-be-encrypted) file.
+/// Must run before anything reads the database.
+pub(in crate::db_operations) fn apply_key(conn: &Connection, passphrase: &str) -> DbResult<()> {
+    if !encryption_available() {
+        return Err(DbError::EncryptionUnavailable);
+    }
+    conn.pragma_update(None, "key", passphrase)?;
+    // SQLCipher only checks the key when the first page is read.
+    match conn.query_row("SELECT COUNT(*) FROM `sqlite_master`;", [], |row| row.get::<_, i64>(0)) {
+        Ok(_) => Ok(()),
+        Err(rusqlite::Error::SqliteFailure(e, _)) if e.code == rusqlite::ErrorCode::NotADatabase => {
+            Err(DbError::WrongPassphrase)
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Rewrites the plaintext database behind `conn` as an encrypted one and reopens it, then
+/// encrypts its copies the same way: the pre-migration backups (`*.bak`) and the backups in
+/// `<camp>.backups/`.
+///
+/// The data is exported into a new file next to the original, which then replaces it, so a
+/// failure part-way leaves the plaintext file untouched. Returns the copies that are still
+/// plaintext because that failed for them; the camp itself is encrypted either way.
+pub fn encrypt_database(conn: &mut Connection, passphrase: &str) -> DbResult<Vec<PathBuf>> {
+    check_passphrase(passphrase)?;
+    if !encryption_available() {
+        return Err(DbError::EncryptionUnavailable);
+    }
+    let Some(path) = conn.path().filter(|p| !p.is_empty()).map(PathBuf::from) else {
+        return Err(DbError::ConstraintViolation("only a database file can be encrypted".to_string()));
+    };
+    if is_encrypted(&path) {
+        return Err(DbError::ConstraintViolation("the database is already encrypted".to_string()));
+    }
+
+    let encrypted_path = encrypting_path(&path);
+    let _ = fs::remove_file(&encrypted_path);
+
+    if let Err(e) = export_encrypted(conn, &encrypted_path, passphrase) {
+        let _ = fs::remove_file(&encrypted_path);
+        return Err(e);
+    }
+
+    // The file can only be replaced once nothing has it open (Windows refuses otherwise).
+    let old = std::mem::replace(conn, Connection::open_in_memory()?);
+    if let Err((old, e)) = old.close() {
+        *conn = old;
+        let _ = fs::remove_file(&encrypted_path);
+        return Err(e.into());
+    }
+    let replaced = fs::rename(&encrypted_path, &path);
+    *conn = open_db(&path, Some(passphrase))?;
+    replaced.map_err(|source| DbError::Io { path: path.clone(), source })?;
+
+    Ok(plaintext_copies(&path)
+        .into_iter()
+        .filter(|copy| encrypt_copy(copy, passphrase).is_err())
+        .collect())
+}
+
+/// New passphrase for an encrypted database. Takes effect immediately; the open connection
+/// stays usable.
+pub fn change_passphrase(conn: &Connection, passphrase: &str) -> DbResult<()> {
+    check_passphrase(passphrase)?;
+    let encrypted = conn.path().is_some_and(|p| is_encrypted(Path::new(p)));
+    if !encrypted {
+        return Err(DbError::ConstraintViolation("the database is not encrypted".to_string()));
+    }
+    conn.pragma_update(None, "rekey", passphrase)?;
+    Ok(())
+}
+
+fn export_encrypted(conn: &Connection, target: &Path, passphrase: &str) -> DbResult<()> {
+    conn.execute(
+        "ATTACH DATABASE ?1 AS `encrypted` KEY ?2;",
+        (target.to_string_lossy(), passphrase),
+    )?;
+    let exported = conn
+        .query_row("SELECT sqlcipher_export('encrypted');", [], |_| Ok(()))
+        .and_then(|()| {
+            // `sqlcipher_export` copies schema and rows, not the header fields.
+            let version = schema_version(conn)?;
+            conn.pragma_update(Some("encrypted"), "user_version", version)
+        });
+    conn.execute("DETACH DATABASE `encrypted`;", ())?;
+    Ok(exported?)
+}
+
+// Backups of the camp at `db_path` that are still plaintext, see `encrypt_database`.
+fn plaintext_copies(db_path: &Path) -> Vec<PathBuf> {
+    let mut copies: Vec<PathBuf> = list_backups(db_path).unwrap_or_default().into_iter().map(|b| b.path).collect();
+    copies.extend(migration_backups(db_path));
+    copies.retain(|copy| !is_encrypted(copy));
+    copies
+}
+
+// Replaces the plaintext database file `path` with an encrypted copy, like `encrypt_database`.
+fn encrypt_copy(path: &Path, passphrase: &str) -> DbResult<()> {
+    let encrypted_path = encrypting_path(path);
+    let _ = fs::remove_file(&encrypted_path);
+    let exported = Connection::open(path)
+        .map_err(DbError::from)
+        .and_then(|conn| export_encrypted(&conn, &encrypted_path, passphrase));
+    if let Err(e) = exported {
+        let _ = fs::remove_file(&encrypted_path);
+        return Err(e);
+    }
+    fs::rename(&encrypted_path, path).map_err(|source| DbError::Io { path: path.to_path_buf(), source })
+}
+
+fn encrypting_path(path: &Path) -> PathBuf {
+    let mut encrypting = path.to_path_buf().into_os_string();
+    encrypting.push(".encrypting");
+    PathBuf::from(encrypting)
+}
+
+fn check_passphrase(passphrase: &str) -> DbResult<()> {
+    if passphrase.is_empty() {
+        return Err(DbError::ConstraintViolation("the passphrase can't be empty".to_string()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db_operations::test_support::TempDir;
+    use crate::db_operations::{create_backup, BackupReason};
+
+    #[test]
+    fn encrypting_a_camp_encrypts_its_backups() {
+        if !encryption_available() {
+            return;
+        }
+        let dir = TempDir::new("encrypt");
+        let path = dir.path().join("camp.db");
+        let mut conn = open_db(&path, None).unwrap();
+        let backup = create_backup(&conn, None, BackupReason::Manual).unwrap();
+        let bak = dir.path().join("camp.db.v3-20260101-120000.bak");
+        conn.execute("VACUUM INTO ?1;", [bak.to_string_lossy()]).unwrap();
+        // Not a copy of this camp; left alone.
+        let other = dir.path().join("other.db.v3-20260101-120000.bak");
+        conn.execute("VACUUM INTO ?1;", [other.to_string_lossy()]).unwrap();
+
+        assert_eq!(encrypt_database(&mut conn, "secret").unwrap(), Vec::<PathBuf>::new());
+        for file in [&path, &backup, &bak] {
+            assert!(is_encrypted(file), "{} is plaintext", file.display());
+            let copy = Connection::open(file).unwrap();
+            apply_key(&copy, "secret").unwrap();
+            assert_eq!(schema_version(&copy).unwrap(), schema_version(&conn).unwrap());
+        }
+        assert!(!is_encrypted(&other));
+        assert!(!fs::read_dir(dir.path()).unwrap().any(|e| e.unwrap().file_name().to_string_lossy().ends_with(".encrypting")));
+    }
+}
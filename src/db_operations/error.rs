@@ -0,0 +1,133 @@
+//! Error type shared by every `db_operations` function.
+//!
+//! `rusqlite` errors are classified once in `From<rusqlite::Error>` so callers can
+//! match on what went wrong instead of parsing messages.
+
+use std::path::PathBuf;
+use std::{fmt, io};
+
+use rusqlite::ffi;
+
+pub type DbResult<T> = Result<T, DbError>;
+
+#[derive(Debug)]
+pub enum DbError {
+    /// No row with this id exists.
+    NotFound { entity: &'static str, id: i32 },
+    /// A primary key or unique constraint rejected the write.
+    Duplicate(String),
+    /// Any other constraint (foreign key, CHECK, NOT NULL) rejected the write.
+    ConstraintViolation(String),
+    /// A stored integer doesn't map to any variant of a DB-backed enum.
+    InvalidEnumValue { enum_name: &'static str, value: i64 },
+    /// A stored value can't be decoded (e.g. a malformed timestamp).
+    Corrupt(String),
+    /// The file was written by a newer build; opening it could silently drop data.
+    SchemaTooNew { found: i32, supported: i32 },
+    /// A bulk operation was rolled back; lists every id that failed and why.
+    BatchFailed(Vec<(i32, DbError)>),
+    /// The database is encrypted and no passphrase was given.
+    PassphraseRequired,
+    /// The passphrase doesn't unlock the database.
+    WrongPassphrase,
+    /// The database is encrypted but this build has no SQLCipher (`encryption` feature off).
+    EncryptionUnavailable,
+    /// A file next to the database (backup, encrypted copy, ...) couldn't be read, written or removed.
+    Io { path: PathBuf, source: io::Error },
+    Sqlite(rusqlite::Error),
+}
+
+/// Carried inside `FromSqlError::Other` so that enum decoding failures survive
+/// rusqlite's error wrapping and can be recognised in `From<rusqlite::Error>`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InvalidEnumValue {
+    pub enum_name: &'static str,
+    pub value: i64,
+}
+
+impl fmt::Display for InvalidEnumValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid value {} for enum {}", self.value, self.enum_name)
+    }
+}
+
+impl std::error::Error for InvalidEnumValue {}
+
+impl fmt::Display for DbError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DbError::NotFound { entity, id } => write!(f, "{entity} {id} does not exist"),
+            DbError::Duplicate(msg) => write!(f, "duplicate entry: {msg}"),
+            DbError::ConstraintViolation(msg) => write!(f, "constraint violation: {msg}"),
+            DbError::InvalidEnumValue { enum_name, value } => {
+                write!(f, "invalid value {value} for enum {enum_name}")
+            }
+            DbError::Corrupt(msg) => write!(f, "corrupt data: {msg}"),
+            DbError::SchemaTooNew { found, supported } => write!(
+                f,
+                "database schema version {found} is newer than the newest supported version {supported}"
+            ),
+            DbError::BatchFailed(failures) => {
+                write!(f, "operation rolled back, {} id(s) failed:", failures.len())?;
+                for (id, e) in failures {
+                    write!(f, " [{id}: {e}]")?;
+                }
+                Ok(())
+            }
+            DbError::PassphraseRequired => write!(f, "the database is encrypted; a passphrase is required"),
+            DbError::WrongPassphrase => write!(f, "wrong passphrase"),
+            DbError::EncryptionUnavailable => write!(f, "this build has no encryption support"),
+            DbError::Io { path, source } => write!(f, "{}: {source}", path.display()),
+            DbError::Sqlite(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for DbError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            DbError::Sqlite(e) => Some(e),
+            DbError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+/// Turns "statement touched no rows" into `NotFound` for single-row writes.
+pub(in crate::db_operations) fn expect_row(affected: usize, entity: &'static str, id: i32) -> DbResult<()> {
+    if affected == 0 {
+        return Err(DbError::NotFound { entity, id });
+    }
+    Ok(())
+}
+
+impl From<InvalidEnumValue> for DbError {
+    fn from(e: InvalidEnumValue) -> Self {
+        DbError::InvalidEnumValue {
+            enum_name: e.enum_name,
+            value: e.value,
+        }
+    }
+}
+
+impl From<rusqlite::Error> for DbError {
+    fn from(e: rusqlite::Error) -> Self {
+        match e {
+            rusqlite::Error::SqliteFailure(code, msg) if code.code == ffi::ErrorCode::ConstraintViolation => {
+                let msg = msg.unwrap_or_else(|| code.to_string());
+                match code.extended_code {
+                    ffi::SQLITE_CONSTRAINT_PRIMARYKEY | ffi::SQLITE_CONSTRAINT_UNIQUE => DbError::Duplicate(msg),
+                    _ => DbError::ConstraintViolation(msg),
+                }
+            }
+            rusqlite::Error::FromSqlConversionFailure(_, _, inner) => match inner.downcast::<InvalidEnumValue>() {
+                Ok(invalid) => (*invalid).into(),
+                Err(other) => DbError::Corrupt(other.to_string()),
+            },
+            rusqlite::Error::IntegralValueOutOfRange(column, value) => {
+                DbError::Corrupt(format!("value {value} out of range in column {column}"))
+            }
+            other => DbError::Sqlite(other),
+        }
+    }
+}
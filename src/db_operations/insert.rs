@@ -1,63 +1,115 @@
-use std::error::Error;
-
 use rusqlite::Connection;
 
-use super::{DatabaseRecord, Group, Log, Person};
+use super::archive::ensure_not_built_in;
+use super::schema::sync_built_in_memberships;
+use super::{format_db_date, format_db_datetime, format_guardians, DatabaseRecord, DbResult, Event, Group, GroupKind, Log, Person};
 
-pub fn insert_to_db(conn: &Connection, record: DatabaseRecord) -> Result<(), Box<dyn Error>> {
+#[allow(dead_code)]
+pub fn insert_to_db(conn: &Connection, record: DatabaseRecord) -> DbResult<()> {
     match record {
-        DatabaseRecord::Person(p) => insert_person(conn, &p),
-        DatabaseRecord::Group(g) => insert_group(conn, &g),
+        DatabaseRecord::Person(p) => insert_person(conn, &p).map(|_| ()),
+        DatabaseRecord::Group(g) => insert_group(conn, &g).map(|_| ()),
         DatabaseRecord::GroupMembers(gid, pid) => insert_group_member(conn, gid, pid),
-        DatabaseRecord::Log(l) => insert_log(conn, &l),
+        DatabaseRecord::Log(l) => insert_log(conn, &l).map(|_| ()),
+        DatabaseRecord::Event(e) => insert_event(conn, &e).map(|_| ()),
+        DatabaseRecord::EventParticipants(eid, pid) => insert_event_participant(conn, eid, pid),
     }
 }
 
-fn insert_person(conn: &Connection, person: &Person) -> Result<(), Box<dyn Error>> {
-    conn.execute(
-        "INSERT INTO `Person`(`name`, `surname`, `rank_level`, `methodology`, `is_inside`) VALUES(?1, ?2, ?3, ?4, ?5);",
-        (
+/// Inserts the person plus their automatic memberships; returns the new id.
+pub(in crate::db_operations) fn insert_person(conn: &Connection, person: &Person) -> DbResult<i32> {
+    let tx = conn.unchecked_transaction()?;
+    tx.execute(
+        "INSERT INTO `Person`(`name`, `surname`, `rank_level`, `methodology`, `nickname`, `patrol`, `date_of_birth`,
+            `address`, `guardians`, `allergies`, `diet`, `medical_notes`, `note`)
+         VALUES(?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13);",
+        rusqlite::params![
             &person.name,
             &person.surname,
             person.rank_level as i32,
             person.methodology as i32,
-            &person.is_inside,
-        ),
+            &person.nickname,
+            &person.patrol,
+            person.date_of_birth.as_ref().map(format_db_date),
+            &person.address,
+            format_guardians(&person.guardians),
+            &person.allergies,
+            &person.diet,
+            &person.medical_notes,
+            &person.note,
+        ],
     )?;
 
-    let person_id: i32 = conn.last_insert_rowid() as i32;
-    let group_id: i32 = person.methodology as i32 + 2; // +2 because db starts from 1 and 1 is reserved for everyone
+    let person_id: i32 = tx.last_insert_rowid() as i32;
+    sync_built_in_memberships(&tx, Some(person_id))?;
+    tx.commit()?;
+
+    Ok(person_id)
+}
 
+/// Always creates a user group; built-in groups come from the schema.
+pub(in crate::db_operations) fn insert_group(conn: &Connection, group: &Group) -> DbResult<i32> {
     conn.execute(
-        "INSERT INTO `GroupMembers`(`group_id`, `person_id`) VALUES (?1, ?2);",
-        (group_id, person_id),
+        "INSERT INTO `Group`(`name`, `kind`) VALUES(?1, ?2);",
+        (&group.name, GroupKind::User),
     )?;
+    Ok(conn.last_insert_rowid() as i32)
+}
+
+/// Only for user groups; built-in memberships follow the roster.
+pub(in crate::db_operations) fn insert_group_member(conn: &Connection, group_id: i32, person_id: i32) -> DbResult<()> {
+    ensure_not_built_in(conn, group_id)?;
     conn.execute(
-        "INSERT INTO `GroupMembers`(`group_id`, `person_id`) VALUES (1, ?1);",
-        (person_id,),
+        "INSERT INTO `GroupMembers`(`group_id`, `person_id`) VALUES(?1, ?2);",
+        (group_id, person_id),
     )?;
-
     Ok(())
 }
 
-fn insert_group(conn: &Connection, group: &Group) -> Result<(), Box<dyn Error>> {
-    conn.execute("INSERT INTO `Group`(`name`) VALUES(?1);", (&group.name,))?;
-    Ok(())
+pub(in crate::db_operations) fn insert_log(conn: &Connection, log: &Log) -> DbResult<i32> {
+    let time_str = format_db_datetime(&log.time);
+    let snapshot = log.snapshot.as_ref();
+    conn.execute(
+        "INSERT INTO `Log`(`entity_type`, `entity_id`, `is_inside`, `timestamp`, `batch_id`, `name`, `surname`, `rank_level`, `methodology`, `groups`, `parent_id`, `operator`, `event_id`)
+         VALUES(?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
+        rusqlite::params![
+            &log.entity_type,
+            &log.entity_id,
+            &log.is_inside,
+            &time_str,
+            &log.batch_id,
+            snapshot.map(|s| &s.name),
+            snapshot.map(|s| &s.surname),
+            snapshot.map(|s| s.rank_level),
+            snapshot.map(|s| s.methodology),
+            snapshot.filter(|s| !s.groups.is_empty()).map(|s| s.groups.join("\n")),
+            &log.parent_id,
+            &log.operator,
+            &log.event_id,
+        ],
+    )?;
+    Ok(conn.last_insert_rowid() as i32)
 }
 
-fn insert_group_member(conn: &Connection, group_id: i32, person_id: i32) -> Result<(), Box<dyn Error>> {
+pub(in crate::db_operations) fn insert_event(conn: &Connection, event: &Event) -> DbResult<i32> {
     conn.execute(
-        "INSERT INTO `GroupMembers`(`group_id`, `person_id`) VALUES(?1, ?2);",
-        (group_id, person_id),
+        "INSERT INTO `Event`(`name`, `starts_on`, `ends_on`, `location`, `retention_days`) VALUES(?1, ?2, ?3, ?4, ?5);",
+        (
+            &event.name,
+            event.starts_on.as_ref().map(format_db_date),
+            event.ends_on.as_ref().map(format_db_date),
+            &event.location,
+            event.retention_days,
+        ),
     )?;
-    Ok(())
+    Ok(conn.last_insert_rowid() as i32)
 }
 
-fn insert_log(conn: &Connection, log: &Log) -> Result<(), Box<dyn Error>> {
-    let time_str = log.time.format("%Y-%m-%d %H:%M:%S").to_string();
+/// Adds the person to the event, initially outside.
+pub(in crate::db_operations) fn insert_event_participant(conn: &Connection, event_id: i32, person_id: i32) -> DbResult<()> {
     conn.execute(
-        "INSERT INTO `Log`(`entity_type`, `entity_id`, `is_inside`, `timestamp`) VALUES(?1, ?2, ?3, ?4)",
-        (&log.entity_type, &log.entity_id, &log.is_inside, &time_str),
+        "INSERT INTO `EventParticipants`(`event_id`, `person_id`) VALUES(?1, ?2);",
+        (event_id, person_id),
     )?;
     Ok(())
 }
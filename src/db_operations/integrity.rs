@@ -0,0 +1,219 @@
+//! Consistency check: SQLite's own `PRAGMA integrity_check` and `foreign_key_check`, plus the
+//! invariants the rest of the code relies on but the schema can't enforce.
+//!
+//! - Every person is in the all-members group and in exactly the methodology group of their
+//!   `Person.methodology` (see `schema::sync_built_in_memberships`).
+//! - Person `Log` rows point at an existing person. Rows left behind by `purge_person` keep a
+//!   snapshot of who they were and are history, so only rows without one are reported. A
+//!   matching name is never taken as proof of identity: two persons can share one, so such
+//!   rows are only reattached to the person the operator picks (`reattach_orphaned_logs`).
+//! - `EventParticipants.is_inside` agrees with the log (see `presence.rs`).
+//!
+//! Only problems with an obvious fix are repairable; damage found by `integrity_check`
+//! needs a backup.
+
+use rusqlite::Connection;
+
+use super::presence::{presence_mismatches, reconcile_in, PresenceMismatch};
+use super::schema::{ensure_built_in_groups, sync_built_in_memberships, METHODOLOGY_GROUP_NAMES};
+use super::queries::get_person_by_id;
+use super::types::EntityType;
+use super::{expect_row, DbResult, GroupKind};
+
+/// One inconsistency found by `check_database`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Problem {
+    /// A message from `PRAGMA integrity_check`.
+    Damaged(String),
+    /// A row whose foreign key points at a missing row of `parent`.
+    DanglingReference { table: String, rowid: i64, parent: String },
+    /// The all-members group or a methodology group doesn't exist.
+    MissingBuiltInGroups,
+    /// The person isn't in the all-members group, or not in exactly their methodology group.
+    BuiltInMemberships { person_id: i32, name: String, surname: String },
+    /// `rows` person log rows point at `entity_id`, which no longer exists, and none of them
+    /// has a snapshot saying who it was.
+    OrphanedLogs { entity_id: i32, rows: i64 },
+    /// Stored presence the log disagrees with; repaired with `PresenceMismatch::default_fix`.
+    PresenceMismatch(PresenceMismatch),
+}
+
+impl Problem {
+    /// `true` when `repair` knows how to fix it.
+    pub fn is_repairable(&self) -> bool {
+        match self {
+            Problem::Damaged(_) => false,
+            Problem::DanglingReference { table, .. } => REPAIRABLE_TABLES.contains(&table.as_str()),
+            Problem::MissingBuiltInGroups | Problem::BuiltInMemberships { .. } | Problem::PresenceMismatch(_) => true,
+            Problem::OrphanedLogs { .. } => false,
+        }
+    }
+}
+
+// Link tables whose dangling rows carry nothing worth keeping.
+const REPAIRABLE_TABLES: [&str; 2] = ["GroupMembers", "EventParticipants"];
+
+/// Every problem found, most serious first; empty when the database is consistent.
+pub fn check_database(conn: &Connection) -> DbResult<Vec<Problem>> {
+    let mut problems = Vec::new();
+
+    let mut stmt = conn.prepare("PRAGMA integrity_check;")?;
+    let messages = stmt
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    problems.extend(messages.into_iter().filter(|m| m != "ok").map(Problem::Damaged));
+
+    let mut stmt = conn.prepare("PRAGMA foreign_key_check;")?;
+    let dangling = stmt
+        .query_map([], |row| {
+            Ok(Problem::DanglingReference {
+                table: row.get(0)?,
+                rowid: row.get::<_, Option<i64>>(1)?.unwrap_or_default(),
+                parent: row.get(2)?,
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    problems.extend(dangling);
+
+    let built_in_groups: usize = conn.query_row(
+        "SELECT (SELECT COUNT(*) FROM `Group` WHERE `kind` = ?1)
+              + (SELECT COUNT(DISTINCT `methodology`) FROM `Group` WHERE `kind` = ?2);",
+        (GroupKind::AllMembers, GroupKind::Methodology),
+        |row| row.get(0),
+    )?;
+    if built_in_groups < 1 + METHODOLOGY_GROUP_NAMES.len() {
+        problems.push(Problem::MissingBuiltInGroups);
+    }
+
+    let mut stmt = conn.prepare(
+        "SELECT `p`.`id`, `p`.`name`, `p`.`surname` FROM `Person` `p`
+         WHERE NOT EXISTS (
+                SELECT 1 FROM `GroupMembers` `gm` JOIN `Group` `g` ON `g`.`id` = `gm`.`group_id`
+                WHERE `gm`.`person_id` = `p`.`id` AND `g`.`kind` = ?1)
+            OR NOT EXISTS (
+                SELECT 1 FROM `GroupMembers` `gm` JOIN `Group` `g` ON `g`.`id` = `gm`.`group_id`
+                WHERE `gm`.`person_id` = `p`.`id` AND `g`.`kind` = ?2 AND `g`.`methodology` = `p`.`methodology`)
+            OR (SELECT COUNT(*) FROM `GroupMembers` `gm` JOIN `Group` `g` ON `g`.`id` = `gm`.`group_id`
+                WHERE `gm`.`person_id` = `p`.`id` AND `g`.`kind` = ?2) <> 1
+         ORDER BY `p`.`id`;",
+    )?;
+    let memberships = stmt
+        .query_map((GroupKind::AllMembers, GroupKind::Methodology), |row| {
+            Ok(Problem::BuiltInMemberships {
+                person_id: row.get(0)?,
+                name: row.get(1)?,
+                surname: row.get(2)?,
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    problems.extend(memberships);
+
+    // Rows of a purged person carry a snapshot: history, kept on purpose.
+    let mut stmt = conn.prepare(
+        "SELECT `l`.`entity_id`, COUNT(*) FROM `Log` `l`
+         WHERE `l`.`entity_type` = ?1 AND NOT EXISTS (SELECT 1 FROM `Person` `p` WHERE `p`.`id` = `l`.`entity_id`)
+         GROUP BY `l`.`entity_id`
+         HAVING COUNT(`l`.`name`) = 0
+         ORDER BY `l`.`entity_id`;",
+    )?;
+    let orphans = stmt
+        .query_map((EntityType::Person,), |row| {
+            Ok(Problem::OrphanedLogs {
+                entity_id: row.get(0)?,
+                rows: row.get(1)?,
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    problems.extend(orphans);
+
+    problems.extend(presence_mismatches(conn, None)?.into_iter().map(Problem::PresenceMismatch));
+
+    Ok(problems)
+}
+
+/// Fixes every repairable problem in `problems` in one transaction; returns how many.
+/// The others are skipped: run `check_database` again to see what is left.
+pub fn repair(conn: &Connection, problems: &[Problem]) -> DbResult<usize> {
+    let tx = conn.unchecked_transaction()?;
+    let mut repaired = 0;
+    for problem in problems.iter().filter(|p| p.is_repairable()) {
+        match problem {
+            Problem::DanglingReference { table, rowid, .. } => {
+                tx.execute(&format!("DELETE FROM `{table}` WHERE `rowid` = ?1;"), (rowid,))?;
+            }
+            Problem::MissingBuiltInGroups => {
+                ensure_built_in_groups(&tx)?;
+                sync_built_in_memberships(&tx, None)?;
+            }
+            Problem::BuiltInMemberships { person_id, .. } => sync_built_in_memberships(&tx, Some(*person_id))?,
+            Problem::PresenceMismatch(mismatch) => reconcile_in(&tx, mismatch, mismatch.default_fix())?,
+            Problem::Damaged(_) | Problem::OrphanedLogs { .. } => continue,
+        }
+        repaired += 1;
+    }
+    tx.commit()?;
+    Ok(repaired)
+}
+
+/// Attaches the orphaned person log rows of the deleted `entity_id` (`Problem::OrphanedLogs`)
+/// to `person_id`, who the operator says they were; returns how many moved. Stored presence
+/// may disagree with the log afterwards: check again to see it.
+pub fn reattach_orphaned_logs(conn: &Connection, entity_id: i32, person_id: i32) -> DbResult<usize> {
+    get_person_by_id(conn, person_id)?;
+    let moved = conn.execute(
+        "UPDATE `Log` SET `entity_id` = ?2
+         WHERE `entity_type` = ?3 AND `entity_id` = ?1 AND `name` IS NULL
+           AND NOT EXISTS (SELECT 1 FROM `Person` WHERE `id` = ?1);",
+        (entity_id, person_id, EntityType::Person),
+    )?;
+    expect_row(moved, "orphaned log", entity_id)?;
+    Ok(moved)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use super::*;
+    use crate::db_operations::test_support::{memory_db, person};
+    use crate::db_operations::{DbError, EventRepository, IsInside, Methodology, PersonRepository, PresenceLogRepository, SqliteRepository};
+
+    #[test]
+    fn orphaned_logs_are_only_reattached_to_the_picked_person() {
+        let conn = Rc::new(RefCell::new(memory_db()));
+        let repo = SqliteRepository::new(conn.clone());
+        let first = repo.add_person(&person("Jan", "Kowalski", Methodology::Scout)).unwrap();
+        repo.add_participant(1, first).unwrap();
+        repo.set_presence(1, &[first], IsInside::In, None).unwrap();
+        repo.archive_person(first).unwrap();
+        repo.purge_person(first).unwrap();
+        // Another Jan Kowalski, not the purged one.
+        let second = repo.add_person(&person("Jan", "Kowalski", Methodology::Cub)).unwrap();
+        // A row written before snapshots existed, of a person deleted since.
+        conn.borrow()
+            .execute(
+                "INSERT INTO `Log`(`entity_type`, `entity_id`, `is_inside`, `timestamp`) VALUES (?1, 99, 1, '2024-07-01 10:00:00');",
+                (EntityType::Person,),
+            )
+            .unwrap();
+
+        let problems = check_database(&conn.borrow()).unwrap();
+        assert_eq!(problems, [Problem::OrphanedLogs { entity_id: 99, rows: 1 }]);
+        assert!(!problems[0].is_repairable());
+        assert_eq!(repair(&conn.borrow(), &problems).unwrap(), 0);
+
+        let logs = repo.person_logs().unwrap();
+        assert!(logs.iter().all(|l| l.entity_id != second));
+        assert_eq!(logs.iter().filter(|l| l.entity_id == first).count(), 1);
+
+        // The operator knows who it was.
+        assert!(matches!(reattach_orphaned_logs(&conn.borrow(), 99, 999), Err(DbError::NotFound { entity: "person", id: 999 })));
+        // The purged person's rows have a snapshot: history, not orphans.
+        assert!(matches!(reattach_orphaned_logs(&conn.borrow(), first, second), Err(DbError::NotFound { entity: "orphaned log", .. })));
+        assert_eq!(reattach_orphaned_logs(&conn.borrow(), 99, second).unwrap(), 1);
+        assert!(check_database(&conn.borrow()).unwrap().iter().all(|p| !matches!(p, Problem::OrphanedLogs { .. })));
+        let logs = repo.person_logs().unwrap();
+        assert_eq!(logs.iter().filter(|l| l.entity_id == second).count(), 1);
+        assert_eq!(logs.iter().filter(|l| l.entity_id == first).count(), 1);
+    }
+}
@@ -0,0 +1,443 @@
+//! Sentry journal: the night's shifts, checkpoint rounds and incidents, and the morning
+//! report built from them.
+//!
+//! A sentry shift is started and ended by hand; an event has at most one in progress.
+//! Checkpoints are the places a round goes past, per event; visiting one is only possible
+//! during a shift. An incident is a free-text note with a severity, tagged with any persons
+//! and groups it concerns, and tied to the shift in progress when it was written down (if any).
+//!
+//! The morning report covers a time window: the shifts overlapping it, with the checkpoints
+//! each of them never visited, and the incidents that happened in it.
+
+use chrono::{DateTime, Utc};
+use rusqlite::{Connection, OptionalExtension, Row};
+use serde::Serialize;
+
+use super::privacy::REDACTED_DESCRIPTION;
+use super::queries::{group_from_row, person_from_row, GROUP_COLUMNS, PERSON_LIST_COLUMNS};
+use super::{expect_row, format_db_datetime, parse_db_datetime, DbError, DbResult, Group, Person, Severity};
+
+/// One sentry shift, as it was actually stood.
+#[derive(Debug, Clone)]
+pub struct SentryShift {
+    pub id: i32,
+    #[allow(dead_code)]
+    pub event_id: i32,
+    pub started_at: DateTime<Utc>,
+    /// `None` while in progress.
+    pub ended_at: Option<DateTime<Utc>>,
+    /// Who stood it, as written when it started.
+    pub guards: String,
+    pub operator: Option<String>,
+    /// Oldest first.
+    pub visits: Vec<CheckpointVisit>,
+}
+
+impl SentryShift {
+    pub fn is_over(&self) -> bool {
+        self.ended_at.is_some()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Checkpoint {
+    pub id: i32,
+    pub event_id: i32,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CheckpointVisit {
+    pub id: i32,
+    pub checkpoint_id: i32,
+    pub checkpoint: String,
+    pub visited_at: DateTime<Utc>,
+}
+
+/// A journal note. Tags are left out of data exports: they name other persons.
+#[derive(Debug, Clone, Serialize)]
+pub struct Incident {
+    pub id: i32,
+    pub event_id: i32,
+    /// The shift in progress when it was recorded.
+    pub shift_id: Option<i32>,
+    pub occurred_at: DateTime<Utc>,
+    pub severity: Severity,
+    pub description: String,
+    pub operator: Option<String>,
+    /// Tagged persons (archived ones too), by id.
+    #[serde(skip)]
+    pub persons: Vec<Person>,
+    /// Tagged groups (archived ones too), by id.
+    #[serde(skip)]
+    pub groups: Vec<Group>,
+}
+
+/// What to record as an incident.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NewIncident {
+    pub occurred_at: DateTime<Utc>,
+    pub severity: Severity,
+    pub description: String,
+    pub person_ids: Vec<i32>,
+    pub group_ids: Vec<i32>,
+}
+
+/// The journal of one event between `from` and `to`, for the commandant.
+#[derive(Debug, Clone)]
+pub struct MorningReport {
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    /// Shifts overlapping the window, oldest first.
+    pub shifts: Vec<SentryShift>,
+    /// Incidents in the window, oldest first.
+    pub incidents: Vec<Incident>,
+    /// Every checkpoint of the event.
+    pub checkpoints: Vec<Checkpoint>,
+}
+
+impl MorningReport {
+    /// Checkpoints `shift` never visited.
+    pub fn missed_checkpoints(&self, shift: &SentryShift) -> Vec<&Checkpoint> {
+        self.checkpoints
+            .iter()
+            .filter(|c| !shift.visits.iter().any(|v| v.checkpoint_id == c.id))
+            .collect()
+    }
+}
+
+/// The report of `from..to` from all of the event's shifts, incidents and checkpoints.
+pub(super) fn build_report(
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    shifts: Vec<SentryShift>,
+    incidents: Vec<Incident>,
+    checkpoints: Vec<Checkpoint>,
+) -> MorningReport {
+    MorningReport {
+        from,
+        to,
+        shifts: shifts
+            .into_iter()
+            .filter(|s| s.started_at < to && s.ended_at.is_none_or(|end| end > from))
+            .collect(),
+        incidents: incidents
+            .into_iter()
+            .filter(|i| i.occurred_at >= from && i.occurred_at < to)
+            .collect(),
+        checkpoints,
+    }
+}
+
+pub(super) fn shift_in_progress_error(event_id: i32, shift_id: i32) -> DbError {
+    DbError::ConstraintViolation(format!("event {event_id} already has sentry shift {shift_id} in progress"))
+}
+
+pub(super) fn shift_over_error(shift_id: i32) -> DbError {
+    DbError::ConstraintViolation(format!("sentry shift {shift_id} is over"))
+}
+
+pub(super) fn empty_text_error(what: &str) -> DbError {
+    DbError::ConstraintViolation(format!("{what} can't be empty"))
+}
+
+fn ensure_event(conn: &Connection, event_id: i32) -> DbResult<()> {
+    let exists: bool = conn.query_row("SELECT EXISTS(SELECT 1 FROM `Event` WHERE `id` = ?1);", (event_id,), |row| row.get(0))?;
+    if !exists {
+        return Err(DbError::NotFound { entity: "event", id: event_id });
+    }
+    Ok(())
+}
+
+fn shift_in_progress(conn: &Connection, event_id: i32) -> DbResult<Option<i32>> {
+    let id = conn
+        .query_row(
+            "SELECT `id` FROM `SentryShift` WHERE `event_id` = ?1 AND `ended_at` IS NULL;",
+            (event_id,),
+            |row| row.get(0),
+        )
+        .optional()?;
+    Ok(id)
+}
+
+/// Starts a sentry shift of event `event_id` stood by `guards`; returns its id.
+/// `ConstraintViolation` if one is already in progress.
+pub fn start_sentry_shift(conn: &Connection, event_id: i32, guards: &str, operator: Option<&str>) -> DbResult<i32> {
+    let tx = conn.unchecked_transaction()?;
+    ensure_event(&tx, event_id)?;
+    if let Some(id) = shift_in_progress(&tx, event_id)? {
+        return Err(shift_in_progress_error(event_id, id));
+    }
+    tx.execute(
+        "INSERT INTO `SentryShift`(`event_id`, `started_at`, `guards`, `operator`) VALUES(?1, ?2, ?3, ?4);",
+        (event_id, format_db_datetime(&Utc::now()), guards.trim(), operator),
+    )?;
+    let id = tx.last_insert_rowid() as i32;
+    tx.commit()?;
+    Ok(id)
+}
+
+pub fn end_sentry_shift(conn: &Connection, shift_id: i32) -> DbResult<()> {
+    let tx = conn.unchecked_transaction()?;
+    ensure_shift_in_progress(&tx, shift_id)?;
+    tx.execute(
+        "UPDATE `SentryShift` SET `ended_at` = ?2 WHERE `id` = ?1;",
+        (shift_id, format_db_datetime(&Utc::now())),
+    )?;
+    tx.commit()?;
+    Ok(())
+}
+
+// The shift's event; `NotFound` for an unknown shift, `ConstraintViolation` once it is over.
+fn ensure_shift_in_progress(conn: &Connection, shift_id: i32) -> DbResult<i32> {
+    let shift: Option<(i32, Option<String>)> = conn
+        .query_row(
+            "SELECT `event_id`, `ended_at` FROM `SentryShift` WHERE `id` = ?1;",
+            (shift_id,),
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()?;
+    match shift {
+        None => Err(DbError::NotFound { entity: "sentry shift", id: shift_id }),
+        Some((_, Some(_))) => Err(shift_over_error(shift_id)),
+        Some((event_id, None)) => Ok(event_id),
+    }
+}
+
+/// Sentry shifts of event `event_id` with their visits, oldest first.
+pub fn get_sentry_shifts(conn: &Connection, event_id: i32) -> DbResult<Vec<SentryShift>> {
+    let mut stmt = conn.prepare_cached(
+        "SELECT `id`, `started_at`, `ended_at`, `guards`, `operator` FROM `SentryShift`
+         WHERE `event_id` = ?1 ORDER BY `started_at`, `id`;",
+    )?;
+    let mut shifts = stmt
+        .query_map((event_id,), |row| {
+            let started_at: String = row.get(1)?;
+            let ended_at: Option<String> = row.get(2)?;
+            Ok(SentryShift {
+                id: row.get(0)?,
+                event_id,
+                started_at: parse_db_datetime(&started_at)?,
+                ended_at: ended_at.map(|t| parse_db_datetime(&t)).transpose()?,
+                guards: row.get(3)?,
+                operator: row.get(4)?,
+                visits: Vec::new(),
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    let mut stmt = conn.prepare_cached(
+        "SELECT `v`.`id`, `v`.`checkpoint_id`, `c`.`name`, `v`.`visited_at`
+         FROM `CheckpointVisit` `v` JOIN `Checkpoint` `c` ON `c`.`id` = `v`.`checkpoint_id`
+         WHERE `v`.`shift_id` = ?1 ORDER BY `v`.`visited_at`, `v`.`id`;",
+    )?;
+    for shift in &mut shifts {
+        shift.visits = stmt
+            .query_map((shift.id,), |row| {
+                let visited_at: String = row.get(3)?;
+                Ok(CheckpointVisit {
+                    id: row.get(0)?,
+                    checkpoint_id: row.get(1)?,
+                    checkpoint: row.get(2)?,
+                    visited_at: parse_db_datetime(&visited_at)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+    }
+    Ok(shifts)
+}
+
+/// Adds a checkpoint to event `event_id`; returns its id. `Duplicate` for a name the event
+/// already has.
+pub fn add_checkpoint(conn: &Connection, event_id: i32, name: &str) -> DbResult<i32> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(empty_text_error("a checkpoint name"));
+    }
+    ensure_event(conn, event_id)?;
+    conn.execute("INSERT INTO `Checkpoint`(`event_id`, `name`) VALUES(?1, ?2);", (event_id, name))?;
+    Ok(conn.last_insert_rowid() as i32)
+}
+
+/// Checkpoints of event `event_id`, in the order they were added.
+pub fn get_checkpoints(conn: &Connection, event_id: i32) -> DbResult<Vec<Checkpoint>> {
+    let mut stmt = conn.prepare_cached("SELECT `id`, `name` FROM `Checkpoint` WHERE `event_id` = ?1 ORDER BY `id`;")?;
+    let checkpoints = stmt
+        .query_map((event_id,), |row| {
+            Ok(Checkpoint {
+                id: row.get(0)?,
+                event_id,
+                name: row.get(1)?,
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(checkpoints)
+}
+
+/// Records a visit of `checkpoint_id` now, during the shift in progress `shift_id`.
+/// `NotFound` for a checkpoint of another event.
+pub fn visit_checkpoint(conn: &Connection, shift_id: i32, checkpoint_id: i32) -> DbResult<()> {
+    let tx = conn.unchecked_transaction()?;
+    let event_id = ensure_shift_in_progress(&tx, shift_id)?;
+    let same_event: bool = tx.query_row(
+        "SELECT EXISTS(SELECT 1 FROM `Checkpoint` WHERE `id` = ?1 AND `event_id` = ?2);",
+        (checkpoint_id, event_id),
+        |row| row.get(0),
+    )?;
+    if !same_event {
+        return Err(DbError::NotFound { entity: "checkpoint", id: checkpoint_id });
+    }
+    tx.execute(
+        "INSERT INTO `CheckpointVisit`(`shift_id`, `checkpoint_id`, `visited_at`) VALUES(?1, ?2, ?3);",
+        (shift_id, checkpoint_id, format_db_datetime(&Utc::now())),
+    )?;
+    tx.commit()?;
+    Ok(())
+}
+
+/// Records an incident of event `event_id`; returns its id. Tagged persons and groups must
+/// be active (`NotFound` otherwise); an empty description is a `ConstraintViolation`.
+pub fn record_incident(conn: &Connection, event_id: i32, incident: &NewIncident, operator: Option<&str>) -> DbResult<i32> {
+    let description = incident.description.trim();
+    if description.is_empty() {
+        return Err(empty_text_error("an incident description"));
+    }
+    let tx = conn.unchecked_transaction()?;
+    ensure_event(&tx, event_id)?;
+    for &person_id in &incident.person_ids {
+        let active: bool = tx.query_row(
+            "SELECT EXISTS(SELECT 1 FROM `Person` WHERE `id` = ?1 AND `archived_at` IS NULL);",
+            (person_id,),
+            |row| row.get(0),
+        )?;
+        if !active {
+            return Err(DbError::NotFound { entity: "person", id: person_id });
+        }
+    }
+    for &group_id in &incident.group_ids {
+        let active: bool = tx.query_row(
+            "SELECT EXISTS(SELECT 1 FROM `Group` WHERE `id` = ?1 AND `archived_at` IS NULL);",
+            (group_id,),
+            |row| row.get(0),
+        )?;
+        if !active {
+            return Err(DbError::NotFound { entity: "group", id: group_id });
+        }
+    }
+
+    tx.execute(
+        "INSERT INTO `Incident`(`event_id`, `shift_id`, `occurred_at`, `severity`, `description`, `operator`)
+         VALUES(?1, ?2, ?3, ?4, ?5, ?6);",
+        (
+            event_id,
+            shift_in_progress(&tx, event_id)?,
+            format_db_datetime(&incident.occurred_at),
+            incident.severity,
+            description,
+            operator,
+        ),
+    )?;
+    let id = tx.last_insert_rowid() as i32;
+    for &person_id in &incident.person_ids {
+        tx.execute(
+            "INSERT OR IGNORE INTO `IncidentPerson`(`incident_id`, `person_id`) VALUES(?1, ?2);",
+            (id, person_id),
+        )?;
+    }
+    for &group_id in &incident.group_ids {
+        tx.execute(
+            "INSERT OR IGNORE INTO `IncidentGroup`(`incident_id`, `group_id`) VALUES(?1, ?2);",
+            (id, group_id),
+        )?;
+    }
+    tx.commit()?;
+    Ok(id)
+}
+
+const INCIDENT_COLUMNS: &str =
+    "`i`.`id`, `i`.`event_id`, `i`.`shift_id`, `i`.`occurred_at`, `i`.`severity`, `i`.`description`, `i`.`operator`";
+
+// An incident from `INCIDENT_COLUMNS`, without its tags.
+fn incident_from_row(row: &Row) -> rusqlite::Result<Incident> {
+    let occurred_at: String = row.get(3)?;
+    Ok(Incident {
+        id: row.get(0)?,
+        event_id: row.get(1)?,
+        shift_id: row.get(2)?,
+        occurred_at: parse_db_datetime(&occurred_at)?,
+        severity: row.get(4)?,
+        description: row.get(5)?,
+        operator: row.get(6)?,
+        persons: Vec::new(),
+        groups: Vec::new(),
+    })
+}
+
+/// Incidents of event `event_id` with their tags, oldest first.
+pub fn get_incidents(conn: &Connection, event_id: i32) -> DbResult<Vec<Incident>> {
+    let mut stmt = conn.prepare_cached(&format!(
+        "SELECT {INCIDENT_COLUMNS} FROM `Incident` `i` WHERE `i`.`event_id` = ?1 ORDER BY `i`.`occurred_at`, `i`.`id`;"
+    ))?;
+    let mut incidents = stmt
+        .query_map((event_id,), incident_from_row)?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    let mut persons = conn.prepare_cached(&format!(
+        "SELECT {PERSON_LIST_COLUMNS} FROM `IncidentPerson` `ip` JOIN `Person` `p` ON `p`.`id` = `ip`.`person_id`
+         WHERE `ip`.`incident_id` = ?1 ORDER BY `p`.`id`;"
+    ))?;
+    let mut groups = conn.prepare_cached(&format!(
+        "SELECT {GROUP_COLUMNS} FROM `IncidentGroup` `ig` JOIN `Group` `g` ON `g`.`id` = `ig`.`group_id`
+         WHERE `ig`.`incident_id` = ?1 ORDER BY `g`.`id`;"
+    ))?;
+    for incident in &mut incidents {
+        incident.persons = persons
+            .query_map((incident.id,), |row| person_from_row(row, 0))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        incident.groups = groups
+            .query_map((incident.id,), |row| group_from_row(row, 0))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+    }
+    Ok(incidents)
+}
+
+/// Incidents `person_id` is tagged in, oldest first, without tags (for `export_person`).
+pub(super) fn person_incidents(conn: &Connection, person_id: i32) -> DbResult<Vec<Incident>> {
+    let mut stmt = conn.prepare_cached(&format!(
+        "SELECT {INCIDENT_COLUMNS} FROM `IncidentPerson` `ip` JOIN `Incident` `i` ON `i`.`id` = `ip`.`incident_id`
+         WHERE `ip`.`person_id` = ?1 ORDER BY `i`.`occurred_at`, `i`.`id`;"
+    ))?;
+    let incidents = stmt
+        .query_map((person_id,), incident_from_row)?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(incidents)
+}
+
+/// Replaces the description of every incident `person_id` is tagged in (see
+/// `privacy::anonymize_person`); time, severity and tags stay.
+pub(super) fn redact_person_incidents(tx: &Connection, person_id: i32) -> DbResult<()> {
+    tx.execute(
+        "UPDATE `Incident` SET `description` = ?2
+         WHERE `id` IN (SELECT `incident_id` FROM `IncidentPerson` WHERE `person_id` = ?1);",
+        (person_id, REDACTED_DESCRIPTION),
+    )?;
+    Ok(())
+}
+
+/// The journal of event `event_id` between `from` and `to`.
+pub fn morning_report(conn: &Connection, event_id: i32, from: DateTime<Utc>, to: DateTime<Utc>) -> DbResult<MorningReport> {
+    ensure_event(conn, event_id)?;
+    Ok(build_report(
+        from,
+        to,
+        get_sentry_shifts(conn, event_id)?,
+        get_incidents(conn, event_id)?,
+        get_checkpoints(conn, event_id)?,
+    ))
+}
+
+/// Deletes incident `incident_id`, e.g. one written by mistake.
+pub fn delete_incident(conn: &Connection, incident_id: i32) -> DbResult<()> {
+    let deleted = conn.execute("DELETE FROM `Incident` WHERE `id` = ?1;", (incident_id,))?;
+    expect_row(deleted, "incident", incident_id)
+}
@@ -0,0 +1,100 @@
+//! Leave passes: where someone went, why, with whom and when they should be back.
+//!
+//! A pass belongs to the OUT `Log` row written by `update::leave_with_pass`, which gives it
+//! its event, person and departure time. The person's next IN row in the same event closes
+//! it (`return_log_id`), see `update::write_presence_rows`.
+
+use rusqlite::{Connection, Row};
+
+use super::types::EntityType;
+use super::{format_db_datetime, parse_db_datetime, DbResult, LeaveDetails, LeavePass};
+
+// Use with `leave_pass_from_row`.
+const LEAVE_PASS_COLUMNS: &str = "`lp`.`id`, `lp`.`log_id`, `l`.`event_id`, `l`.`entity_id`, `l`.`timestamp`, `lp`.`destination`, \
+     `lp`.`reason`, `lp`.`companion`, `lp`.`expected_return`, `lp`.`return_log_id`, `r`.`timestamp`";
+const LEAVE_PASS_FROM: &str = "`LeavePass` `lp`
+     JOIN `Log` `l` ON `l`.`id` = `lp`.`log_id`
+     LEFT JOIN `Log` `r` ON `r`.`id` = `lp`.`return_log_id`";
+
+fn leave_pass_from_row(row: &Row) -> rusqlite::Result<LeavePass> {
+    let left: String = row.get(4)?;
+    let expected_return: Option<String> = row.get(8)?;
+    let returned: Option<String> = row.get(10)?;
+    Ok(LeavePass {
+        id: row.get(0)?,
+        log_id: row.get(1)?,
+        event_id: row.get(2)?,
+        person_id: row.get(3)?,
+        left: parse_db_datetime(&left)?,
+        details: LeaveDetails {
+            destination: row.get(5)?,
+            reason: row.get(6)?,
+            companion: row.get(7)?,
+            expected_return: expected_return.map(|t| parse_db_datetime(&t)).transpose()?,
+        },
+        return_log_id: row.get(9)?,
+        returned: returned.map(|t| parse_db_datetime(&t)).transpose()?,
+    })
+}
+
+/// Passes of event `event_id` not closed yet, oldest departure first.
+pub fn open_leave_passes(conn: &Connection, event_id: i32) -> DbResult<Vec<LeavePass>> {
+    let mut stmt = conn.prepare_cached(&format!(
+        "SELECT {LEAVE_PASS_COLUMNS} FROM {LEAVE_PASS_FROM}
+         WHERE `l`.`event_id` = ?1 AND `lp`.`return_log_id` IS NULL
+         ORDER BY `l`.`timestamp`, `l`.`id`;"
+    ))?;
+    let passes = stmt
+        .query_map((event_id,), leave_pass_from_row)?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(passes)
+}
+
+/// Every pass of person `person_id`, oldest departure first.
+pub(super) fn person_leave_passes(conn: &Connection, person_id: i32) -> DbResult<Vec<LeavePass>> {
+    let mut stmt = conn.prepare_cached(&format!(
+        "SELECT {LEAVE_PASS_COLUMNS} FROM {LEAVE_PASS_FROM}
+         WHERE `l`.`entity_type` = ?1 AND `l`.`entity_id` = ?2
+         ORDER BY `l`.`timestamp`, `l`.`id`;"
+    ))?;
+    let passes = stmt
+        .query_map((EntityType::Person, person_id), leave_pass_from_row)?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(passes)
+}
+
+/// Writes the pass of OUT row `log_id`.
+pub(super) fn insert_leave_pass(tx: &Connection, log_id: i32, details: &LeaveDetails) -> DbResult<()> {
+    tx.execute(
+        "INSERT INTO `LeavePass`(`log_id`, `destination`, `reason`, `companion`, `expected_return`) VALUES(?1, ?2, ?3, ?4, ?5);",
+        (
+            log_id,
+            &details.destination,
+            details.reason,
+            &details.companion,
+            details.expected_return.as_ref().map(format_db_datetime),
+        ),
+    )?;
+    Ok(())
+}
+
+/// Closes the open passes of `person_id` in event `event_id` with IN row `return_log_id`.
+pub(super) fn close_leave_passes(tx: &Connection, event_id: i32, person_id: i32, return_log_id: i32) -> DbResult<()> {
+    tx.execute(
+        "UPDATE `LeavePass` SET `return_log_id` = ?4
+         WHERE `return_log_id` IS NULL AND `log_id` IN (
+             SELECT `id` FROM `Log` WHERE `entity_type` = ?1 AND `entity_id` = ?3 AND `event_id` = ?2);",
+        (EntityType::Person, event_id, person_id, return_log_id),
+    )?;
+    Ok(())
+}
+
+/// Clears the free text of every pass of `person_id` (see `privacy::anonymize_person`).
+pub(super) fn anonymize_leave_passes(tx: &Connection, person_id: i32) -> DbResult<()> {
+    tx.execute(
+        "UPDATE `LeavePass` SET `destination` = '', `companion` = ''
+         WHERE `log_id` IN (SELECT `id` FROM `Log` WHERE `entity_type` = ?1 AND `entity_id` = ?2);",
+        (EntityType::Person, person_id),
+    )?;
+    Ok(())
+}
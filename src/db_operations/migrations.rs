@@ -0,0 +1,265 @@
+//! Versioned schema migrations.
+//!
+//! The schema version lives in SQLite's `PRAGMA user_version` header field.
+//! Every step in `schema::MIGRATIONS` moves the database exactly one version up and
+//! runs inside its own transaction, so a failing step leaves the previous version intact.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chrono::Local;
+use rusqlite::{Connection, Transaction};
+
+use super::schema::MIGRATIONS;
+use super::{DbError, DbResult};
+
+pub(in crate::db_operations) struct Migration {
+    pub version: i32,
+    pub description: &'static str,
+    pub up: fn(&Transaction) -> rusqlite::Result<()>,
+}
+
+pub(in crate::db_operations) fn latest_version() -> i32 {
+    MIGRATIONS.last().map_or(0, |m| m.version)
+}
+
+pub(in crate::db_operations) fn schema_version(conn: &Connection) -> rusqlite::Result<i32> {
+    conn.pragma_query_value(None, "user_version", |row| row.get(0))
+}
+
+pub(in crate::db_operations) fn migrate(conn: &mut Connection) -> DbResult<()> {
+    let current = schema_version(conn)?;
+    let latest = latest_version();
+
+    if current > latest {
+        return Err(DbError::SchemaTooNew {
+            found: current,
+            supported: latest,
+        });
+    }
+    if current == latest {
+        return Ok(());
+    }
+
+    // Databases created before versioning report version 0 but already have tables.
+    if has_user_tables(conn)? {
+        backup_before_migration(conn, current)?;
+    }
+
+    // Table rebuilds need foreign keys off; each step is checked with
+    // `foreign_key_check` before it commits instead.
+    conn.pragma_update(None, "foreign_keys", false)?;
+    let result = run_pending(conn, current);
+    conn.pragma_update(None, "foreign_keys", true)?;
+    result
+}
+
+fn run_pending(conn: &mut Connection, current: i32) -> DbResult<()> {
+    for migration in MIGRATIONS.iter().filter(|m| m.version > current) {
+        eprintln!(
+            "Migrating database to version {}: {}",
+            migration.version, migration.description
+        );
+
+        let tx = conn.transaction()?;
+        (migration.up)(&tx)?;
+        check_foreign_keys(&tx)?;
+        tx.pragma_update(None, "user_version", migration.version)?;
+        tx.commit()?;
+    }
+
+    Ok(())
+}
+
+fn has_user_tables(conn: &Connection) -> rusqlite::Result<bool> {
+    let count: i32 = conn.query_row(
+        "SELECT COUNT(*) FROM `sqlite_master` WHERE `type` = 'table' AND `name` NOT LIKE 'sqlite_%';",
+        [],
+        |row| row.get(0),
+    )?;
+    Ok(count > 0)
+}
+
+fn check_foreign_keys(tx: &Transaction) -> rusqlite::Result<()> {
+    let mut stmt = tx.prepare("PRAGMA foreign_key_check;")?;
+    let mut rows = stmt.query([])?;
+    if let Some(row) = rows.next()? {
+        let table: String = row.get(0)?;
+        return Err(rusqlite::Error::SqliteFailure(
+            rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_CONSTRAINT_FOREIGNKEY),
+            Some(format!("foreign key violation in `{table}` after migration")),
+        ));
+    }
+    Ok(())
+}
+
+fn backup_before_migration(conn: &Connection, from_version: i32) -> rusqlite::Result<()> {
+    // In-memory and temporary databases have no file to protect.
+    let Some(db_file) = conn.path().filter(|p| !p.is_empty()) else {
+        return Ok(());
+    };
+
+    let stamp = Local::now().format("%Y%m%d-%H%M%S");
+    let backup_file = format!("{db_file}.v{from_version}-{stamp}.bak");
+
+    // `VACUUM INTO` writes a consistent snapshot through the open connection.
+    conn.execute("VACUUM INTO ?1;", (&backup_file,))?;
+    eprintln!("Database backed up to {backup_file}");
+
+    Ok(())
+}
+
+/// Pre-migration backups of the camp at `db_path` (`{camp file}.v{version}-{stamp}.bak`),
+/// in no particular order.
+pub(in crate::db_operations) fn migration_backups(db_path: &Path) -> Vec<PathBuf> {
+    let (Some(dir), Some(name)) = (db_path.parent(), db_path.file_name()) else {
+        return Vec::new();
+    };
+    let prefix = format!("{}.v", name.to_string_lossy());
+    let entries = fs::read_dir(if dir.as_os_str().is_empty() { Path::new(".") } else { dir });
+    entries
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.ok()?.file_name().to_string_lossy().into_owned();
+            (name.starts_with(&prefix) && name.ends_with(".bak")).then(|| dir.join(name))
+        })
+        .collect()
+}
+
+/// Deletes the pre-migration backups of the camp at `db_path`; returns how many went.
+/// Nothing rotates them, so after an anonymization they would keep the removed data for good.
+pub fn delete_migration_backups(db_path: &Path) -> DbResult<usize> {
+    let backups = migration_backups(db_path);
+    for path in &backups {
+        fs::remove_file(path).map_err(|source| DbError::Io { path: path.clone(), source })?;
+    }
+    Ok(backups.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, fs, rc::Rc};
+
+    use rusqlite::Connection;
+
+    use super::{delete_migration_backups, latest_version};
+    use crate::db_operations::test_support::TempDir;
+    use crate::db_operations::{
+        check_database, open_db, DbError, EventRepository, GroupKind, GroupRepository, IsInside, Methodology, PersonRepository,
+        PresenceLogRepository, SqliteRepository,
+    };
+
+    // The schema `ensure_schema` created before versioning, with a small camp in it.
+    const BASELINE: &str = "
+        CREATE TABLE `Person`(
+            `id` INTEGER PRIMARY KEY AUTOINCREMENT,
+            `name` TEXT NOT NULL,
+            `surname` TEXT NOT NULL,
+            `rank_level` INTEGER NOT NULL,
+            `methodology` INTEGER NOT NULL,
+            `is_inside` BOOLEAN NOT NULL DEFAULT 0
+        );
+        CREATE TABLE `Group`(
+            `id` INTEGER PRIMARY KEY AUTOINCREMENT,
+            `name` TEXT NOT NULL
+        );
+        CREATE TABLE `GroupMembers`(
+            `group_id` INTEGER NOT NULL REFERENCES `Group`(`id`) ON DELETE CASCADE,
+            `person_id` INTEGER NOT NULL REFERENCES `Person`(`id`) ON DELETE CASCADE,
+            PRIMARY KEY (group_id, person_id)
+        );
+        CREATE TABLE `Log`(
+            `id` INTEGER PRIMARY KEY AUTOINCREMENT,
+            `entity_type` INTEGER NOT NULL CHECK(`entity_type` IN (0, 1)),
+            `entity_id` INTEGER NOT NULL,
+            `is_inside` BOOLEAN NOT NULL DEFAULT 0,
+            `timestamp` TEXT DEFAULT (datetime('now'))
+        );
+        INSERT INTO `Group`(`id`, `name`) VALUES
+            (1, 'Camp'), (2, 'Cub'), (3, 'Scout'), (4, 'Venture Scout'), (5, 'Rover'), (6, 'Wolves');
+        INSERT INTO `Person`(`id`, `name`, `surname`, `rank_level`, `methodology`, `is_inside`) VALUES
+            (1, 'Jan', 'Kowalski', 0, 1, 1),
+            (2, 'Ola', 'Nowak', 0, 0, 0);
+        INSERT INTO `GroupMembers`(`group_id`, `person_id`) VALUES (1, 1), (3, 1), (6, 1), (1, 2), (2, 2);
+        INSERT INTO `Log`(`entity_type`, `entity_id`, `is_inside`, `timestamp`) VALUES
+            (0, 2, 1, '2024-07-01 10:00:00'),
+            (0, 2, 0, '2024-07-01 12:00:00'),
+            (0, 1, 1, '2024-07-01 13:00:00');";
+
+    #[test]
+    fn baseline_database_migrates_to_latest() {
+        let dir = TempDir::new("migrate");
+        let path = dir.path().join("camp.db");
+        Connection::open(&path).unwrap().execute_batch(BASELINE).unwrap();
+
+        let conn = open_db(&path, None).unwrap();
+        let version: i32 = conn.pragma_query_value(None, "user_version", |row| row.get(0)).unwrap();
+        assert_eq!(version, latest_version());
+        assert_eq!(version, 15);
+        assert!(check_database(&conn).unwrap().is_empty());
+
+        let backups: Vec<String> = fs::read_dir(dir.path())
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+            .filter(|name| name.starts_with("camp.db.v0-") && name.ends_with(".bak"))
+            .collect();
+        assert_eq!(backups.len(), 1);
+
+        let repo = SqliteRepository::new(Rc::new(RefCell::new(conn)));
+        let persons = repo.persons().unwrap();
+        assert_eq!(persons.iter().map(|p| p.surname.as_str()).collect::<Vec<_>>(), ["Kowalski", "Nowak"]);
+        assert_eq!(persons[0].methodology, Methodology::Scout);
+
+        let groups = repo.groups_with_members().unwrap();
+        let kind_of = |name: &str| groups.iter().find(|g| g.name == name).map(|g| g.kind);
+        assert_eq!(kind_of("Camp"), Some(GroupKind::AllMembers));
+        assert_eq!(kind_of("Cub"), Some(GroupKind::Methodology));
+        assert_eq!(kind_of("Wolves"), Some(GroupKind::User));
+        let wolves = groups.iter().find(|g| g.name == "Wolves").unwrap();
+        assert_eq!(wolves.members.iter().map(|p| p.id).collect::<Vec<_>>(), [1]);
+
+        // Presence and history moved into the implicit first event.
+        let event_id = repo.active_event().unwrap().expect("an active event");
+        let participants = repo.participants(event_id).unwrap();
+        let inside = |id: i32| participants.iter().find(|p| p.person.id == id).map(|p| p.is_inside);
+        assert_eq!(inside(1), Some(IsInside::In));
+        assert_eq!(inside(2), Some(IsInside::Out));
+        let logs = repo.person_logs().unwrap();
+        assert_eq!(logs.len(), 3);
+        assert!(logs.iter().all(|l| l.event_id == Some(event_id)));
+    }
+
+    #[test]
+    fn newer_database_is_refused() {
+        let dir = TempDir::new("too-new");
+        let path = dir.path().join("camp.db");
+        drop(open_db(&path, None).unwrap());
+        Connection::open(&path).unwrap().pragma_update(None, "user_version", latest_version() + 1).unwrap();
+
+        match open_db(&path, None) {
+            Err(DbError::SchemaTooNew { found, supported }) => {
+                assert_eq!((found, supported), (latest_version() + 1, latest_version()));
+            }
+            other => panic!("expected SchemaTooNew, got {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn only_this_camps_migration_backups_are_deleted() {
+        let dir = TempDir::new("migration-backups");
+        let path = dir.path().join("camp.db");
+        let others = ["camp.db", "other.db.v3-20260101-120000.bak", "camp.db.v3-20260101-120000.db"];
+        for name in ["camp.db.v0-20260101-120000.bak", "camp.db.v14-20260301-080000.bak"].iter().chain(&others) {
+            fs::write(dir.path().join(name), b"").unwrap();
+        }
+
+        assert_eq!(delete_migration_backups(&path).unwrap(), 2);
+        let mut left: Vec<String> = fs::read_dir(dir.path())
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        left.sort();
+        assert_eq!(left, ["camp.db", "camp.db.v3-20260101-120000.db", "other.db.v3-20260101-120000.bak"]);
+    }
+}
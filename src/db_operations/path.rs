@@ -3,18 +3,10 @@ use std::path::PathBuf;
 
 use cfg_if::cfg_if;
 
-pub(super) fn db_path() -> PathBuf {
+/// App data directory; camp databases created from the UI live under it.
+pub fn data_dir() -> PathBuf {
     cfg_if! {
-        if #[cfg(target_os = "windows")] {
-            // NOTE: This is currently hardcoded for the author's machine.
-            // Consider switching back to `dirs::data_dir()` (commented below) for portability.
-            // let base = dirs::data_dir().unwrap_or_else(|| PathBuf::from("."));
-            // let dir = base.join("eWartownik");
-            let dir = PathBuf::from("C:/Users/fanok/Desktop/programowanie/Rust/eWartownik/db");
-        } else if #[cfg(target_os = "linux")] {
-            let base = dirs::data_dir().unwrap_or_else(|| PathBuf::from("."));
-            let dir = base.join("eWartownik");
-        } else if #[cfg(target_os = "macos")] {
+        if #[cfg(any(target_os = "windows", target_os = "linux", target_os = "macos"))] {
             let base = dirs::data_dir().unwrap_or_else(|| PathBuf::from("."));
             let dir = base.join("eWartownik");
         } else if #[cfg(target_os = "android")] {
@@ -31,5 +23,10 @@ pub(super) fn db_path() -> PathBuf {
         eprintln!("Couldn't create directory: {e}");
     }
 
-    dir.join("database.db")
+    dir
+}
+
+/// The single database used before camp profiles existed.
+pub fn legacy_db_path() -> PathBuf {
+    data_dir().join("database.db")
 }
@@ -0,0 +1,197 @@
+//! Presence derived from the `Log`.
+//!
+//! `EventParticipants.is_inside` is a copy kept for fast reads; the log is the history it
+//! was written from. A person's presence in an event at time `t` is that of their newest
+//! person row of the event written at or before `t` (ties go to the higher id, like the
+//! logs screen order); with no such row they are outside.
+//!
+//! The copy can drift from the log (presence carried over by the events migration, edits by
+//! hand, older versions), so `presence_mismatches` lists the disagreements and
+//! `reconcile_presence` settles one of them either way.
+
+use chrono::{DateTime, Utc};
+use rusqlite::{Connection, OptionalExtension};
+
+use super::queries::{log_from_row, LOG_COLUMNS};
+use super::types::EntityType;
+use super::update::write_presence_rows;
+use super::{expect_row, format_db_datetime, parse_db_datetime, DbError, DbResult, IsInside, Log};
+
+/// A person's presence in an event at some moment, as the log tells it.
+#[derive(Debug, Clone)]
+pub struct PresenceState {
+    pub person_id: i32,
+    pub is_inside: IsInside,
+    /// The row that decided it; `None` when nothing was logged for the person by then.
+    /// Its snapshot names persons purged since.
+    pub last: Option<Log>,
+}
+
+/// A participant whose stored presence disagrees with their newest log row.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PresenceMismatch {
+    pub event_id: i32,
+    pub event_name: String,
+    pub person_id: i32,
+    pub name: String,
+    pub surname: String,
+    /// `EventParticipants.is_inside`.
+    pub stored: IsInside,
+    /// What the log says; `Out` when nothing was logged.
+    pub logged: IsInside,
+    /// Time of the newest log row, if any.
+    pub since: Option<DateTime<Utc>>,
+}
+
+impl PresenceMismatch {
+    /// The fix a repair picks without asking: the log wins, unless it has nothing on the
+    /// person in this event; then the stored state gets its first row.
+    pub fn default_fix(&self) -> ReconcileWith {
+        if self.since.is_some() { ReconcileWith::Log } else { ReconcileWith::Stored }
+    }
+}
+
+/// Which side `reconcile_presence` keeps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReconcileWith {
+    /// Overwrite the stored presence with the logged one.
+    Log,
+    /// Log the stored presence now, as a check-in/out without an operator.
+    Stored,
+}
+
+/// Presence of every participant of event `event_id`, and of everyone logged in it, at
+/// `at`; ordered by person id.
+pub fn presence_at(conn: &Connection, event_id: i32, at: DateTime<Utc>) -> DbResult<Vec<PresenceState>> {
+    let mut stmt = conn.prepare_cached(&format!(
+        "WITH `last` AS (
+            SELECT {LOG_COLUMNS}, ROW_NUMBER() OVER (
+                PARTITION BY `l`.`entity_id` ORDER BY `l`.`timestamp` DESC, `l`.`id` DESC) AS `n`
+            FROM `Log` `l`
+            WHERE `l`.`entity_type` = ?3 AND `l`.`event_id` = ?1 AND `l`.`timestamp` <= ?2)
+         SELECT `k`.`person_id`, `last`.*
+         FROM (SELECT `person_id` FROM `EventParticipants` WHERE `event_id` = ?1
+               UNION SELECT `entity_id` FROM `last`) `k`
+         LEFT JOIN `last` ON `last`.`entity_id` = `k`.`person_id` AND `last`.`n` = 1
+         ORDER BY `k`.`person_id`;"
+    ))?;
+
+    let states = stmt
+        .query_map((event_id, format_db_datetime(&at), EntityType::Person), |row| {
+            let last = match row.get::<_, Option<i32>>(1)? {
+                Some(_) => Some(log_from_row(row, 1)?),
+                None => None,
+            };
+            Ok(PresenceState {
+                person_id: row.get(0)?,
+                is_inside: last.as_ref().map_or(IsInside::Out, |l| l.is_inside),
+                last,
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    Ok(states)
+}
+
+/// Ids of the persons inside event `event_id` at `at`, ascending.
+pub fn inside_at(conn: &Connection, event_id: i32, at: DateTime<Utc>) -> DbResult<Vec<i32>> {
+    Ok(presence_at(conn, event_id, at)?
+        .into_iter()
+        .filter(|s| s.is_inside == IsInside::In)
+        .map(|s| s.person_id)
+        .collect())
+}
+
+/// Participants of event `event_id` (of every event when `None`) whose
+/// `EventParticipants.is_inside` disagrees with the log. Archived persons are left out.
+pub fn presence_mismatches(conn: &Connection, event_id: Option<i32>) -> DbResult<Vec<PresenceMismatch>> {
+    // No log row counts as outside (`?3`).
+    let mut stmt = conn.prepare_cached(
+        "WITH `last` AS (
+            SELECT `l`.`event_id`, `l`.`entity_id`, `l`.`is_inside`, `l`.`timestamp`, ROW_NUMBER() OVER (
+                PARTITION BY `l`.`event_id`, `l`.`entity_id` ORDER BY `l`.`timestamp` DESC, `l`.`id` DESC) AS `n`
+            FROM `Log` `l`
+            WHERE `l`.`entity_type` = ?2 AND `l`.`event_id` IS NOT NULL AND (?1 IS NULL OR `l`.`event_id` = ?1))
+         SELECT `ep`.`event_id`, `e`.`name`, `p`.`id`, `p`.`name`, `p`.`surname`, `ep`.`is_inside`,
+                COALESCE(`last`.`is_inside`, ?3), `last`.`timestamp`
+         FROM `EventParticipants` `ep`
+         JOIN `Event` `e` ON `e`.`id` = `ep`.`event_id`
+         JOIN `Person` `p` ON `p`.`id` = `ep`.`person_id` AND `p`.`archived_at` IS NULL
+         LEFT JOIN `last` ON `last`.`event_id` = `ep`.`event_id` AND `last`.`entity_id` = `ep`.`person_id` AND `last`.`n` = 1
+         WHERE (?1 IS NULL OR `ep`.`event_id` = ?1) AND `ep`.`is_inside` <> COALESCE(`last`.`is_inside`, ?3)
+         ORDER BY `ep`.`event_id`, `p`.`id`;",
+    )?;
+
+    let mismatches = stmt
+        .query_map((event_id, EntityType::Person, IsInside::Out), |row| {
+            Ok(PresenceMismatch {
+                event_id: row.get(0)?,
+                event_name: row.get(1)?,
+                person_id: row.get(2)?,
+                name: row.get(3)?,
+                surname: row.get(4)?,
+                stored: row.get(5)?,
+                logged: row.get(6)?,
+                since: row
+                    .get::<_, Option<String>>(7)?
+                    .map(|t| parse_db_datetime(&t))
+                    .transpose()?,
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    Ok(mismatches)
+}
+
+/// Settles `mismatch` in favour of `with`.
+///
+/// Both sides are read again first, so a check-in or check-out since the mismatch was
+/// listed isn't undone: if they agree by now, nothing happens.
+pub fn reconcile_presence(conn: &Connection, mismatch: &PresenceMismatch, with: ReconcileWith) -> DbResult<()> {
+    let tx = conn.unchecked_transaction()?;
+    reconcile_in(&tx, mismatch, with)?;
+    tx.commit()?;
+    Ok(())
+}
+
+/// `reconcile_presence` inside the caller's transaction (see `integrity::repair`).
+pub(super) fn reconcile_in(tx: &Connection, mismatch: &PresenceMismatch, with: ReconcileWith) -> DbResult<()> {
+    let PresenceMismatch { event_id, person_id, .. } = *mismatch;
+    let stored: IsInside = tx
+        .query_row(
+            "SELECT `is_inside` FROM `EventParticipants` WHERE `event_id` = ?1 AND `person_id` = ?2;",
+            (event_id, person_id),
+            |row| row.get(0),
+        )
+        .optional()?
+        .ok_or(DbError::NotFound {
+            entity: "participant",
+            id: person_id,
+        })?;
+    let logged: IsInside = tx
+        .query_row(
+            "SELECT `is_inside` FROM `Log` WHERE `entity_type` = ?3 AND `event_id` = ?1 AND `entity_id` = ?2
+             ORDER BY `timestamp` DESC, `id` DESC LIMIT 1;",
+            (event_id, person_id, EntityType::Person),
+            |row| row.get(0),
+        )
+        .optional()?
+        .unwrap_or(IsInside::Out);
+    if stored == logged {
+        return Ok(());
+    }
+
+    match with {
+        ReconcileWith::Log => {
+            let updated = tx.execute(
+                "UPDATE `EventParticipants` SET `is_inside` = ?3 WHERE `event_id` = ?1 AND `person_id` = ?2;",
+                (event_id, person_id, logged),
+            )?;
+            expect_row(updated, "participant", person_id)
+        }
+        ReconcileWith::Stored => {
+            write_presence_rows(tx, event_id, None, &[person_id], stored, None, None)?;
+            Ok(())
+        }
+    }
+}
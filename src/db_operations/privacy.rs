@@ -0,0 +1,133 @@
+//! Personal data protection: retention, anonymization and data access exports.
+//!
+//! An event's data is due `retention_days` after it ended (its own value, else the camp-wide
+//! `AppSetting`). Events without an end date count from their last log entry, then from their
+//! start. A person is due once every event they take part in is; persons in no event are due
+//! once their newest log entry is older than the camp-wide period.
+//!
+//! Anonymizing keeps the rows, so presence statistics (counts, times, rank, methodology,
+//! groups) stay correct; only what identifies the person is replaced or cleared, leave pass
+//! destinations and companions and the descriptions of incidents they are tagged in included.
+//! Backups are files, not rows: the controller deletes all of them afterwards.
+
+use chrono::{NaiveDate, Utc};
+use rusqlite::Connection;
+
+use super::app_setting::get_retention_days;
+use super::journal::{person_incidents, redact_person_incidents};
+use super::leave_pass::{anonymize_leave_passes, person_leave_passes};
+use super::queries::{event_from_row, log_from_row, person_from_row, get_person_by_id, EVENT_COLUMNS, LOG_COLUMNS, PERSON_LIST_COLUMNS};
+use super::types::EntityType;
+use super::{format_db_date, format_db_datetime, DbError, DbResult, Participation, Person, PersonExport};
+
+/// Replaces the first name of anonymized persons, in `Person` and in `Log` snapshots.
+pub const ANONYMIZED_NAME: &str = "Anonymized";
+
+/// Replaces the description of incidents an anonymized person is tagged in.
+pub const REDACTED_DESCRIPTION: &str = "[removed]";
+
+/// Surname given to an anonymized person: their id, so rows stay tellable apart.
+pub(in crate::db_operations) fn anonymized_surname(person_id: i32) -> String {
+    format!("#{person_id}")
+}
+
+/// Persons whose retention period has passed on `today` and who are not anonymized yet.
+pub fn persons_past_retention(conn: &Connection, today: NaiveDate) -> DbResult<Vec<Person>> {
+    let default_days = get_retention_days(conn)?;
+    let mut stmt = conn.prepare(&format!(
+        "WITH `expiry` AS (
+            SELECT `e`.`id`, date(
+                COALESCE(`e`.`ends_on`, (SELECT max(date(`l`.`timestamp`)) FROM `Log` `l` WHERE `l`.`event_id` = `e`.`id`), `e`.`starts_on`),
+                '+' || COALESCE(`e`.`retention_days`, ?1) || ' days') AS `until`
+            FROM `Event` `e`
+        )
+        SELECT {PERSON_LIST_COLUMNS} FROM `Person` `p`
+        WHERE `p`.`anonymized_at` IS NULL AND CASE
+            WHEN EXISTS (SELECT 1 FROM `EventParticipants` `ep` WHERE `ep`.`person_id` = `p`.`id`) THEN NOT EXISTS (
+                SELECT 1 FROM `EventParticipants` `ep` JOIN `expiry` `x` ON `x`.`id` = `ep`.`event_id`
+                WHERE `ep`.`person_id` = `p`.`id` AND (`x`.`until` IS NULL OR `x`.`until` > ?2))
+            ELSE ?1 IS NOT NULL AND (
+                SELECT max(date(`l`.`timestamp`)) FROM `Log` `l` WHERE `l`.`entity_type` = 0 AND `l`.`entity_id` = `p`.`id`
+            ) <= date(?2, '-' || ?1 || ' days')
+        END
+        ORDER BY `p`.`id`;"
+    ))?;
+    let persons = stmt
+        .query_map((default_days, format_db_date(&today)), |row| person_from_row(row, 0))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(persons)
+}
+
+/// Removes the person's personal data for good and archives them. Presence rows, memberships
+/// and participations stay, with the name in `Log` snapshots replaced as well.
+pub fn anonymize_person(conn: &Connection, person_id: i32) -> DbResult<()> {
+    let person = get_person_by_id(conn, person_id)?;
+    if person.anonymized_at.is_some() {
+        return Err(DbError::ConstraintViolation(format!("person {person_id} is already anonymized")));
+    }
+
+    let now = format_db_datetime(&Utc::now());
+    let surname = anonymized_surname(person_id);
+    let tx = conn.unchecked_transaction()?;
+    tx.execute(
+        "UPDATE `Person` SET `name` = ?2, `surname` = ?3, `nickname` = '', `patrol` = '', `date_of_birth` = NULL,
+            `address` = '', `guardians` = '[]', `allergies` = '', `diet` = '', `medical_notes` = '', `note` = '',
+            `archived_at` = COALESCE(`archived_at`, ?4), `anonymized_at` = ?4
+         WHERE `id` = ?1;",
+        (person_id, ANONYMIZED_NAME, &surname, &now),
+    )?;
+    tx.execute(
+        "UPDATE `Log` SET `name` = ?2, `surname` = ?3 WHERE `entity_type` = ?4 AND `entity_id` = ?1;",
+        (person_id, ANONYMIZED_NAME, &surname, EntityType::Person),
+    )?;
+    anonymize_leave_passes(&tx, person_id)?;
+    redact_person_incidents(&tx, person_id)?;
+    tx.commit()?;
+    Ok(())
+}
+
+/// Everything stored about the person, archived or not.
+pub fn export_person(conn: &Connection, person_id: i32) -> DbResult<PersonExport> {
+    let person = get_person_by_id(conn, person_id)?;
+
+    let mut stmt = conn.prepare(
+        "SELECT `g`.`name` FROM `GroupMembers` `gm` JOIN `Group` `g` ON `g`.`id` = `gm`.`group_id`
+         WHERE `gm`.`person_id` = ?1 ORDER BY `g`.`id`;",
+    )?;
+    let groups = stmt
+        .query_map((person_id,), |row| row.get(0))?
+        .collect::<rusqlite::Result<Vec<String>>>()?;
+
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {EVENT_COLUMNS}, `ep`.`is_inside`
+         FROM `EventParticipants` `ep` JOIN `Event` `e` ON `e`.`id` = `ep`.`event_id`
+         WHERE `ep`.`person_id` = ?1 ORDER BY `e`.`id`;"
+    ))?;
+    let events = stmt
+        .query_map((person_id,), |row| {
+            Ok(Participation {
+                event: event_from_row(row, 0)?,
+                is_inside: row.get(6)?,
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {LOG_COLUMNS} FROM `Log` `l`
+         WHERE `l`.`entity_type` = ?2 AND `l`.`entity_id` = ?1
+         ORDER BY `l`.`timestamp`, `l`.`id`;"
+    ))?;
+    let logs = stmt
+        .query_map((person_id, EntityType::Person), |row| log_from_row(row, 0))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    Ok(PersonExport {
+        exported_at: Utc::now(),
+        person,
+        groups,
+        events,
+        logs,
+        leave_passes: person_leave_passes(conn, person_id)?,
+        incidents: person_incidents(conn, person_id)?,
+    })
+}
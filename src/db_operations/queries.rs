@@ -1,47 +1,221 @@
-use std::error::Error;
+use rusqlite::{Connection, Row};
 
-use rusqlite::Connection;
+use super::types::EntityType;
+use super::{
+    format_db_datetime, parse_db_date, parse_db_datetime, parse_guardians, DbError, DbResult, Event, Group, GroupKind, GroupWithMembers, Log, LogCursor,
+    LogEntry, LogFilter, LogPage, LogSnapshot, Participant, Person,
+};
 
-use super::{parse_db_datetime, Group, GroupWithMembers, Methodology, Person, RankLevel};
+// Column lists shared by every query that builds a `Person` / `Group`, so that adding a
+// column only touches one place. Use with `person_from_row` / `group_from_row`.
+pub(in crate::db_operations) const PERSON_COLUMNS: &str = "`p`.`id`, `p`.`name`, `p`.`surname`, `p`.`rank_level`, `p`.`methodology`, `p`.`archived_at`, \
+     `p`.`nickname`, `p`.`patrol`, `p`.`date_of_birth`, `p`.`address`, `p`.`guardians`, `p`.`allergies`, `p`.`diet`, `p`.`medical_notes`, `p`.`note`, \
+     `p`.`anonymized_at`";
+/// `PERSON_COLUMNS` for lists: the sensitive profile fields other than allergies and diet come back
+/// empty, so they are only read for the one person whose profile is opened.
+pub(in crate::db_operations) const PERSON_LIST_COLUMNS: &str = "`p`.`id`, `p`.`name`, `p`.`surname`, `p`.`rank_level`, `p`.`methodology`, `p`.`archived_at`, \
+     `p`.`nickname`, `p`.`patrol`, `p`.`date_of_birth`, '', '[]', `p`.`allergies`, `p`.`diet`, '', '', `p`.`anonymized_at`";
+/// Number of columns in `PERSON_COLUMNS` and `PERSON_LIST_COLUMNS`, for queries selecting more after them.
+pub(in crate::db_operations) const PERSON_COLUMN_COUNT: usize = 16;
+pub(in crate::db_operations) const GROUP_COLUMNS: &str =
+    "`g`.`id`, `g`.`name`, `g`.`kind`, `g`.`methodology`, `g`.`archived_at`";
+pub(in crate::db_operations) const LOG_COLUMNS: &str = "`l`.`id`, `l`.`entity_type`, `l`.`entity_id`, `l`.`is_inside`, `l`.`timestamp`, `l`.`batch_id`, \
+     `l`.`name`, `l`.`surname`, `l`.`rank_level`, `l`.`methodology`, `l`.`groups`, `l`.`parent_id`, `l`.`operator`, `l`.`event_id`";
 
+/// Builds a `Person` from `PERSON_COLUMNS` starting at column `first`.
+pub(in crate::db_operations) fn person_from_row(row: &Row, first: usize) -> rusqlite::Result<Person> {
+    Ok(Person {
+        id: row.get(first)?,
+        name: row.get(first + 1)?,
+        surname: row.get(first + 2)?,
+        nickname: row.get(first + 6)?,
+        rank_level: row.get(first + 3)?,
+        methodology: row.get(first + 4)?,
+        patrol: row.get(first + 7)?,
+        date_of_birth: row
+            .get::<_, Option<String>>(first + 8)?
+            .map(|s| parse_db_date(&s))
+            .transpose()?,
+        archived_at: row
+            .get::<_, Option<String>>(first + 5)?
+            .map(|s| parse_db_datetime(&s))
+            .transpose()?,
+        anonymized_at: row
+            .get::<_, Option<String>>(first + 15)?
+            .map(|s| parse_db_datetime(&s))
+            .transpose()?,
+        address: row.get(first + 9)?,
+        guardians: parse_guardians(&row.get::<_, String>(first + 10)?)?,
+        allergies: row.get(first + 11)?,
+        diet: row.get(first + 12)?,
+        medical_notes: row.get(first + 13)?,
+        note: row.get(first + 14)?,
+    })
+}
+
+/// Builds a `Group` from `GROUP_COLUMNS` starting at column `first`.
+pub(in crate::db_operations) fn group_from_row(row: &Row, first: usize) -> rusqlite::Result<Group> {
+    Ok(Group {
+        id: row.get(first)?,
+        name: row.get(first + 1)?,
+        kind: row.get(first + 2)?,
+        methodology: row.get(first + 3)?,
+        archived_at: row
+            .get::<_, Option<String>>(first + 4)?
+            .map(|s| parse_db_datetime(&s))
+            .transpose()?,
+    })
+}
+
+pub(in crate::db_operations) const EVENT_COLUMNS: &str =
+    "`e`.`id`, `e`.`name`, `e`.`starts_on`, `e`.`ends_on`, `e`.`location`, `e`.`retention_days`";
+
+/// Builds an `Event` from `EVENT_COLUMNS` starting at column `first`.
+pub(in crate::db_operations) fn event_from_row(row: &Row, first: usize) -> rusqlite::Result<Event> {
+    let date = |i: usize| -> rusqlite::Result<_> { row.get::<_, Option<String>>(i)?.map(|s| parse_db_date(&s)).transpose() };
+    Ok(Event {
+        id: row.get(first)?,
+        name: row.get(first + 1)?,
+        starts_on: date(first + 2)?,
+        ends_on: date(first + 3)?,
+        location: row.get(first + 4)?,
+        retention_days: row.get(first + 5)?,
+    })
+}
+
+/// Builds a `Log` from `LOG_COLUMNS` starting at column `first`.
+pub(in crate::db_operations) fn log_from_row(row: &Row, first: usize) -> rusqlite::Result<Log> {
+    let time_str: String = row.get(first + 4)?;
+    let entity_type: EntityType = row.get(first + 1)?;
+
+    // Group rows only use `name` (the group name); see `group_name_from_row`.
+    let snapshot = match row.get::<_, Option<String>>(first + 6)? {
+        Some(name) if matches!(entity_type, EntityType::Person) => Some(LogSnapshot {
+            name,
+            surname: row.get(first + 7)?,
+            rank_level: row.get(first + 8)?,
+            methodology: row.get(first + 9)?,
+            groups: row
+                .get::<_, Option<String>>(first + 10)?
+                .map(|g| g.split('\n').map(str::to_string).collect())
+                .unwrap_or_default(),
+        }),
+        _ => None,
+    };
+
+    Ok(Log {
+        id: row.get(first)?,
+        entity_type,
+        entity_id: row.get(first + 2)?,
+        event_id: row.get(first + 13)?,
+        is_inside: row.get(first + 3)?,
+        time: parse_db_datetime(&time_str)?,
+        batch_id: row.get(first + 5)?,
+        snapshot,
+        parent_id: row.get(first + 11)?,
+        operator: row.get(first + 12)?,
+    })
+}
+
+/// The group name stored on a group row read with `LOG_COLUMNS` starting at column `first`.
+fn group_name_from_row(row: &Row, first: usize) -> rusqlite::Result<Option<String>> {
+    row.get(first + 6)
+}
+
+/// Active (not archived) persons.
 #[allow(dead_code)]
-pub fn get_person(conn: &Connection) -> Result<Vec<Person>, Box<dyn Error>> {
-    let mut stmt = conn.prepare(
-        "SELECT `id`, `name`, `surname`, `rank_level`, `methodology`, `is_inside` FROM `Person`;",
-    )?;
+pub fn get_person(conn: &Connection) -> DbResult<Vec<Person>> {
+    query_persons(conn, "`p`.`archived_at` IS NULL")
+}
 
-    let person_iter = stmt.query_map([], |row| {
-        Ok(Person {
-            id: row.get(0)?,
-            name: row.get(1)?,
-            surname: row.get(2)?,
-            rank_level: row.get(3)?,
-            methodology: row.get(4)?,
-            is_inside: row.get(5)?,
-        })
-    })?;
+/// Active persons whose name, surname or nickname starts with every word of `query`, best match first.
+/// Case and diacritics are ignored ("luk zolk" finds "Łukasz Żółkiewski"). A query
+/// without any letters or digits matches every active person.
+pub fn search_persons(conn: &Connection, query: &str) -> DbResult<Vec<Person>> {
+    let terms = search_terms(query);
+    if terms.is_empty() {
+        return get_person(conn);
+    }
+
+    // Each term becomes a quoted prefix query; FTS5 ANDs them.
+    let fts_query = terms.iter().map(|t| format!("\"{t}\"*")).collect::<Vec<_>>().join(" ");
+    let mut stmt = conn.prepare_cached(&format!(
+        "SELECT {PERSON_LIST_COLUMNS}
+         FROM `PersonSearch` `s` JOIN `Person` `p` ON `p`.`id` = `s`.`rowid`
+         WHERE `PersonSearch` MATCH ?1 AND `p`.`archived_at` IS NULL
+         ORDER BY `s`.`rank`, `p`.`id`;"
+    ))?;
+    let persons = stmt
+        .query_map([fts_query], |row| person_from_row(row, 0))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(persons)
+}
+
+/// Splits a search query into words the way the unicode61 tokenizer does, with the
+/// 'ł' replacement applied to the `PersonSearch` index (see schema v10).
+pub(in crate::db_operations) fn search_terms(query: &str) -> Vec<String> {
+    query
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|t| !t.is_empty())
+        .map(|t| t.replace('ł', "l").replace('Ł', "L"))
+        .collect()
+}
+
+/// Archived persons, most recently archived first.
+pub fn get_archived_persons(conn: &Connection) -> DbResult<Vec<Person>> {
+    query_persons(conn, "`p`.`archived_at` IS NOT NULL ORDER BY `p`.`archived_at` DESC")
+}
+
+fn query_persons(conn: &Connection, filter: &str) -> DbResult<Vec<Person>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {PERSON_LIST_COLUMNS} FROM `Person` `p` WHERE {filter};"
+    ))?;
+
+    let person_iter = stmt.query_map([], |row| person_from_row(row, 0))?;
 
     let persons: rusqlite::Result<Vec<Person>> = person_iter.collect();
     Ok(persons?)
 }
 
+/// Any person by id, archived or not (history must stay readable).
+pub(in crate::db_operations) fn get_person_by_id(conn: &Connection, person_id: i32) -> DbResult<Person> {
+    let person = conn.query_row(
+        &format!("SELECT {PERSON_COLUMNS} FROM `Person` `p` WHERE `p`.`id` = ?1;"),
+        (person_id,),
+        |row| person_from_row(row, 0),
+    );
+
+    match person {
+        Err(rusqlite::Error::QueryReturnedNoRows) => Err(DbError::NotFound {
+            entity: "person",
+            id: person_id,
+        }),
+        other => Ok(other?),
+    }
+}
+
+/// Active (not archived) groups.
 #[allow(dead_code)]
-pub fn get_group(conn: &Connection) -> Result<Vec<Group>, Box<dyn Error>> {
-    let mut stmt = conn.prepare("SELECT `id`, `name` FROM `Group`;")?;
-
-    let group_iter = stmt.query_map([], |row| {
-        Ok(Group {
-            id: row.get(0)?,
-            name: row.get(1)?,
-        })
-    })?;
+pub fn get_group(conn: &Connection) -> DbResult<Vec<Group>> {
+    query_groups(conn, "`g`.`archived_at` IS NULL")
+}
+
+/// Archived groups, most recently archived first.
+pub fn get_archived_groups(conn: &Connection) -> DbResult<Vec<Group>> {
+    query_groups(conn, "`g`.`archived_at` IS NOT NULL ORDER BY `g`.`archived_at` DESC")
+}
+
+fn query_groups(conn: &Connection, filter: &str) -> DbResult<Vec<Group>> {
+    let mut stmt = conn.prepare(&format!("SELECT {GROUP_COLUMNS} FROM `Group` `g` WHERE {filter};"))?;
+
+    let group_iter = stmt.query_map([], |row| group_from_row(row, 0))?;
 
     let groups: rusqlite::Result<Vec<Group>> = group_iter.collect();
     Ok(groups?)
 }
 
 #[allow(dead_code)]
-pub fn get_group_member(conn: &Connection) -> Result<Vec<(i32, i32)>, Box<dyn Error>> {
+pub fn get_group_member(conn: &Connection) -> DbResult<Vec<(i32, i32)>> {
     let mut stmt = conn.prepare("SELECT `group_id`, `person_id` FROM `GroupMembers`;")?;
 
     let group_members_iter = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
@@ -50,48 +224,41 @@ pub fn get_group_member(conn: &Connection) -> Result<Vec<(i32, i32)>, Box<dyn Er
     Ok(group_members?)
 }
 
-pub fn get_group_with_members(conn: &Connection) -> Result<Vec<GroupWithMembers>, Box<dyn Error>> {
+/// Active groups with their active members. Archived persons and groups are left out.
+pub fn get_group_with_members(conn: &Connection) -> DbResult<Vec<GroupWithMembers>> {
     // Implementation note:
     // We build a map of groups first, then populate members with a join query.
     // The returned vector order is not guaranteed (HashMap iteration), so callers that
     // care about ordering should sort (the UI does).
-    let mut groups_stmt = conn.prepare("SELECT `id`, `name` FROM `Group`;")?;
+    let mut groups_stmt = conn.prepare("SELECT `id`, `name`, `kind` FROM `Group` WHERE `archived_at` IS NULL;")?;
     let groups_iter = groups_stmt.query_map([], |row| {
-        Ok((row.get::<_, i32>(0)?, row.get::<_, String>(1)?))
+        Ok((row.get::<_, i32>(0)?, row.get::<_, String>(1)?, row.get::<_, GroupKind>(2)?))
     })?;
 
     let mut groups_map: std::collections::HashMap<i32, GroupWithMembers> = std::collections::HashMap::new();
 
     for group in groups_iter {
-        let (id, name) = group?;
+        let (id, name, kind) = group?;
         groups_map.insert(
             id,
             GroupWithMembers {
                 id,
                 name,
+                kind,
                 members: Vec::new(),
             },
         );
     }
 
-    let mut members_stmt = conn.prepare(
-        "SELECT `gm`.`group_id`, `p`.`id`, `p`.`name`, `p`.`surname`, `p`.`rank_level`, `p`.`methodology`, `p`.`is_inside`
+    let mut members_stmt = conn.prepare(&format!(
+        "SELECT `gm`.`group_id`, {PERSON_LIST_COLUMNS}
          FROM `GroupMembers` `gm`
-         JOIN `Person` `p` ON `gm`.`person_id` = `p`.`id`;",
-    )?;
+         JOIN `Person` `p` ON `gm`.`person_id` = `p`.`id`
+         WHERE `p`.`archived_at` IS NULL;"
+    ))?;
 
     let members_iter = members_stmt.query_map([], |row| {
-        Ok((
-            row.get::<_, i32>(0)?,
-            Person {
-                id: row.get(1)?,
-                name: row.get(2)?,
-                surname: row.get(3)?,
-                rank_level: RankLevel::try_from(row.get::<_, i32>(4)?).unwrap_or(RankLevel::RankNone),
-                methodology: Methodology::try_from(row.get::<_, i32>(5)?).unwrap_or(Methodology::Cub),
-                is_inside: row.get(6)?,
-            },
-        ))
+        Ok((row.get::<_, i32>(0)?, person_from_row(row, 1)?))
     })?;
 
     for member in members_iter {
@@ -104,26 +271,179 @@ pub fn get_group_with_members(conn: &Connection) -> Result<Vec<GroupWithMembers>
     Ok(groups_map.into_values().collect())
 }
 
+/// Events, newest first (undated ones last).
+pub fn get_events(conn: &Connection) -> DbResult<Vec<Event>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {EVENT_COLUMNS} FROM `Event` `e` ORDER BY `e`.`starts_on` IS NULL, `e`.`starts_on` DESC, `e`.`id` DESC;"
+    ))?;
+    let events = stmt.query_map([], |row| event_from_row(row, 0))?.collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(events)
+}
+
+/// Active (not archived) participants of the event with their presence in it.
+pub fn get_event_participants(conn: &Connection, event_id: i32) -> DbResult<Vec<Participant>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {PERSON_LIST_COLUMNS}, `ep`.`is_inside`
+         FROM `EventParticipants` `ep` JOIN `Person` `p` ON `p`.`id` = `ep`.`person_id`
+         WHERE `ep`.`event_id` = ?1 AND `p`.`archived_at` IS NULL
+         ORDER BY `p`.`id`;"
+    ))?;
+    let participants = stmt
+        .query_map([event_id], |row| {
+            Ok(Participant {
+                person: person_from_row(row, 0)?,
+                is_inside: row.get(PERSON_COLUMN_COUNT)?,
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(participants)
+}
+
 #[allow(dead_code)]
-pub fn get_log(conn: &Connection) -> Result<Vec<super::Log>, Box<dyn Error>> {
-    let mut stmt = conn.prepare(
-        "SELECT `id`, `entity_type`, `entity_id`, `is_inside`, `timestamp` FROM `Log` WHERE `entity_type` = 0 ORDER BY `timestamp` DESC, `id` DESC;",
-    )?;
+pub fn get_log(conn: &Connection) -> DbResult<Vec<Log>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {LOG_COLUMNS} FROM `Log` `l` WHERE `l`.`entity_type` = 0 ORDER BY `l`.`timestamp` DESC, `l`.`id` DESC;"
+    ))?;
 
-    let log_iter = stmt.query_map([], |row| {
-        let time_str: String = row.get(4)?;
-        let datetime_utc = parse_db_datetime(&time_str)?;
-
-        Ok(super::Log {
-            id: row.get(0)?,
-            entity_type: row.get(1)?,
-            entity_id: row.get(2)?,
-            is_inside: row.get(3)?,
-            time: datetime_utc,
-        })
-    })?;
+    let log_iter = stmt.query_map([], |row| log_from_row(row, 0))?;
 
-    let logs: rusqlite::Result<Vec<super::Log>> = log_iter.collect();
+    let logs: rusqlite::Result<Vec<Log>> = log_iter.collect();
 
     Ok(logs?)
 }
+
+/// One page of logs screen entries, newest first, starting after `after`.
+///
+/// Paging and filters apply to top-level rows; member rows of a group event are nested
+/// in it and always come complete. The group filter uses current membership, see
+/// `LogFilter::group_id`.
+pub fn query_log(conn: &Connection, filter: &LogFilter, after: Option<LogCursor>, limit: usize) -> DbResult<LogPage> {
+    let mut stmt = conn.prepare_cached(&format!(
+        "SELECT {LOG_COLUMNS} FROM `Log` `l`
+         WHERE `l`.`parent_id` IS NULL
+           AND (?10 IS NULL OR `l`.`event_id` = ?10)
+           AND (?1 IS NULL OR `l`.`timestamp` >= ?1)
+           AND (?2 IS NULL OR `l`.`timestamp` < ?2)
+           AND (?3 IS NULL OR `l`.`is_inside` = ?3)
+           AND (?4 IS NULL OR `l`.`operator` = ?4)
+           AND (?5 IS NULL
+                OR (`l`.`entity_type` = 0 AND `l`.`entity_id` = ?5)
+                OR (`l`.`entity_type` = 1 AND EXISTS (
+                    SELECT 1 FROM `Log` `c` WHERE `c`.`parent_id` = `l`.`id` AND `c`.`entity_id` = ?5)))
+           AND (?6 IS NULL
+                OR (`l`.`entity_type` = 1 AND `l`.`entity_id` = ?6)
+                OR (`l`.`entity_type` = 0 AND `l`.`entity_id` IN (
+                    SELECT `gm`.`person_id` FROM `GroupMembers` `gm` WHERE `gm`.`group_id` = ?6))
+                OR (`l`.`entity_type` = 1 AND EXISTS (
+                    SELECT 1 FROM `Log` `c` JOIN `GroupMembers` `gm` ON `gm`.`person_id` = `c`.`entity_id`
+                    WHERE `c`.`parent_id` = `l`.`id` AND `gm`.`group_id` = ?6)))
+           AND (?7 IS NULL OR (`l`.`timestamp`, `l`.`id`) < (?7, ?8))
+         ORDER BY `l`.`timestamp` DESC, `l`.`id` DESC
+         LIMIT ?9;"
+    ))?;
+
+    let rows = stmt.query_map(
+        rusqlite::params![
+            filter.from.as_ref().map(format_db_datetime),
+            filter.to.as_ref().map(format_db_datetime),
+            filter.is_inside,
+            filter.operator,
+            filter.person_id,
+            filter.group_id,
+            after.map(|c| format_db_datetime(&c.time)),
+            after.map(|c| c.id),
+            // One extra row tells whether there is a next page.
+            i64::try_from(limit.saturating_add(1)).unwrap_or(i64::MAX),
+            filter.event_id,
+        ],
+        |row| Ok((log_from_row(row, 0)?, group_name_from_row(row, 0)?)),
+    )?;
+    let mut top_level = rows.collect::<rusqlite::Result<Vec<(Log, Option<String>)>>>()?;
+
+    let next = if top_level.len() > limit {
+        top_level.truncate(limit);
+        top_level.last().map(|(log, _)| LogCursor { time: log.time, id: log.id })
+    } else {
+        None
+    };
+
+    let mut members_stmt = conn.prepare_cached(&format!(
+        "SELECT {LOG_COLUMNS} FROM `Log` `l` WHERE `l`.`parent_id` = ?1 ORDER BY `l`.`id` DESC;"
+    ))?;
+
+    let mut entries = Vec::with_capacity(top_level.len());
+    for (log, name) in top_level {
+        entries.push(match log.entity_type {
+            EntityType::Person => LogEntry::Person(log),
+            EntityType::Group => LogEntry::Group {
+                members: members_stmt
+                    .query_map([log.id], |row| log_from_row(row, 0))?
+                    .collect::<rusqlite::Result<Vec<Log>>>()?,
+                group_name: name.unwrap_or_default(),
+                log,
+            },
+        });
+    }
+
+    Ok(LogPage { entries, next })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db_operations::test_support::{memory_db, person};
+    use crate::db_operations::{insert_to_db, DatabaseRecord, Guardian, Methodology};
+
+    fn surnames(conn: &Connection, query: &str) -> Vec<String> {
+        search_persons(conn, query).unwrap().into_iter().map(|p| p.surname).collect()
+    }
+
+    #[test]
+    fn search_ignores_polish_diacritics_and_matches_prefixes() {
+        let conn = memory_db();
+        for (name, surname) in [("Łukasz", "Kowalski"), ("Ola", "Żółć"), ("Jan", "Nowak")] {
+            insert_to_db(&conn, DatabaseRecord::Person(person(name, surname, Methodology::Scout))).unwrap();
+        }
+
+        assert_eq!(surnames(&conn, "lukasz"), ["Kowalski"]);
+        assert_eq!(surnames(&conn, "Łukasz"), ["Kowalski"]);
+        assert_eq!(surnames(&conn, "zolc"), ["Żółć"]);
+        assert_eq!(surnames(&conn, "Żółć"), ["Żółć"]);
+        assert_eq!(surnames(&conn, "kowal"), ["Kowalski"]);
+        assert_eq!(surnames(&conn, "luk kow"), ["Kowalski"]);
+        assert!(surnames(&conn, "kowalskia").is_empty());
+        assert_eq!(surnames(&conn, "  ").len(), 3);
+    }
+
+    #[test]
+    fn guardians_round_trip_and_lists_leave_sensitive_fields_empty() {
+        let conn = memory_db();
+        let mut p = person("Jan", "Kowalski", Methodology::Scout);
+        p.guardians = vec![
+            Guardian { name: "Anna Kowalska".to_string(), relation: "mother".to_string(), phone: "+48 600 100 200".to_string(), email: String::new() },
+            Guardian { name: "Piotr \"Kowal\" Kowalski".to_string(), relation: "father".to_string(), phone: String::new(), email: "p@example.pl".to_string() },
+        ];
+        p.address = "ul. Długa 1, Kraków".to_string();
+        p.allergies = "orzechy".to_string();
+        p.diet = "bezglutenowa".to_string();
+        p.medical_notes = "astma".to_string();
+        p.note = "tęskni za domem".to_string();
+        insert_to_db(&conn, DatabaseRecord::Person(p.clone())).unwrap();
+
+        let id = get_person(&conn).unwrap()[0].id;
+        let full = get_person_by_id(&conn, id).unwrap();
+        assert_eq!(full.guardians, p.guardians);
+        assert_eq!((full.address, full.medical_notes, full.note), (p.address, p.medical_notes, p.note));
+
+        let mut listed = get_person(&conn).unwrap();
+        listed.extend(search_persons(&conn, "kowal").unwrap());
+        listed.extend(get_group_with_members(&conn).unwrap().into_iter().flat_map(|g| g.members));
+        listed.extend(get_event_participants(&conn, 1).unwrap().into_iter().map(|pt| pt.person));
+        assert!(listed.len() >= 4);
+        for listed in listed {
+            assert!(listed.guardians.is_empty() && listed.address.is_empty() && listed.medical_notes.is_empty() && listed.note.is_empty());
+            // The patrol list can show these on request.
+            assert_eq!((listed.allergies.as_str(), listed.diet.as_str()), ("orzechy", "bezglutenowa"));
+        }
+    }
+}
@@ -0,0 +1,219 @@
+//! Storage-agnostic access to the roster and the presence log.
+//!
+//! `app_controller` talks to these traits instead of a raw `rusqlite::Connection`, so the
+//! controller logic can be tested on its own. `SqliteRepository` works the same on a camp
+//! file and on `Connection::open_in_memory()`, which is what tests and tools without a
+//! database file use.
+
+mod sqlite;
+#[cfg(test)]
+mod tests;
+
+pub use sqlite::SqliteRepository;
+
+use chrono::{DateTime, NaiveDate, Utc};
+
+use super::{
+    AlertLimits, Checkpoint, DbResult, Event, Group, GroupWithMembers, Incident, IsInside, LeaveDetails, LeavePass, Log, LogCursor, LogFilter,
+    LogPage, MorningReport, NewIncident, Overdue, Participant, Person, PersonExport, PresenceBatch, PresenceMismatch, PresenceState,
+    ReconcileWith, RollCall, RollCallDiscrepancy, Rota, RotaRequest, SentryShift, TimeOutside,
+};
+
+#[allow(dead_code)]
+pub trait PersonRepository {
+    /// Active persons only.
+    fn persons(&self) -> DbResult<Vec<Person>>;
+    /// Most recently archived first.
+    fn archived_persons(&self) -> DbResult<Vec<Person>>;
+    /// Any person, archived or not.
+    fn person(&self, person_id: i32) -> DbResult<Person>;
+    /// Active persons matching `query`, see `db_operations::search_persons`.
+    fn search_persons(&self, query: &str) -> DbResult<Vec<Person>>;
+    /// Inserts the person with their automatic memberships; returns the new id.
+    fn add_person(&self, person: &Person) -> DbResult<i32>;
+    /// Keeps the methodology group membership in line with `person.methodology`.
+    /// Writes every field, so start from `person()`: lists leave the sensitive ones empty.
+    fn update_person(&self, person: &Person) -> DbResult<()>;
+    fn archive_person(&self, person_id: i32) -> DbResult<()>;
+    fn restore_person(&self, person_id: i32) -> DbResult<()>;
+    /// Permanent delete; only allowed for archived persons. Log rows survive.
+    fn purge_person(&self, person_id: i32) -> DbResult<()>;
+}
+
+#[allow(dead_code)]
+pub trait GroupRepository {
+    /// Active groups only.
+    fn groups(&self) -> DbResult<Vec<Group>>;
+    /// Most recently archived first.
+    fn archived_groups(&self) -> DbResult<Vec<Group>>;
+    /// Active groups with active members.
+    /// Order is unspecified; callers that display the result should sort it.
+    fn groups_with_members(&self) -> DbResult<Vec<GroupWithMembers>>;
+    /// Always a `GroupKind::User` group; returns the new id.
+    fn add_group(&self, group: &Group) -> DbResult<i32>;
+    fn update_group(&self, group: &Group) -> DbResult<()>;
+    /// Built-in groups are refused with `ConstraintViolation`.
+    fn archive_group(&self, group_id: i32) -> DbResult<()>;
+    fn restore_group(&self, group_id: i32) -> DbResult<()>;
+    /// Permanent delete; only allowed for archived user groups.
+    fn purge_group(&self, group_id: i32) -> DbResult<()>;
+    /// User groups only; members of built-in groups follow the roster (`ConstraintViolation`).
+    fn add_member(&self, group_id: i32, person_id: i32) -> DbResult<()>;
+    fn remove_member(&self, group_id: i32, person_id: i32) -> DbResult<()>;
+}
+
+#[allow(dead_code)]
+pub trait EventRepository {
+    /// Newest first (undated events last).
+    fn events(&self) -> DbResult<Vec<Event>>;
+    /// Returns the new id.
+    fn add_event(&self, event: &Event) -> DbResult<i32>;
+    fn update_event(&self, event: &Event) -> DbResult<()>;
+    /// Active participants with their presence in the event.
+    fn participants(&self, event_id: i32) -> DbResult<Vec<Participant>>;
+    /// New participants start outside; `Duplicate` if the person already takes part.
+    fn add_participant(&self, event_id: i32, person_id: i32) -> DbResult<()>;
+    fn remove_participant(&self, event_id: i32, person_id: i32) -> DbResult<()>;
+    /// Event the main screen works on, if set and still existing.
+    fn active_event(&self) -> DbResult<Option<i32>>;
+    fn set_active_event(&self, event_id: i32) -> DbResult<()>;
+    /// Atomic bulk check-in/out, see `db_operations::set_persons_is_inside`.
+    fn set_presence(
+        &self,
+        event_id: i32,
+        person_ids: &[i32],
+        is_inside: IsInside,
+        operator: Option<&str>,
+    ) -> DbResult<PresenceBatch>;
+    /// Same, recorded as one group event, see `db_operations::set_group_is_inside`.
+    fn set_group_presence(
+        &self,
+        event_id: i32,
+        group_id: i32,
+        person_ids: &[i32],
+        is_inside: IsInside,
+        operator: Option<&str>,
+    ) -> DbResult<PresenceBatch>;
+    /// Check-out with a leave pass per person, see `db_operations::leave_with_pass`.
+    fn leave_with_pass(
+        &self,
+        event_id: i32,
+        group_id: Option<i32>,
+        person_ids: &[i32],
+        operator: Option<&str>,
+        details: &LeaveDetails,
+    ) -> DbResult<PresenceBatch>;
+    /// Passes of event `event_id` not closed by an IN yet, oldest departure first.
+    fn open_leave_passes(&self, event_id: i32) -> DbResult<Vec<LeavePass>>;
+}
+
+#[allow(dead_code)]
+pub trait PresenceLogRepository {
+    /// Person in/out events, newest first.
+    fn person_logs(&self) -> DbResult<Vec<Log>>;
+    /// One filtered page of logs screen entries, see `db_operations::query_log`.
+    fn log_page(&self, filter: &LogFilter, after: Option<LogCursor>, limit: usize) -> DbResult<LogPage>;
+    /// Presence in event `event_id` at `at` as the log tells it, see `db_operations::presence`.
+    fn presence_at(&self, event_id: i32, at: DateTime<Utc>) -> DbResult<Vec<PresenceState>>;
+    /// Ids of the persons inside event `event_id` at `at`, ascending.
+    fn inside_at(&self, event_id: i32, at: DateTime<Utc>) -> DbResult<Vec<i32>>;
+    /// Participants whose stored presence disagrees with the log; every event when `None`.
+    fn presence_mismatches(&self, event_id: Option<i32>) -> DbResult<Vec<PresenceMismatch>>;
+    fn reconcile_presence(&self, mismatch: &PresenceMismatch, with: ReconcileWith) -> DbResult<()>;
+    /// Time outside per person logged in event `event_id`, see `db_operations::time_outside`.
+    fn time_outside(&self, event_id: i32, until: DateTime<Utc>) -> DbResult<Vec<TimeOutside>>;
+}
+
+/// Retention and data access requests, see `db_operations::privacy`.
+#[allow(dead_code)]
+pub trait PrivacyRepository {
+    /// Camp-wide retention period in days; `None` keeps data until removed by hand.
+    fn retention_days(&self) -> DbResult<Option<u32>>;
+    fn set_retention_days(&self, days: Option<u32>) -> DbResult<()>;
+    /// Not yet anonymized persons whose retention period has passed on `today`.
+    fn persons_past_retention(&self, today: NaiveDate) -> DbResult<Vec<Person>>;
+    /// Irreversible; also archives the person. `ConstraintViolation` if already anonymized.
+    fn anonymize_person(&self, person_id: i32) -> DbResult<()>;
+    fn export_person(&self, person_id: i32) -> DbResult<PersonExport>;
+}
+
+/// Overdue alerts, see `db_operations::alerts`.
+#[allow(dead_code)]
+pub trait AlertRepository {
+    fn alert_limits(&self) -> DbResult<AlertLimits>;
+    fn set_alert_limits(&self, limits: &AlertLimits) -> DbResult<()>;
+    /// Participants of event `event_id` past a limit at `now`, out the longest first.
+    fn overdue_persons(&self, event_id: i32, now: DateTime<Utc>) -> DbResult<Vec<Overdue>>;
+}
+
+/// Roll call sessions, see `db_operations::roll_call`.
+#[allow(dead_code)]
+pub trait RollCallRepository {
+    /// Snapshot of the event's participants; `ConstraintViolation` if one is in progress.
+    fn start_roll_call(&self, event_id: i32, operator: Option<&str>) -> DbResult<i32>;
+    /// Newest first.
+    fn roll_calls(&self, event_id: i32) -> DbResult<Vec<RollCall>>;
+    fn roll_call(&self, roll_call_id: i32) -> DbResult<RollCall>;
+    /// `ConstraintViolation` once the roll call is finished.
+    fn tick_roll_call(&self, roll_call_id: i32, person_id: i32, present: bool) -> DbResult<()>;
+    fn finish_roll_call(&self, roll_call_id: i32) -> DbResult<()>;
+    fn roll_call_discrepancies(&self, roll_call_id: i32) -> DbResult<Vec<RollCallDiscrepancy>>;
+    /// Logs the roll call result for `person_ids`, see `db_operations::fix_roll_call`.
+    fn fix_roll_call(&self, roll_call_id: i32, person_ids: &[i32], operator: Option<&str>) -> DbResult<()>;
+}
+
+/// Sentry duty rotas, see `db_operations::rota`.
+#[allow(dead_code)]
+pub trait RotaRepository {
+    /// Plans and stores a rota from the event's participants; returns its id.
+    fn generate_rota(&self, event_id: i32, request: &RotaRequest) -> DbResult<i32>;
+    /// Latest night first.
+    fn rotas(&self, event_id: i32) -> DbResult<Vec<Rota>>;
+    fn rota(&self, rota_id: i32) -> DbResult<Rota>;
+    /// Slots are `(shift, slot)`; `Duplicate` if someone would be twice on one shift.
+    fn swap_rota_slots(&self, rota_id: i32, a: (u32, u32), b: (u32, u32)) -> DbResult<()>;
+    /// `None` empties the slot.
+    fn set_rota_slot(&self, rota_id: i32, at: (u32, u32), person_id: Option<i32>) -> DbResult<()>;
+    fn delete_rota(&self, rota_id: i32) -> DbResult<()>;
+}
+
+/// Sentry journal, see `db_operations::journal`.
+#[allow(dead_code)]
+pub trait JournalRepository {
+    /// `ConstraintViolation` if the event has a shift in progress.
+    fn start_sentry_shift(&self, event_id: i32, guards: &str, operator: Option<&str>) -> DbResult<i32>;
+    fn end_sentry_shift(&self, shift_id: i32) -> DbResult<()>;
+    /// Oldest first.
+    fn sentry_shifts(&self, event_id: i32) -> DbResult<Vec<SentryShift>>;
+    /// `Duplicate` for a name the event already has.
+    fn add_checkpoint(&self, event_id: i32, name: &str) -> DbResult<i32>;
+    fn checkpoints(&self, event_id: i32) -> DbResult<Vec<Checkpoint>>;
+    /// Only during the shift; `ConstraintViolation` once it is over.
+    fn visit_checkpoint(&self, shift_id: i32, checkpoint_id: i32) -> DbResult<()>;
+    fn record_incident(&self, event_id: i32, incident: &NewIncident, operator: Option<&str>) -> DbResult<i32>;
+    /// Oldest first.
+    fn incidents(&self, event_id: i32) -> DbResult<Vec<Incident>>;
+    fn delete_incident(&self, incident_id: i32) -> DbResult<()>;
+    fn morning_report(&self, event_id: i32, from: DateTime<Utc>, to: DateTime<Utc>) -> DbResult<MorningReport>;
+}
+
+/// Everything the controller needs, as one object-safe bound.
+pub trait Repository:
+    PersonRepository + GroupRepository + EventRepository + PresenceLogRepository + PrivacyRepository + AlertRepository + RollCallRepository
+    + RotaRepository
+    + JournalRepository
+{
+}
+
+impl<T> Repository for T where
+    T: PersonRepository
+        + GroupRepository
+        + EventRepository
+        + PresenceLogRepository
+        + PrivacyRepository
+        + AlertRepository
+        + RollCallRepository
+        + RotaRepository
+        + JournalRepository
+{
+}
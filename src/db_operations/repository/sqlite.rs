@@ -0,0 +1,344 @@
+use std::{
+    cell::RefCell,
+    rc::Rc,
+};
+
+use chrono::{DateTime, NaiveDate, Utc};
+use rusqlite::Connection;
+
+use super::{
+    AlertRepository, EventRepository, GroupRepository, JournalRepository, PersonRepository, PresenceLogRepository, PrivacyRepository,
+    RollCallRepository, RotaRepository,
+};
+use crate::db_operations::{
+    alerts, app_setting, archive, delete, insert, journal, leave_pass, presence, privacy, queries, roll_call, rota, time_outside, update,
+    AlertLimits, Checkpoint, DbResult, Event, Group, GroupWithMembers, Incident, IsInside, LeaveDetails, LeavePass, Log, LogCursor, LogFilter,
+    LogPage, MorningReport, NewIncident, Overdue, Participant, Person, PersonExport, PresenceBatch, PresenceMismatch, PresenceState,
+    ReconcileWith, RollCall, RollCallDiscrepancy, Rota, RotaRequest, SentryShift, TimeOutside,
+};
+
+/// Repository backed by the shared application connection.
+#[derive(Clone)]
+pub struct SqliteRepository {
+    conn: Rc<RefCell<Connection>>,
+}
+
+impl SqliteRepository {
+    pub fn new(conn: Rc<RefCell<Connection>>) -> Self {
+        Self { conn }
+    }
+}
+
+impl PersonRepository for SqliteRepository {
+    fn persons(&self) -> DbResult<Vec<Person>> {
+        queries::get_person(&self.conn.borrow())
+    }
+
+    fn archived_persons(&self) -> DbResult<Vec<Person>> {
+        queries::get_archived_persons(&self.conn.borrow())
+    }
+
+    fn person(&self, person_id: i32) -> DbResult<Person> {
+        queries::get_person_by_id(&self.conn.borrow(), person_id)
+    }
+
+    fn search_persons(&self, query: &str) -> DbResult<Vec<Person>> {
+        queries::search_persons(&self.conn.borrow(), query)
+    }
+
+    fn add_person(&self, person: &Person) -> DbResult<i32> {
+        insert::insert_person(&self.conn.borrow(), person)
+    }
+
+    fn update_person(&self, person: &Person) -> DbResult<()> {
+        update::update_person(&self.conn.borrow(), person)
+    }
+
+    fn archive_person(&self, person_id: i32) -> DbResult<()> {
+        archive::archive_person(&self.conn.borrow(), person_id)
+    }
+
+    fn restore_person(&self, person_id: i32) -> DbResult<()> {
+        archive::restore_person(&self.conn.borrow(), person_id)
+    }
+
+    fn purge_person(&self, person_id: i32) -> DbResult<()> {
+        delete::purge_person(&self.conn.borrow(), person_id)
+    }
+}
+
+impl GroupRepository for SqliteRepository {
+    fn groups(&self) -> DbResult<Vec<Group>> {
+        queries::get_group(&self.conn.borrow())
+    }
+
+    fn archived_groups(&self) -> DbResult<Vec<Group>> {
+        queries::get_archived_groups(&self.conn.borrow())
+    }
+
+    fn groups_with_members(&self) -> DbResult<Vec<GroupWithMembers>> {
+        queries::get_group_with_members(&self.conn.borrow())
+    }
+
+    fn add_group(&self, group: &Group) -> DbResult<i32> {
+        insert::insert_group(&self.conn.borrow(), group)
+    }
+
+    fn update_group(&self, group: &Group) -> DbResult<()> {
+        update::update_group(&self.conn.borrow(), group)
+    }
+
+    fn archive_group(&self, group_id: i32) -> DbResult<()> {
+        archive::archive_group(&self.conn.borrow(), group_id)
+    }
+
+    fn restore_group(&self, group_id: i32) -> DbResult<()> {
+        archive::restore_group(&self.conn.borrow(), group_id)
+    }
+
+    fn purge_group(&self, group_id: i32) -> DbResult<()> {
+        delete::purge_group(&self.conn.borrow(), group_id)
+    }
+
+    fn add_member(&self, group_id: i32, person_id: i32) -> DbResult<()> {
+        insert::insert_group_member(&self.conn.borrow(), group_id, person_id)
+    }
+
+    fn remove_member(&self, group_id: i32, person_id: i32) -> DbResult<()> {
+        delete::delete_group_member(&self.conn.borrow(), group_id, person_id)
+    }
+}
+
+impl EventRepository for SqliteRepository {
+    fn events(&self) -> DbResult<Vec<Event>> {
+        queries::get_events(&self.conn.borrow())
+    }
+
+    fn add_event(&self, event: &Event) -> DbResult<i32> {
+        insert::insert_event(&self.conn.borrow(), event)
+    }
+
+    fn update_event(&self, event: &Event) -> DbResult<()> {
+        update::update_event(&self.conn.borrow(), event)
+    }
+
+    fn participants(&self, event_id: i32) -> DbResult<Vec<Participant>> {
+        queries::get_event_participants(&self.conn.borrow(), event_id)
+    }
+
+    fn add_participant(&self, event_id: i32, person_id: i32) -> DbResult<()> {
+        insert::insert_event_participant(&self.conn.borrow(), event_id, person_id)
+    }
+
+    fn remove_participant(&self, event_id: i32, person_id: i32) -> DbResult<()> {
+        delete::delete_event_participant(&self.conn.borrow(), event_id, person_id)
+    }
+
+    fn active_event(&self) -> DbResult<Option<i32>> {
+        app_setting::get_active_event_id(&self.conn.borrow())
+    }
+
+    fn set_active_event(&self, event_id: i32) -> DbResult<()> {
+        app_setting::set_active_event_id(&self.conn.borrow(), event_id)
+    }
+
+    fn set_presence(
+        &self,
+        event_id: i32,
+        person_ids: &[i32],
+        is_inside: IsInside,
+        operator: Option<&str>,
+    ) -> DbResult<PresenceBatch> {
+        update::set_persons_is_inside(&self.conn.borrow(), event_id, person_ids, is_inside, operator)
+    }
+
+    fn set_group_presence(
+        &self,
+        event_id: i32,
+        group_id: i32,
+        person_ids: &[i32],
+        is_inside: IsInside,
+        operator: Option<&str>,
+    ) -> DbResult<PresenceBatch> {
+        update::set_group_is_inside(&self.conn.borrow(), event_id, group_id, person_ids, is_inside, operator)
+    }
+
+    fn leave_with_pass(
+        &self,
+        event_id: i32,
+        group_id: Option<i32>,
+        person_ids: &[i32],
+        operator: Option<&str>,
+        details: &LeaveDetails,
+    ) -> DbResult<PresenceBatch> {
+        update::leave_with_pass(&self.conn.borrow(), event_id, group_id, person_ids, operator, details)
+    }
+
+    fn open_leave_passes(&self, event_id: i32) -> DbResult<Vec<LeavePass>> {
+        leave_pass::open_leave_passes(&self.conn.borrow(), event_id)
+    }
+}
+
+impl PresenceLogRepository for SqliteRepository {
+    fn person_logs(&self) -> DbResult<Vec<Log>> {
+        queries::get_log(&self.conn.borrow())
+    }
+
+    fn log_page(&self, filter: &LogFilter, after: Option<LogCursor>, limit: usize) -> DbResult<LogPage> {
+        queries::query_log(&self.conn.borrow(), filter, after, limit)
+    }
+
+    fn presence_at(&self, event_id: i32, at: DateTime<Utc>) -> DbResult<Vec<PresenceState>> {
+        presence::presence_at(&self.conn.borrow(), event_id, at)
+    }
+
+    fn inside_at(&self, event_id: i32, at: DateTime<Utc>) -> DbResult<Vec<i32>> {
+        presence::inside_at(&self.conn.borrow(), event_id, at)
+    }
+
+    fn presence_mismatches(&self, event_id: Option<i32>) -> DbResult<Vec<PresenceMismatch>> {
+        presence::presence_mismatches(&self.conn.borrow(), event_id)
+    }
+
+    fn reconcile_presence(&self, mismatch: &PresenceMismatch, with: ReconcileWith) -> DbResult<()> {
+        presence::reconcile_presence(&self.conn.borrow(), mismatch, with)
+    }
+
+    fn time_outside(&self, event_id: i32, until: DateTime<Utc>) -> DbResult<Vec<TimeOutside>> {
+        time_outside::time_outside(&self.conn.borrow(), event_id, until)
+    }
+}
+
+impl PrivacyRepository for SqliteRepository {
+    fn retention_days(&self) -> DbResult<Option<u32>> {
+        app_setting::get_retention_days(&self.conn.borrow())
+    }
+
+    fn set_retention_days(&self, days: Option<u32>) -> DbResult<()> {
+        app_setting::set_retention_days(&self.conn.borrow(), days)
+    }
+
+    fn persons_past_retention(&self, today: NaiveDate) -> DbResult<Vec<Person>> {
+        privacy::persons_past_retention(&self.conn.borrow(), today)
+    }
+
+    fn anonymize_person(&self, person_id: i32) -> DbResult<()> {
+        privacy::anonymize_person(&self.conn.borrow(), person_id)
+    }
+
+    fn export_person(&self, person_id: i32) -> DbResult<PersonExport> {
+        privacy::export_person(&self.conn.borrow(), person_id)
+    }
+}
+
+impl AlertRepository for SqliteRepository {
+    fn alert_limits(&self) -> DbResult<AlertLimits> {
+        app_setting::get_alert_limits(&self.conn.borrow())
+    }
+
+    fn set_alert_limits(&self, limits: &AlertLimits) -> DbResult<()> {
+        app_setting::set_alert_limits(&self.conn.borrow(), limits)
+    }
+
+    fn overdue_persons(&self, event_id: i32, now: DateTime<Utc>) -> DbResult<Vec<Overdue>> {
+        alerts::overdue_persons(&self.conn.borrow(), event_id, now)
+    }
+}
+
+impl RollCallRepository for SqliteRepository {
+    fn start_roll_call(&self, event_id: i32, operator: Option<&str>) -> DbResult<i32> {
+        roll_call::start_roll_call(&self.conn.borrow(), event_id, operator)
+    }
+
+    fn roll_calls(&self, event_id: i32) -> DbResult<Vec<RollCall>> {
+        roll_call::get_roll_calls(&self.conn.borrow(), event_id)
+    }
+
+    fn roll_call(&self, roll_call_id: i32) -> DbResult<RollCall> {
+        roll_call::get_roll_call(&self.conn.borrow(), roll_call_id)
+    }
+
+    fn tick_roll_call(&self, roll_call_id: i32, person_id: i32, present: bool) -> DbResult<()> {
+        roll_call::tick_roll_call(&self.conn.borrow(), roll_call_id, person_id, present)
+    }
+
+    fn finish_roll_call(&self, roll_call_id: i32) -> DbResult<()> {
+        roll_call::finish_roll_call(&self.conn.borrow(), roll_call_id)
+    }
+
+    fn roll_call_discrepancies(&self, roll_call_id: i32) -> DbResult<Vec<RollCallDiscrepancy>> {
+        roll_call::roll_call_discrepancies(&self.conn.borrow(), roll_call_id)
+    }
+
+    fn fix_roll_call(&self, roll_call_id: i32, person_ids: &[i32], operator: Option<&str>) -> DbResult<()> {
+        roll_call::fix_roll_call(&self.conn.borrow(), roll_call_id, person_ids, operator)
+    }
+}
+
+impl RotaRepository for SqliteRepository {
+    fn generate_rota(&self, event_id: i32, request: &RotaRequest) -> DbResult<i32> {
+        rota::generate_rota(&self.conn.borrow(), event_id, request)
+    }
+
+    fn rotas(&self, event_id: i32) -> DbResult<Vec<Rota>> {
+        rota::get_rotas(&self.conn.borrow(), event_id)
+    }
+
+    fn rota(&self, rota_id: i32) -> DbResult<Rota> {
+        rota::get_rota(&self.conn.borrow(), rota_id)
+    }
+
+    fn swap_rota_slots(&self, rota_id: i32, a: (u32, u32), b: (u32, u32)) -> DbResult<()> {
+        rota::swap_rota_slots(&self.conn.borrow(), rota_id, a, b)
+    }
+
+    fn set_rota_slot(&self, rota_id: i32, at: (u32, u32), person_id: Option<i32>) -> DbResult<()> {
+        rota::set_rota_slot(&self.conn.borrow(), rota_id, at, person_id)
+    }
+
+    fn delete_rota(&self, rota_id: i32) -> DbResult<()> {
+        rota::delete_rota(&self.conn.borrow(), rota_id)
+    }
+}
+
+impl JournalRepository for SqliteRepository {
+    fn start_sentry_shift(&self, event_id: i32, guards: &str, operator: Option<&str>) -> DbResult<i32> {
+        journal::start_sentry_shift(&self.conn.borrow(), event_id, guards, operator)
+    }
+
+    fn end_sentry_shift(&self, shift_id: i32) -> DbResult<()> {
+        journal::end_sentry_shift(&self.conn.borrow(), shift_id)
+    }
+
+    fn sentry_shifts(&self, event_id: i32) -> DbResult<Vec<SentryShift>> {
+        journal::get_sentry_shifts(&self.conn.borrow(), event_id)
+    }
+
+    fn add_checkpoint(&self, event_id: i32, name: &str) -> DbResult<i32> {
+        journal::add_checkpoint(&self.conn.borrow(), event_id, name)
+    }
+
+    fn checkpoints(&self, event_id: i32) -> DbResult<Vec<Checkpoint>> {
+        journal::get_checkpoints(&self.conn.borrow(), event_id)
+    }
+
+    fn visit_checkpoint(&self, shift_id: i32, checkpoint_id: i32) -> DbResult<()> {
+        journal::visit_checkpoint(&self.conn.borrow(), shift_id, checkpoint_id)
+    }
+
+    fn record_incident(&self, event_id: i32, incident: &NewIncident, operator: Option<&str>) -> DbResult<i32> {
+        journal::record_incident(&self.conn.borrow(), event_id, incident, operator)
+    }
+
+    fn incidents(&self, event_id: i32) -> DbResult<Vec<Incident>> {
+        journal::get_incidents(&self.conn.borrow(), event_id)
+    }
+
+    fn delete_incident(&self, incident_id: i32) -> DbResult<()> {
+        journal::delete_incident(&self.conn.borrow(), incident_id)
+    }
+
+    fn morning_report(&self, event_id: i32, from: DateTime<Utc>, to: DateTime<Utc>) -> DbResult<MorningReport> {
+        journal::morning_report(&self.conn.borrow(), event_id, from, to)
+    }
+}
@@ -0,0 +1,574 @@
+//! Scenarios run through the `Repository` traits the way `app_controller` uses them, on a
+//! migrated in-memory database.
+
+use std::{cell::RefCell, rc::Rc};
+
+use chrono::{NaiveDate, TimeDelta, Utc};
+
+use super::{Repository, SqliteRepository};
+use crate::db_operations::test_support::{memory_db, person};
+use crate::db_operations::{
+    AlertLimits, DbError, Event, Group, GroupKind, IsInside, LeaveDetails, LeaveReason, LogEntry, LogFilter, LogPage, Methodology, NewIncident,
+    OverdueReason, Participant, Person, RankLevel, ReconcileWith, RotaRequest, RotaRules, Severity, ANONYMIZED_NAME, REDACTED_DESCRIPTION,
+};
+
+const CAMP: i32 = 1;
+
+fn repo() -> Box<dyn Repository> {
+    Box::new(SqliteRepository::new(Rc::new(RefCell::new(memory_db()))))
+}
+
+fn user_group(name: &str) -> Group {
+    Group {
+        id: 0,
+        name: name.to_string(),
+        kind: GroupKind::User,
+        methodology: None,
+        archived_at: None,
+    }
+}
+
+fn event(name: &str, ends_on: Option<NaiveDate>) -> Event {
+    Event {
+        id: 0,
+        name: name.to_string(),
+        starts_on: None,
+        ends_on,
+        location: String::new(),
+        retention_days: None,
+    }
+}
+
+// Three participants of the default event and a user group of the first two.
+fn camp(repo: &dyn Repository) -> (i32, i32, i32, i32) {
+    let a = repo.add_person(&person("Jan", "Kowalski", Methodology::Scout)).unwrap();
+    let b = repo.add_person(&person("Ola", "Nowak", Methodology::Cub)).unwrap();
+    let c = repo.add_person(&person("Piotr", "Kowal", Methodology::Rover)).unwrap();
+    let wolves = repo.add_group(&user_group("Wolves")).unwrap();
+    for id in [a, b] {
+        repo.add_member(wolves, id).unwrap();
+    }
+    for id in [a, b, c] {
+        repo.add_participant(CAMP, id).unwrap();
+    }
+    (a, b, c, wolves)
+}
+
+fn ids(persons: &[Person]) -> Vec<i32> {
+    persons.iter().map(|p| p.id).collect()
+}
+
+fn presence_of(participants: &[Participant]) -> Vec<(i32, IsInside)> {
+    participants.iter().map(|p| (p.person.id, p.is_inside)).collect()
+}
+
+// Member ids of each group, by group name.
+fn members(repo: &dyn Repository) -> Vec<(String, Vec<i32>)> {
+    let mut groups: Vec<_> = repo
+        .groups_with_members()
+        .unwrap()
+        .into_iter()
+        .map(|g| {
+            let mut members = ids(&g.members);
+            members.sort_unstable();
+            (g.name, members)
+        })
+        .collect();
+    groups.sort();
+    groups
+}
+
+fn group_members(repo: &dyn Repository, name: &str) -> Vec<i32> {
+    members(repo).into_iter().find(|(n, _)| n == name).map(|(_, m)| m).unwrap_or_default()
+}
+
+// (log id, sorted member log ids) of each entry.
+fn entry_ids(page: &LogPage) -> Vec<(i32, Vec<i32>)> {
+    page.entries
+        .iter()
+        .map(|e| match e {
+            LogEntry::Person(log) => (log.id, Vec::new()),
+            LogEntry::Group { log, members, .. } => {
+                let mut members: Vec<i32> = members.iter().map(|m| m.id).collect();
+                members.sort_unstable();
+                (log.id, members)
+            }
+        })
+        .collect()
+}
+
+#[test]
+fn roster() {
+    let repo = repo();
+    let (a, b, c, wolves) = camp(repo.as_ref());
+    assert!(matches!(repo.add_member(wolves, a), Err(DbError::Duplicate(_))));
+    assert!(matches!(repo.add_member(1, c), Err(DbError::ConstraintViolation(_))));
+    assert!(matches!(repo.add_member(99, c), Err(DbError::ConstraintViolation(_))));
+    assert_eq!(
+        members(repo.as_ref()),
+        [
+            ("Camp".to_string(), vec![a, b, c]),
+            ("Cub".to_string(), vec![b]),
+            ("Rover".to_string(), vec![c]),
+            ("Scout".to_string(), vec![a]),
+            ("Venture Scout".to_string(), vec![]),
+            ("Wolves".to_string(), vec![a, b]),
+        ]
+    );
+
+    // A new methodology moves the person between the built-in groups.
+    let mut moved = repo.person(b).unwrap();
+    moved.methodology = Methodology::Scout;
+    moved.patrol = "Foxes".to_string();
+    repo.update_person(&moved).unwrap();
+    assert_eq!((group_members(repo.as_ref(), "Cub"), group_members(repo.as_ref(), "Scout")), (vec![], vec![a, b]));
+    assert_eq!(repo.person(b).unwrap().patrol, "Foxes");
+    assert_eq!(ids(&repo.search_persons("kowal").unwrap()), [a, c]);
+    assert!(matches!(
+        repo.update_person(&person("No", "One", Methodology::Cub)),
+        Err(DbError::NotFound { entity: "person", id: 0 })
+    ));
+
+    assert!(matches!(repo.purge_person(c), Err(DbError::ConstraintViolation(_))));
+    repo.archive_person(c).unwrap();
+    assert_eq!(ids(&repo.persons().unwrap()), [a, b]);
+    assert_eq!(ids(&repo.archived_persons().unwrap()), [c]);
+    assert_eq!(presence_of(&repo.participants(CAMP).unwrap()), [(a, IsInside::Out), (b, IsInside::Out)]);
+    repo.restore_person(c).unwrap();
+    assert_eq!(ids(&repo.persons().unwrap()), [a, b, c]);
+    repo.archive_person(c).unwrap();
+    repo.purge_person(c).unwrap();
+    assert!(matches!(repo.person(c), Err(DbError::NotFound { entity: "person", .. })));
+    assert_eq!(group_members(repo.as_ref(), "Camp"), [a, b]);
+
+    assert!(matches!(repo.archive_group(1), Err(DbError::ConstraintViolation(_))));
+    let mut renamed = user_group("Grey wolves");
+    renamed.id = wolves;
+    repo.update_group(&renamed).unwrap();
+    repo.archive_group(wolves).unwrap();
+    assert!(repo.groups().unwrap().iter().all(|g| g.id != wolves));
+    let archived: Vec<_> = repo.archived_groups().unwrap().into_iter().map(|g| (g.id, g.name)).collect();
+    assert_eq!(archived, [(wolves, "Grey wolves".to_string())]);
+    repo.restore_group(wolves).unwrap();
+    repo.remove_member(wolves, a).unwrap();
+    assert!(matches!(repo.remove_member(wolves, a), Err(DbError::NotFound { .. })));
+    assert_eq!(group_members(repo.as_ref(), "Grey wolves"), [b]);
+    repo.archive_group(wolves).unwrap();
+    repo.purge_group(wolves).unwrap();
+    assert!(members(repo.as_ref()).iter().all(|(name, _)| name != "Grey wolves"));
+    assert!(repo.archived_groups().unwrap().is_empty());
+
+    let outing = repo.add_event(&event("Outing", NaiveDate::from_ymd_opt(2024, 7, 10))).unwrap();
+    repo.add_participant(outing, a).unwrap();
+    assert!(matches!(repo.add_participant(outing, a), Err(DbError::Duplicate(_))));
+    assert!(matches!(repo.add_participant(99, a), Err(DbError::ConstraintViolation(_))));
+    repo.set_active_event(outing).unwrap();
+    assert_eq!(repo.active_event().unwrap(), Some(outing));
+    let events: Vec<_> = repo.events().unwrap().into_iter().map(|e| (e.id, e.name)).collect();
+    assert_eq!(events, [(outing, "Outing".to_string()), (CAMP, "Camp".to_string())]);
+    assert_eq!(presence_of(&repo.participants(outing).unwrap()), [(a, IsInside::Out)]);
+    repo.remove_participant(outing, a).unwrap();
+    assert!(repo.participants(outing).unwrap().is_empty());
+}
+
+#[test]
+fn presence() {
+    let repo = repo();
+    let (a, b, c, wolves) = camp(repo.as_ref());
+    let first = repo.set_presence(CAMP, &[a, b], IsInside::In, Some("op")).unwrap();
+    assert_eq!((first.person_ids.as_slice(), first.group_log_id), ([a, b].as_slice(), None));
+    match repo.set_presence(CAMP, &[a, 999], IsInside::Out, None) {
+        Err(DbError::BatchFailed(failures)) => {
+            let failed: Vec<i32> = failures.iter().map(|(id, _)| *id).collect();
+            assert_eq!(failed, [999]);
+        }
+        other => panic!("expected BatchFailed, got {other:?}"),
+    }
+    let group_out = repo.set_group_presence(CAMP, wolves, &[a, b], IsInside::Out, Some("op")).unwrap();
+    assert_ne!(group_out.batch_id, first.batch_id);
+    let group_log = group_out.group_log_id.expect("group event row");
+    assert!(matches!(
+        repo.set_group_presence(CAMP, 99, &[a], IsInside::In, None),
+        Err(DbError::NotFound { entity: "group", id: 99 })
+    ));
+
+    let details = LeaveDetails {
+        destination: "Shop".to_string(),
+        reason: LeaveReason::Shop,
+        companion: "Leader".to_string(),
+        expected_return: Some(Utc::now() + TimeDelta::hours(1)),
+    };
+    repo.leave_with_pass(CAMP, None, &[c], None, &details).unwrap();
+    let passes = repo.open_leave_passes(CAMP).unwrap();
+    assert_eq!(passes.len(), 1);
+    assert_eq!((passes[0].person_id, passes[0].details.destination.as_str()), (c, "Shop"));
+    repo.set_presence(CAMP, &[a, c], IsInside::In, None).unwrap();
+    assert!(repo.open_leave_passes(CAMP).unwrap().is_empty());
+    assert_eq!(
+        presence_of(&repo.participants(CAMP).unwrap()),
+        [(a, IsInside::In), (b, IsInside::Out), (c, IsInside::In)]
+    );
+
+    // Rows: 1-2 in, 3 the group event with 4-5, 6 c's leave, 7-8 back in.
+    let logs: Vec<(i32, i32, IsInside)> = repo.person_logs().unwrap().iter().map(|l| (l.id, l.entity_id, l.is_inside)).collect();
+    assert_eq!(
+        logs,
+        [
+            (8, c, IsInside::In),
+            (7, a, IsInside::In),
+            (6, c, IsInside::Out),
+            (5, b, IsInside::Out),
+            (4, a, IsInside::Out),
+            (2, b, IsInside::In),
+            (1, a, IsInside::In),
+        ]
+    );
+
+    let page = repo.log_page(&LogFilter::default(), None, 2).unwrap();
+    assert_eq!(entry_ids(&page), [(8, vec![]), (7, vec![])]);
+    let page = repo.log_page(&LogFilter::default(), page.next, 2).unwrap();
+    assert_eq!(entry_ids(&page), [(6, vec![]), (group_log, vec![4, 5])]);
+    let page = repo.log_page(&LogFilter::default(), page.next, 2).unwrap();
+    assert_eq!(entry_ids(&page), [(2, vec![]), (1, vec![])]);
+    assert!(page.next.is_none());
+    for (filter, expected) in [
+        (LogFilter { person_id: Some(b), ..Default::default() }, vec![(group_log, vec![4, 5]), (2, vec![])]),
+        (LogFilter { group_id: Some(wolves), ..Default::default() }, vec![(7, vec![]), (group_log, vec![4, 5]), (2, vec![]), (1, vec![])]),
+        (LogFilter { is_inside: Some(IsInside::Out), ..Default::default() }, vec![(6, vec![]), (group_log, vec![4, 5])]),
+        (LogFilter { operator: Some("op".to_string()), ..Default::default() }, vec![(group_log, vec![4, 5]), (2, vec![]), (1, vec![])]),
+        (LogFilter { event_id: Some(99), ..Default::default() }, vec![]),
+    ] {
+        assert_eq!(entry_ids(&repo.log_page(&filter, None, 10).unwrap()), expected, "{filter:?}");
+    }
+
+    // Current membership, not the snapshot: b's own rows leave the group's history with b.
+    repo.remove_member(wolves, b).unwrap();
+    let by_group = repo.log_page(&LogFilter { group_id: Some(wolves), ..Default::default() }, None, 10).unwrap();
+    assert_eq!(entry_ids(&by_group), [(7, vec![]), (group_log, vec![4, 5]), (1, vec![])]);
+    repo.add_member(wolves, b).unwrap();
+
+    let later = Utc::now() + TimeDelta::hours(1);
+    let states: Vec<(i32, IsInside, Option<i32>)> = repo
+        .presence_at(CAMP, later)
+        .unwrap()
+        .into_iter()
+        .map(|s| (s.person_id, s.is_inside, s.last.map(|l| l.id)))
+        .collect();
+    assert_eq!(states, [(a, IsInside::In, Some(7)), (b, IsInside::Out, Some(5)), (c, IsInside::In, Some(8))]);
+    assert_eq!(repo.inside_at(CAMP, later).unwrap(), [a, c]);
+    let outside: Vec<(i32, Vec<bool>)> = repo
+        .time_outside(CAMP, later)
+        .unwrap()
+        .into_iter()
+        .map(|o| (o.person_id, o.outings.iter().map(|x| x.returned.is_some()).collect()))
+        .collect();
+    assert_eq!(outside, [(a, vec![true]), (b, vec![false]), (c, vec![true])]);
+
+    // Rejoining resets the stored presence; the log still says inside.
+    for id in [a, c] {
+        repo.remove_participant(CAMP, id).unwrap();
+        repo.add_participant(CAMP, id).unwrap();
+    }
+    let mismatches = repo.presence_mismatches(None).unwrap();
+    let found: Vec<(i32, IsInside, IsInside)> = mismatches.iter().map(|m| (m.person_id, m.stored, m.logged)).collect();
+    assert_eq!(found, [(a, IsInside::Out, IsInside::In), (c, IsInside::Out, IsInside::In)]);
+    repo.reconcile_presence(&mismatches[0], ReconcileWith::Log).unwrap();
+    repo.reconcile_presence(&mismatches[1], ReconcileWith::Stored).unwrap();
+    assert!(repo.presence_mismatches(Some(CAMP)).unwrap().is_empty());
+    // Trusting the log fixes the stored copy; trusting the stored copy logs c out.
+    assert_eq!(
+        presence_of(&repo.participants(CAMP).unwrap()),
+        [(a, IsInside::In), (b, IsInside::Out), (c, IsInside::Out)]
+    );
+    let newest = &repo.person_logs().unwrap()[0];
+    assert_eq!((newest.entity_id, newest.is_inside), (c, IsInside::Out));
+
+    // A mismatch listed before the person moved again is stale: fixing it changes nothing.
+    repo.remove_participant(CAMP, a).unwrap();
+    repo.add_participant(CAMP, a).unwrap();
+    let stale = repo.presence_mismatches(Some(CAMP)).unwrap().remove(0);
+    assert_eq!((stale.person_id, stale.logged), (a, IsInside::In));
+    repo.set_presence(CAMP, &[a], IsInside::Out, None).unwrap();
+    let rows = repo.person_logs().unwrap().len();
+    for with in [ReconcileWith::Log, ReconcileWith::Stored] {
+        repo.reconcile_presence(&stale, with).unwrap();
+    }
+    assert_eq!(presence_of(&repo.participants(CAMP).unwrap())[0], (a, IsInside::Out));
+    assert_eq!(repo.person_logs().unwrap().len(), rows);
+
+    // A purged person's rows stay, with the snapshot naming them.
+    repo.archive_person(b).unwrap();
+    repo.purge_person(b).unwrap();
+    let kept: Vec<_> = repo.person_logs().unwrap().into_iter().filter(|l| l.entity_id == b).collect();
+    assert_eq!(kept.len(), 2);
+    assert_eq!(kept[0].snapshot.as_ref().map(|s| s.surname.as_str()), Some("Nowak"));
+    let state = repo.presence_at(CAMP, later).unwrap().into_iter().find(|s| s.person_id == b).expect("purged person's state");
+    assert_eq!(state.is_inside, IsInside::Out);
+}
+
+#[test]
+fn privacy() {
+    let repo = repo();
+    let (a, b, _, _) = camp(repo.as_ref());
+    let past = repo.add_event(&event("Summer", NaiveDate::from_ymd_opt(2024, 7, 10))).unwrap();
+    let only_past = repo.add_person(&person("Ewa", "Lis", Methodology::Cub)).unwrap();
+    for id in [a, only_past] {
+        repo.add_participant(past, id).unwrap();
+    }
+    repo.set_presence(past, &[a], IsInside::In, None).unwrap();
+    repo.set_retention_days(Some(30)).unwrap();
+    assert_eq!(repo.retention_days().unwrap(), Some(30));
+    // Due 30 days after the person's last event ends; `a` still takes part in the open camp.
+    assert!(repo.persons_past_retention(NaiveDate::from_ymd_opt(2024, 8, 1).unwrap()).unwrap().is_empty());
+    let due = NaiveDate::from_ymd_opt(2024, 8, 10).unwrap();
+    assert_eq!(ids(&repo.persons_past_retention(due).unwrap()), [only_past]);
+
+    let incident = |description: &str, person_ids| NewIncident {
+        occurred_at: Utc::now(),
+        severity: Severity::Minor,
+        description: description.to_string(),
+        person_ids,
+        ..Default::default()
+    };
+    let about_a = repo.record_incident(CAMP, &incident("Jan Kowalski left the tent", vec![a, b]), None).unwrap();
+    let about_b = repo.record_incident(CAMP, &incident("Ola Nowak lost a torch", vec![b]), None).unwrap();
+
+    let export = repo.export_person(a).unwrap();
+    assert_eq!(export.person.surname, "Kowalski");
+    assert_eq!(export.groups, ["Camp", "Scout", "Wolves"]);
+    assert_eq!((export.events.len(), export.logs.len()), (2, 1));
+
+    repo.anonymize_person(a).unwrap();
+    assert!(matches!(repo.anonymize_person(a), Err(DbError::ConstraintViolation(_))));
+    assert!(matches!(repo.anonymize_person(999), Err(DbError::NotFound { entity: "person", id: 999 })));
+    let anonymized = repo.person(a).unwrap();
+    assert_eq!((anonymized.name.as_str(), anonymized.surname), (ANONYMIZED_NAME, format!("#{a}")));
+    assert!(anonymized.archived_at.is_some() && anonymized.anonymized_at.is_some());
+    // The rows stay for the headcount history, with their snapshot scrubbed too.
+    let logs = repo.export_person(a).unwrap().logs;
+    assert_eq!(logs.len(), 1);
+    assert_eq!(logs[0].snapshot.as_ref().map(|s| s.name.as_str()), Some(ANONYMIZED_NAME));
+    // Incident text may name them; the incident itself and its tags stay.
+    let incidents = repo.incidents(CAMP).unwrap();
+    let description = |id| incidents.iter().find(|i| i.id == id).map(|i| i.description.as_str());
+    assert_eq!((description(about_a), description(about_b)), (Some(REDACTED_DESCRIPTION), Some("Ola Nowak lost a torch")));
+
+    let other = repo.export_person(b).unwrap();
+    assert_eq!((other.person.surname.as_str(), other.events.len()), ("Nowak", 1));
+    assert_eq!(other.groups, ["Camp", "Cub", "Wolves"]);
+}
+
+#[test]
+fn alerts() {
+    let repo = repo();
+    let (a, b, c, _) = camp(repo.as_ref());
+    let mut limits = AlertLimits::default();
+    limits.set_max_out_minutes(Methodology::Scout, Some(60));
+    limits.set_max_out_minutes(Methodology::Cub, Some(30));
+    repo.set_alert_limits(&limits).unwrap();
+    let read = repo.alert_limits().unwrap();
+    assert_eq!((read.max_out_minutes(Methodology::Scout), read.max_out_minutes(Methodology::Rover)), (Some(60), None));
+
+    repo.set_presence(CAMP, &[a, b, c], IsInside::Out, None).unwrap();
+    let now = Utc::now();
+    let overdue = |at| -> Vec<i32> { repo.overdue_persons(CAMP, at).unwrap().iter().map(|o| o.person.id).collect() };
+    assert!(overdue(now + TimeDelta::minutes(10)).is_empty());
+    assert_eq!(overdue(now + TimeDelta::minutes(45)), [b]);
+    // c is a rover, without a limit.
+    assert_eq!(overdue(now + TimeDelta::hours(2)), [a, b]);
+    let reasons = repo.overdue_persons(CAMP, now + TimeDelta::hours(2)).unwrap().remove(0).reasons;
+    assert!(matches!(reasons.as_slice(), [OverdueReason::MaxOut { limit }] if *limit == TimeDelta::minutes(60)));
+    repo.set_presence(CAMP, &[b], IsInside::In, None).unwrap();
+    assert_eq!(overdue(now + TimeDelta::hours(2)), [a]);
+}
+
+#[test]
+fn roll_call() {
+    let repo = repo();
+    let (a, b, c, _) = camp(repo.as_ref());
+    repo.set_presence(CAMP, &[a, b], IsInside::In, None).unwrap();
+    let id = repo.start_roll_call(CAMP, Some("op")).unwrap();
+    assert!(matches!(repo.start_roll_call(CAMP, None), Err(DbError::ConstraintViolation(_))));
+    repo.tick_roll_call(id, a, true).unwrap();
+    repo.tick_roll_call(id, c, true).unwrap();
+    assert!(matches!(repo.tick_roll_call(id, 999, true), Err(DbError::NotFound { .. })));
+    let entries: Vec<(i32, IsInside, bool)> = repo.roll_call(id).unwrap().entries.iter().map(|e| (e.person_id, e.expected, e.present)).collect();
+    assert_eq!(entries, [(c, IsInside::Out, true), (a, IsInside::In, true), (b, IsInside::In, false)]);
+    let found: Vec<(i32, IsInside)> = repo.roll_call_discrepancies(id).unwrap().iter().map(|d| (d.person_id, d.counted)).collect();
+    assert_eq!(found, [(c, IsInside::In), (b, IsInside::Out)]);
+
+    repo.finish_roll_call(id).unwrap();
+    assert!(matches!(repo.tick_roll_call(id, b, true), Err(DbError::ConstraintViolation(_))));
+    assert!(matches!(repo.finish_roll_call(id), Err(DbError::ConstraintViolation(_))));
+    repo.fix_roll_call(id, &[b, c], Some("op")).unwrap();
+    assert!(repo.roll_call_discrepancies(id).unwrap().is_empty());
+    assert_eq!(
+        presence_of(&repo.participants(CAMP).unwrap()),
+        [(a, IsInside::In), (b, IsInside::Out), (c, IsInside::In)]
+    );
+    let roll_calls = repo.roll_calls(CAMP).unwrap();
+    assert_eq!((roll_calls.len(), roll_calls[0].is_finished()), (1, true));
+    assert!(matches!(repo.roll_call(999), Err(DbError::NotFound { .. })));
+}
+
+#[test]
+fn rota() {
+    let repo = repo();
+    let (a, b, c, wolves) = camp(repo.as_ref());
+    let mut ranked = repo.person(c).unwrap();
+    ranked.rank_level = RankLevel::RankThirdM;
+    repo.update_person(&ranked).unwrap();
+
+    let starts_at = Utc::now() + TimeDelta::days(1);
+    let request = |per_shift, rules| RotaRequest {
+        starts_at,
+        ends_at: starts_at + TimeDelta::hours(4),
+        shift_minutes: 60,
+        per_shift,
+        rules,
+    };
+    let guards = |rota_id| -> Vec<Vec<Option<i32>>> {
+        repo.rota(rota_id)
+            .unwrap()
+            .shifts
+            .iter()
+            .map(|s| s.guards.iter().map(|g| g.as_ref().map(|p| p.id)).collect())
+            .collect()
+    };
+    let everyone = repo.generate_rota(CAMP, &request(2, RotaRules::default())).unwrap();
+    let plan = guards(everyone);
+    assert_eq!(plan.len(), 4);
+    assert!(plan.iter().all(|shift| shift.len() == 2));
+    // Three people, two per shift, nobody twice in a row.
+    for pair in plan.windows(2) {
+        assert!(pair[0].iter().flatten().all(|id| !pair[1].contains(&Some(*id))), "{plan:?}");
+    }
+    assert!(plan[0].iter().all(Option::is_some));
+
+    let scouts = RotaRules {
+        min_methodology: Some(Methodology::Scout),
+        ..Default::default()
+    };
+    let scouts = repo.generate_rota(CAMP, &request(1, scouts)).unwrap();
+    assert!(guards(scouts).iter().flatten().flatten().all(|&id| id == a || id == c));
+    let pack = RotaRules {
+        group_id: Some(wolves),
+        no_back_to_back: false,
+        ..Default::default()
+    };
+    let pack = repo.generate_rota(CAMP, &request(1, pack)).unwrap();
+    assert!(guards(pack).iter().flatten().flatten().all(|&id| id == a || id == b));
+    let ranks = RotaRules {
+        min_rank_level: 3,
+        ..Default::default()
+    };
+    let ranks = repo.generate_rota(CAMP, &request(1, ranks)).unwrap();
+    // Only c qualifies, and not twice in a row.
+    assert_eq!(guards(ranks), [vec![Some(c)], vec![None], vec![Some(c)], vec![None]]);
+    assert!(matches!(
+        repo.generate_rota(99, &request(1, RotaRules::default())),
+        Err(DbError::NotFound { entity: "event", id: 99 })
+    ));
+    let rotas: Vec<i32> = repo.rotas(CAMP).unwrap().iter().map(|r| r.id).collect();
+    assert_eq!(rotas, [ranks, pack, scouts, everyone]);
+
+    repo.swap_rota_slots(everyone, (0, 0), (0, 1)).unwrap();
+    assert_eq!(guards(everyone)[0], [plan[0][1], plan[0][0]]);
+    repo.swap_rota_slots(everyone, (0, 0), (1, 0)).unwrap();
+    assert_eq!((guards(everyone)[0][0], guards(everyone)[1][0]), (plan[1][0], plan[0][1]));
+    let plan = guards(everyone);
+    repo.set_rota_slot(everyone, (2, 1), None).unwrap();
+    assert_eq!(guards(everyone)[2][1], None);
+    assert!(matches!(repo.set_rota_slot(everyone, (2, 1), plan[2][0]), Err(DbError::Duplicate(_))));
+    repo.set_rota_slot(everyone, (2, 1), plan[2][1]).unwrap();
+    assert_eq!(guards(everyone), plan);
+    assert!(matches!(repo.set_rota_slot(everyone, (9, 0), Some(a)), Err(DbError::NotFound { .. })));
+
+    // A purged guard leaves an empty slot.
+    repo.archive_person(b).unwrap();
+    repo.purge_person(b).unwrap();
+    let expected: Vec<Vec<Option<i32>>> = plan.iter().map(|s| s.iter().map(|g| g.filter(|&id| id != b)).collect()).collect();
+    assert_eq!(guards(everyone), expected);
+    repo.delete_rota(everyone).unwrap();
+    assert!(matches!(repo.rota(everyone), Err(DbError::NotFound { entity: "rota", .. })));
+}
+
+#[test]
+fn journal() {
+    let repo = repo();
+    let (a, b, _, wolves) = camp(repo.as_ref());
+    let gate = repo.add_checkpoint(CAMP, " Gate ").unwrap();
+    let kitchen = repo.add_checkpoint(CAMP, "Kitchen").unwrap();
+    assert!(matches!(repo.add_checkpoint(CAMP, "Gate"), Err(DbError::Duplicate(_))));
+    assert!(matches!(repo.add_checkpoint(CAMP, "  "), Err(DbError::ConstraintViolation(_))));
+    assert!(matches!(repo.add_checkpoint(99, "Gate"), Err(DbError::NotFound { entity: "event", id: 99 })));
+    let names: Vec<_> = repo.checkpoints(CAMP).unwrap().into_iter().map(|c| (c.id, c.name)).collect();
+    assert_eq!(names, [(gate, "Gate".to_string()), (kitchen, "Kitchen".to_string())]);
+
+    let now = Utc::now();
+    let old = NewIncident {
+        occurred_at: now - TimeDelta::hours(30),
+        severity: Severity::Minor,
+        description: "old".to_string(),
+        ..Default::default()
+    };
+    let early = repo.record_incident(CAMP, &old, None).unwrap();
+    let shift = repo.start_sentry_shift(CAMP, " A, B ", Some("op")).unwrap();
+    assert!(matches!(repo.start_sentry_shift(CAMP, "", None), Err(DbError::ConstraintViolation(_))));
+    repo.visit_checkpoint(shift, gate).unwrap();
+    assert!(matches!(repo.visit_checkpoint(999, gate), Err(DbError::NotFound { .. })));
+    let noise = NewIncident {
+        occurred_at: now,
+        severity: Severity::Serious,
+        description: " noise in tent ".to_string(),
+        person_ids: vec![b, a, a],
+        group_ids: vec![wolves],
+    };
+    let noise = repo.record_incident(CAMP, &noise, Some("op")).unwrap();
+    let blank = NewIncident {
+        description: " ".to_string(),
+        ..Default::default()
+    };
+    assert!(matches!(repo.record_incident(CAMP, &blank, None), Err(DbError::ConstraintViolation(_))));
+    let stranger = NewIncident {
+        description: "x".to_string(),
+        person_ids: vec![999],
+        ..Default::default()
+    };
+    assert!(matches!(repo.record_incident(CAMP, &stranger, None), Err(DbError::NotFound { entity: "person", id: 999 })));
+    repo.end_sentry_shift(shift).unwrap();
+    assert!(matches!(repo.end_sentry_shift(shift), Err(DbError::ConstraintViolation(_))));
+    assert!(matches!(repo.visit_checkpoint(shift, kitchen), Err(DbError::ConstraintViolation(_))));
+    let next = repo.start_sentry_shift(CAMP, "C", None).unwrap();
+    repo.visit_checkpoint(next, kitchen).unwrap();
+
+    let shifts = repo.sentry_shifts(CAMP).unwrap();
+    let shifts: Vec<_> = shifts.iter().map(|s| (s.id, s.guards.as_str(), s.ended_at.is_some(), s.visits.len())).collect();
+    assert_eq!(shifts, [(shift, "A, B", true, 1), (next, "C", false, 1)]);
+    let incidents = repo.incidents(CAMP).unwrap();
+    let tagged = &incidents[1];
+    assert_eq!((tagged.id, tagged.shift_id, tagged.description.as_str()), (noise, Some(shift), "noise in tent"));
+    assert_eq!((ids(&tagged.persons), tagged.groups.len()), (vec![a, b], 1));
+
+    let report = repo.morning_report(CAMP, now - TimeDelta::hours(12), now + TimeDelta::hours(1)).unwrap();
+    let reported: Vec<i32> = report.incidents.iter().map(|i| i.id).collect();
+    assert_eq!(reported, [noise]);
+    let missed: Vec<Vec<String>> = report
+        .shifts
+        .iter()
+        .map(|s| report.missed_checkpoints(s).iter().map(|c| c.name.clone()).collect())
+        .collect();
+    assert_eq!(missed, [vec!["Kitchen".to_string()], vec!["Gate".to_string()]]);
+    let exported: Vec<i32> = repo.export_person(a).unwrap().incidents.iter().map(|i| i.id).collect();
+    assert_eq!(exported, [noise]);
+
+    // Tags of purged persons and groups go; the incident stays.
+    repo.archive_person(a).unwrap();
+    repo.purge_person(a).unwrap();
+    repo.archive_group(wolves).unwrap();
+    repo.purge_group(wolves).unwrap();
+    let tagged = repo.incidents(CAMP).unwrap().remove(1);
+    assert_eq!((ids(&tagged.persons), tagged.groups.len()), (vec![b], 0));
+    repo.delete_incident(early).unwrap();
+    assert!(matches!(repo.delete_incident(early), Err(DbError::NotFound { entity: "incident", .. })));
+}
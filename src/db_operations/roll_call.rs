@@ -0,0 +1,335 @@
+//! Roll calls (apel): confirming the presence list by ticking everyone off.
+//!
+//! Starting one takes a snapshot of every active participant of the event with their stored
+//! presence (`expected`). Ticked-off persons count as inside, the others as outside. A
+//! discrepancy is someone counted otherwise than expected. Anyone checked in or out after the
+//! roll call started is left out (the log knows better than the list; log times are whole
+//! seconds, so that is from the next second on), and so is anyone whose stored presence
+//! already matches the count. Fixing one writes a regular check-in/out
+//! (`update::write_presence_rows`).
+//!
+//! An event has at most one roll call in progress. Finished ones are kept as a record and
+//! can't be ticked anymore.
+
+use chrono::{DateTime, Utc};
+use rusqlite::{Connection, OptionalExtension};
+
+use super::types::EntityType;
+use super::update::write_presence_rows;
+use super::{expect_row, format_db_datetime, parse_db_datetime, DbError, DbResult, IsInside};
+
+/// One roll call session and its list.
+#[derive(Debug, Clone)]
+pub struct RollCall {
+    pub id: i32,
+    pub event_id: i32,
+    pub started_at: DateTime<Utc>,
+    /// `None` while in progress.
+    pub finished_at: Option<DateTime<Utc>>,
+    pub operator: Option<String>,
+    /// Ordered by patrol, then surname and name (see `sort_entries`).
+    pub entries: Vec<RollCallEntry>,
+}
+
+impl RollCall {
+    pub fn is_finished(&self) -> bool {
+        self.finished_at.is_some()
+    }
+}
+
+/// A participant on a roll call list, named as they are now.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RollCallEntry {
+    pub person_id: i32,
+    pub name: String,
+    pub surname: String,
+    pub patrol: String,
+    /// Stored presence when the roll call started.
+    pub expected: IsInside,
+    /// Ticked off.
+    pub present: bool,
+    pub ticked_at: Option<DateTime<Utc>>,
+}
+
+/// A participant the roll call counted otherwise than expected.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RollCallDiscrepancy {
+    pub person_id: i32,
+    pub name: String,
+    pub surname: String,
+    pub patrol: String,
+    /// What the roll call found: `In` when ticked off.
+    pub counted: IsInside,
+    /// `EventParticipants.is_inside` now.
+    pub stored: IsInside,
+}
+
+/// Starts a roll call of event `event_id`; returns its id. `ConstraintViolation` if one is
+/// already in progress.
+pub fn start_roll_call(conn: &Connection, event_id: i32, operator: Option<&str>) -> DbResult<i32> {
+    let tx = conn.unchecked_transaction()?;
+    let event_exists: bool = tx.query_row("SELECT EXISTS(SELECT 1 FROM `Event` WHERE `id` = ?1);", (event_id,), |row| {
+        row.get(0)
+    })?;
+    if !event_exists {
+        return Err(DbError::NotFound { entity: "event", id: event_id });
+    }
+    let in_progress: Option<i32> = tx
+        .query_row(
+            "SELECT `id` FROM `RollCall` WHERE `event_id` = ?1 AND `finished_at` IS NULL;",
+            (event_id,),
+            |row| row.get(0),
+        )
+        .optional()?;
+    if let Some(id) = in_progress {
+        return Err(in_progress_error(event_id, id));
+    }
+
+    tx.execute(
+        "INSERT INTO `RollCall`(`event_id`, `started_at`, `operator`) VALUES(?1, ?2, ?3);",
+        (event_id, format_db_datetime(&Utc::now()), operator),
+    )?;
+    let id = tx.last_insert_rowid() as i32;
+    tx.execute(
+        "INSERT INTO `RollCallEntry`(`roll_call_id`, `person_id`, `expected`)
+         SELECT ?1, `ep`.`person_id`, `ep`.`is_inside`
+         FROM `EventParticipants` `ep` JOIN `Person` `p` ON `p`.`id` = `ep`.`person_id` AND `p`.`archived_at` IS NULL
+         WHERE `ep`.`event_id` = ?2;",
+        (id, event_id),
+    )?;
+    tx.commit()?;
+    Ok(id)
+}
+
+pub(super) fn in_progress_error(event_id: i32, roll_call_id: i32) -> DbError {
+    DbError::ConstraintViolation(format!("event {event_id} already has roll call {roll_call_id} in progress"))
+}
+
+pub(super) fn finished_error(roll_call_id: i32) -> DbError {
+    DbError::ConstraintViolation(format!("roll call {roll_call_id} is finished"))
+}
+
+/// Roll calls of event `event_id`, newest first.
+pub fn get_roll_calls(conn: &Connection, event_id: i32) -> DbResult<Vec<RollCall>> {
+    let mut stmt = conn.prepare_cached("SELECT `id` FROM `RollCall` WHERE `event_id` = ?1 ORDER BY `started_at` DESC, `id` DESC;")?;
+    let ids = stmt
+        .query_map((event_id,), |row| row.get(0))?
+        .collect::<rusqlite::Result<Vec<i32>>>()?;
+    ids.into_iter().map(|id| get_roll_call(conn, id)).collect()
+}
+
+pub fn get_roll_call(conn: &Connection, roll_call_id: i32) -> DbResult<RollCall> {
+    let header = conn
+        .query_row(
+            "SELECT `event_id`, `started_at`, `finished_at`, `operator` FROM `RollCall` WHERE `id` = ?1;",
+            (roll_call_id,),
+            |row| {
+                let started_at: String = row.get(1)?;
+                let finished_at: Option<String> = row.get(2)?;
+                Ok((
+                    row.get::<_, i32>(0)?,
+                    parse_db_datetime(&started_at)?,
+                    finished_at.map(|t| parse_db_datetime(&t)).transpose()?,
+                    row.get::<_, Option<String>>(3)?,
+                ))
+            },
+        )
+        .optional()?;
+    let Some((event_id, started_at, finished_at, operator)) = header else {
+        return Err(DbError::NotFound { entity: "roll call", id: roll_call_id });
+    };
+
+    let mut stmt = conn.prepare_cached(
+        "SELECT `e`.`person_id`, `p`.`name`, `p`.`surname`, `p`.`patrol`, `e`.`expected`, `e`.`present`, `e`.`ticked_at`
+         FROM `RollCallEntry` `e` JOIN `Person` `p` ON `p`.`id` = `e`.`person_id`
+         WHERE `e`.`roll_call_id` = ?1;",
+    )?;
+    let mut entries = stmt
+        .query_map((roll_call_id,), |row| {
+            let ticked_at: Option<String> = row.get(6)?;
+            Ok(RollCallEntry {
+                person_id: row.get(0)?,
+                name: row.get(1)?,
+                surname: row.get(2)?,
+                patrol: row.get(3)?,
+                expected: row.get(4)?,
+                present: row.get(5)?,
+                ticked_at: ticked_at.map(|t| parse_db_datetime(&t)).transpose()?,
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    sort_entries(&mut entries);
+
+    Ok(RollCall {
+        id: roll_call_id,
+        event_id,
+        started_at,
+        finished_at,
+        operator,
+        entries,
+    })
+}
+
+/// Patrol, then surname and name (case-insensitive), then id; persons without a patrol last.
+pub(super) fn sort_entries(entries: &mut [RollCallEntry]) {
+    entries.sort_by_cached_key(|e| {
+        (e.patrol.is_empty(), e.patrol.to_lowercase(), e.surname.to_lowercase(), e.name.to_lowercase(), e.person_id)
+    });
+}
+
+/// Ticks `person_id` off (or back on) the roll call in progress `roll_call_id`.
+pub fn tick_roll_call(conn: &Connection, roll_call_id: i32, person_id: i32, present: bool) -> DbResult<()> {
+    let tx = conn.unchecked_transaction()?;
+    ensure_in_progress(&tx, roll_call_id)?;
+    let time = present.then(|| format_db_datetime(&Utc::now()));
+    let updated = tx.execute(
+        "UPDATE `RollCallEntry` SET `present` = ?3, `ticked_at` = ?4 WHERE `roll_call_id` = ?1 AND `person_id` = ?2;",
+        (roll_call_id, person_id, present, time),
+    )?;
+    expect_row(updated, "roll call entry", person_id)?;
+    tx.commit()?;
+    Ok(())
+}
+
+pub fn finish_roll_call(conn: &Connection, roll_call_id: i32) -> DbResult<()> {
+    let tx = conn.unchecked_transaction()?;
+    ensure_in_progress(&tx, roll_call_id)?;
+    tx.execute(
+        "UPDATE `RollCall` SET `finished_at` = ?2 WHERE `id` = ?1;",
+        (roll_call_id, format_db_datetime(&Utc::now())),
+    )?;
+    tx.commit()?;
+    Ok(())
+}
+
+fn ensure_in_progress(conn: &Connection, roll_call_id: i32) -> DbResult<()> {
+    let finished_at: Option<Option<String>> = conn
+        .query_row("SELECT `finished_at` FROM `RollCall` WHERE `id` = ?1;", (roll_call_id,), |row| row.get(0))
+        .optional()?;
+    match finished_at {
+        None => Err(DbError::NotFound { entity: "roll call", id: roll_call_id }),
+        Some(Some(_)) => Err(finished_error(roll_call_id)),
+        Some(None) => Ok(()),
+    }
+}
+
+/// Participants of roll call `roll_call_id` counted otherwise than expected, in list order.
+/// Persons who moved since it started, archived persons and persons no longer taking part
+/// are left out.
+pub fn roll_call_discrepancies(conn: &Connection, roll_call_id: i32) -> DbResult<Vec<RollCallDiscrepancy>> {
+    let roll_call = get_roll_call(conn, roll_call_id)?;
+    let mut stmt = conn.prepare_cached(
+        "SELECT `ep`.`is_inside` FROM `EventParticipants` `ep` JOIN `Person` `p` ON `p`.`id` = `ep`.`person_id` AND `p`.`archived_at` IS NULL
+         WHERE `ep`.`event_id` = ?1 AND `ep`.`person_id` = ?2;",
+    )?;
+    let mut moved_stmt = conn.prepare_cached(
+        "SELECT EXISTS(SELECT 1 FROM `Log` WHERE `entity_type` = ?1 AND `event_id` = ?2 AND `entity_id` = ?3 AND `timestamp` > ?4);",
+    )?;
+    let started_at = format_db_datetime(&roll_call.started_at);
+    let mut discrepancies = Vec::new();
+    for entry in roll_call.entries {
+        let stored: Option<IsInside> = stmt.query_row((roll_call.event_id, entry.person_id), |row| row.get(0)).optional()?;
+        let Some(stored) = stored else {
+            continue;
+        };
+        let moved: bool = moved_stmt.query_row((EntityType::Person, roll_call.event_id, entry.person_id, &started_at), |row| row.get(0))?;
+        if moved {
+            continue;
+        }
+        if let Some(discrepancy) = discrepancy(entry, stored) {
+            discrepancies.push(discrepancy);
+        }
+    }
+    Ok(discrepancies)
+}
+
+/// `entry` as a discrepancy, if what it counted differs from its `expected` snapshot and
+/// `stored` doesn't already agree with the count.
+fn discrepancy(entry: RollCallEntry, stored: IsInside) -> Option<RollCallDiscrepancy> {
+    let counted = if entry.present { IsInside::In } else { IsInside::Out };
+    (counted != entry.expected && counted != stored).then_some(RollCallDiscrepancy {
+        person_id: entry.person_id,
+        name: entry.name,
+        surname: entry.surname,
+        patrol: entry.patrol,
+        counted,
+        stored,
+    })
+}
+
+/// Settles the discrepancies of `person_ids` in roll call `roll_call_id` in favour of the roll
+/// call, as one check-in batch and one check-out batch by `operator`. Ids without a
+/// discrepancy are skipped.
+pub fn fix_roll_call(conn: &Connection, roll_call_id: i32, person_ids: &[i32], operator: Option<&str>) -> DbResult<()> {
+    let tx = conn.unchecked_transaction()?;
+    let event_id: i32 = tx
+        .query_row("SELECT `event_id` FROM `RollCall` WHERE `id` = ?1;", (roll_call_id,), |row| row.get(0))
+        .optional()?
+        .ok_or(DbError::NotFound { entity: "roll call", id: roll_call_id })?;
+    let discrepancies = roll_call_discrepancies(&tx, roll_call_id)?;
+    for direction in [IsInside::In, IsInside::Out] {
+        let ids: Vec<i32> = discrepancies
+            .iter()
+            .filter(|d| d.counted == direction && person_ids.contains(&d.person_id))
+            .map(|d| d.person_id)
+            .collect();
+        if !ids.is_empty() {
+            write_presence_rows(&tx, event_id, None, &ids, direction, operator, None)?;
+        }
+    }
+    tx.commit()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use chrono::TimeDelta;
+
+    use super::*;
+    use crate::db_operations::test_support::{memory_db, person};
+    use crate::db_operations::{get_event_participants, set_persons_is_inside, EventRepository, Methodology, PersonRepository, SqliteRepository};
+
+    const EVENT_ID: i32 = 1;
+
+    #[test]
+    fn moves_during_the_roll_call_are_not_discrepancies() {
+        let conn = Rc::new(RefCell::new(memory_db()));
+        let repo = SqliteRepository::new(conn.clone());
+        let [a, b, c, d] = [("Jan", "Kowalski"), ("Ola", "Nowak"), ("Ewa", "Lis"), ("Piotr", "Wilk")]
+            .map(|(name, surname)| repo.add_person(&person(name, surname, Methodology::Scout)).unwrap());
+        for id in [a, b, c, d] {
+            repo.add_participant(EVENT_ID, id).unwrap();
+        }
+        let conn = conn.borrow();
+        set_persons_is_inside(&conn, EVENT_ID, &[a, b, d], IsInside::In, None).unwrap();
+        let id = start_roll_call(&conn, EVENT_ID, None).unwrap();
+        // Started a minute ago, so the moves below come after it.
+        conn.execute(
+            "UPDATE `RollCall` SET `started_at` = ?2 WHERE `id` = ?1;",
+            (id, format_db_datetime(&(Utc::now() - TimeDelta::minutes(1)))),
+        )
+        .unwrap();
+
+        // a is ticked off, then checks out: the list is right about when it was read.
+        tick_roll_call(&conn, id, a, true).unwrap();
+        set_persons_is_inside(&conn, EVENT_ID, &[a], IsInside::Out, None).unwrap();
+        // b missed it while popping out and back in.
+        set_persons_is_inside(&conn, EVENT_ID, &[b], IsInside::Out, None).unwrap();
+        set_persons_is_inside(&conn, EVENT_ID, &[b], IsInside::In, None).unwrap();
+        // c was out but stood in the line; d checked out before being read.
+        tick_roll_call(&conn, id, c, true).unwrap();
+        set_persons_is_inside(&conn, EVENT_ID, &[d], IsInside::Out, None).unwrap();
+
+        let found: Vec<(i32, IsInside, IsInside)> =
+            roll_call_discrepancies(&conn, id).unwrap().iter().map(|d| (d.person_id, d.counted, d.stored)).collect();
+        assert_eq!(found, [(c, IsInside::In, IsInside::Out)]);
+
+        fix_roll_call(&conn, id, &[a, b, c, d], None).unwrap();
+        let presence: Vec<(i32, IsInside)> =
+            get_event_participants(&conn, EVENT_ID).unwrap().iter().map(|p| (p.person.id, p.is_inside)).collect();
+        assert_eq!(presence, [(a, IsInside::Out), (b, IsInside::In), (c, IsInside::In), (d, IsInside::Out)]);
+        assert!(roll_call_discrepancies(&conn, id).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,522 @@
+//! Sentry duty (warta) rotas: night watches planned from the event's participants.
+//!
+//! A night window is cut into shifts of `shift_minutes` (the last one ends with the window),
+//! each with `per_shift` slots. Participants are eligible when they meet the minimum
+//! methodology and rank level and, if the rota is limited to a group, belong to it.
+//!
+//! Slots are filled shift by shift. Everyone eligible is ranked by how many duties they
+//! already have in the event's rotas (this one included as it fills), then by how long ago
+//! their last one started, then by id; the first ones not already on the shift (nor on the
+//! previous one, with `no_back_to_back`) get it. Slots nobody can take stay empty and can be
+//! filled by hand, like any slot can be swapped or changed.
+
+use std::collections::{HashMap, HashSet};
+
+use chrono::{DateTime, TimeDelta, Utc};
+use rusqlite::{Connection, OptionalExtension};
+
+use super::queries::{get_event_participants, person_from_row, PERSON_LIST_COLUMNS};
+use super::{expect_row, format_db_datetime, parse_db_datetime, DbError, DbResult, Methodology, Person};
+
+/// Longest night a rota can cover, in shifts.
+pub const MAX_SHIFTS: usize = 48;
+
+/// Who may stand guard.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RotaRules {
+    pub min_methodology: Option<Methodology>,
+    /// `RankLevel::level`; 0 for any.
+    pub min_rank_level: u8,
+    /// Only members of this group, if set.
+    pub group_id: Option<i32>,
+    /// Nobody gets two shifts in a row.
+    pub no_back_to_back: bool,
+}
+
+impl Default for RotaRules {
+    fn default() -> Self {
+        RotaRules {
+            min_methodology: None,
+            min_rank_level: 0,
+            group_id: None,
+            no_back_to_back: true,
+        }
+    }
+}
+
+impl RotaRules {
+    fn admits(&self, person: &Person, group_members: Option<&HashSet<i32>>) -> bool {
+        self.min_methodology.is_none_or(|m| person.methodology >= m)
+            && person.rank_level.level() >= self.min_rank_level
+            && group_members.is_none_or(|members| members.contains(&person.id))
+    }
+}
+
+/// What to plan.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RotaRequest {
+    pub starts_at: DateTime<Utc>,
+    pub ends_at: DateTime<Utc>,
+    pub shift_minutes: u32,
+    pub per_shift: u32,
+    pub rules: RotaRules,
+}
+
+/// A stored rota.
+#[derive(Debug, Clone)]
+pub struct Rota {
+    pub id: i32,
+    #[allow(dead_code)]
+    pub event_id: i32,
+    pub request: RotaRequest,
+    pub created_at: DateTime<Utc>,
+    /// In time order.
+    pub shifts: Vec<RotaShift>,
+}
+
+impl Rota {
+    pub fn empty_slots(&self) -> usize {
+        self.shifts.iter().flat_map(|s| &s.guards).filter(|g| g.is_none()).count()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RotaShift {
+    pub starts_at: DateTime<Utc>,
+    pub ends_at: DateTime<Utc>,
+    /// One entry per slot; `None` for an empty one.
+    pub guards: Vec<Option<Person>>,
+}
+
+/// Person ids per shift and slot; `None` for an empty slot.
+pub(super) type SlotIds = Vec<Vec<Option<i32>>>;
+
+/// Start and end of every shift of `request`; `ConstraintViolation` for an empty window, a
+/// zero shift length or slot count, or more than `MAX_SHIFTS` shifts.
+pub(super) fn shift_windows(request: &RotaRequest) -> DbResult<Vec<(DateTime<Utc>, DateTime<Utc>)>> {
+    if request.ends_at <= request.starts_at || request.shift_minutes == 0 || request.per_shift == 0 {
+        return Err(DbError::ConstraintViolation(
+            "a rota needs a night window, a shift length and at least one person per shift".to_string(),
+        ));
+    }
+    let length = TimeDelta::minutes(i64::from(request.shift_minutes));
+    let mut windows = Vec::new();
+    let mut start = request.starts_at;
+    while start < request.ends_at {
+        if windows.len() == MAX_SHIFTS {
+            return Err(DbError::ConstraintViolation(format!("a rota can have at most {MAX_SHIFTS} shifts")));
+        }
+        let end = (start + length).min(request.ends_at);
+        windows.push((start, end));
+        start = end;
+    }
+    Ok(windows)
+}
+
+/// Slots of `request` filled from the event's active `participants` (`group_members` only,
+/// if set), counting the duties they have in the event's `existing` rotas.
+pub(super) fn plan_rota(
+    request: &RotaRequest,
+    participants: &[Person],
+    group_members: Option<&HashSet<i32>>,
+    existing: &[Rota],
+) -> DbResult<SlotIds> {
+    let windows = shift_windows(request)?;
+    let eligible: Vec<i32> = participants
+        .iter()
+        .filter(|p| request.rules.admits(p, group_members))
+        .map(|p| p.id)
+        .collect();
+    if eligible.is_empty() {
+        return Err(DbError::ConstraintViolation("nobody taking part in the event meets the rota rules".to_string()));
+    }
+
+    // (duties, start of the last one) per person.
+    let mut history: HashMap<i32, (usize, Option<DateTime<Utc>>)> = HashMap::new();
+    for shift in existing.iter().flat_map(|r| &r.shifts) {
+        for guard in shift.guards.iter().flatten() {
+            let entry = history.entry(guard.id).or_default();
+            entry.0 += 1;
+            entry.1 = entry.1.max(Some(shift.starts_at));
+        }
+    }
+
+    let mut plan: SlotIds = Vec::with_capacity(windows.len());
+    for (start, _) in windows {
+        let previous: Vec<i32> = match plan.last() {
+            Some(previous) if request.rules.no_back_to_back => previous.iter().flatten().copied().collect(),
+            _ => Vec::new(),
+        };
+        let mut ranked: Vec<i32> = eligible.iter().copied().filter(|id| !previous.contains(id)).collect();
+        ranked.sort_by_key(|id| {
+            let (duties, last) = history.get(id).copied().unwrap_or_default();
+            (duties, last, *id)
+        });
+        let mut shift: Vec<Option<i32>> = ranked.into_iter().take(request.per_shift as usize).map(Some).collect();
+        shift.resize(request.per_shift as usize, None);
+        for id in shift.iter().flatten() {
+            let entry = history.entry(*id).or_default();
+            entry.0 += 1;
+            entry.1 = Some(start);
+        }
+        plan.push(shift);
+    }
+    Ok(plan)
+}
+
+/// Plans and stores a rota for event `event_id`; returns its id.
+pub fn generate_rota(conn: &Connection, event_id: i32, request: &RotaRequest) -> DbResult<i32> {
+    let tx = conn.unchecked_transaction()?;
+    let event_exists: bool = tx.query_row("SELECT EXISTS(SELECT 1 FROM `Event` WHERE `id` = ?1);", (event_id,), |row| {
+        row.get(0)
+    })?;
+    if !event_exists {
+        return Err(DbError::NotFound { entity: "event", id: event_id });
+    }
+    let group_members = match request.rules.group_id {
+        None => None,
+        Some(group_id) => Some(group_member_ids(&tx, group_id)?),
+    };
+    let participants: Vec<Person> = get_event_participants(&tx, event_id)?.into_iter().map(|p| p.person).collect();
+    let plan = plan_rota(request, &participants, group_members.as_ref(), &get_rotas(&tx, event_id)?)?;
+
+    let rules = &request.rules;
+    tx.execute(
+        "INSERT INTO `Rota`(`event_id`, `starts_at`, `ends_at`, `shift_minutes`, `per_shift`, `min_methodology`, `min_rank_level`,
+            `group_id`, `no_back_to_back`, `created_at`)
+         VALUES(?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10);",
+        rusqlite::params![
+            event_id,
+            format_db_datetime(&request.starts_at),
+            format_db_datetime(&request.ends_at),
+            request.shift_minutes,
+            request.per_shift,
+            rules.min_methodology,
+            rules.min_rank_level,
+            rules.group_id,
+            rules.no_back_to_back,
+            format_db_datetime(&Utc::now()),
+        ],
+    )?;
+    let rota_id = tx.last_insert_rowid() as i32;
+    {
+        let mut stmt = tx.prepare("INSERT INTO `RotaSlot`(`rota_id`, `shift`, `slot`, `person_id`) VALUES(?1, ?2, ?3, ?4);")?;
+        for (shift, guards) in plan.iter().enumerate() {
+            for (slot, person_id) in guards.iter().enumerate() {
+                stmt.execute((rota_id, shift, slot, person_id))?;
+            }
+        }
+    }
+    tx.commit()?;
+    Ok(rota_id)
+}
+
+// Ids of the members of `group_id`; `NotFound` for an unknown or archived group.
+fn group_member_ids(conn: &Connection, group_id: i32) -> DbResult<HashSet<i32>> {
+    let exists: bool = conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM `Group` WHERE `id` = ?1 AND `archived_at` IS NULL);",
+        (group_id,),
+        |row| row.get(0),
+    )?;
+    if !exists {
+        return Err(DbError::NotFound { entity: "group", id: group_id });
+    }
+    let mut stmt = conn.prepare_cached("SELECT `person_id` FROM `GroupMembers` WHERE `group_id` = ?1;")?;
+    let ids = stmt
+        .query_map((group_id,), |row| row.get(0))?
+        .collect::<rusqlite::Result<HashSet<i32>>>()?;
+    Ok(ids)
+}
+
+/// Rotas of event `event_id`, latest night first.
+pub fn get_rotas(conn: &Connection, event_id: i32) -> DbResult<Vec<Rota>> {
+    let mut stmt = conn.prepare_cached("SELECT `id` FROM `Rota` WHERE `event_id` = ?1 ORDER BY `starts_at` DESC, `id` DESC;")?;
+    let ids = stmt
+        .query_map((event_id,), |row| row.get(0))?
+        .collect::<rusqlite::Result<Vec<i32>>>()?;
+    ids.into_iter().map(|id| get_rota(conn, id)).collect()
+}
+
+pub fn get_rota(conn: &Connection, rota_id: i32) -> DbResult<Rota> {
+    let header = conn
+        .query_row(
+            "SELECT `event_id`, `starts_at`, `ends_at`, `shift_minutes`, `per_shift`, `min_methodology`, `min_rank_level`,
+                `group_id`, `no_back_to_back`, `created_at`
+             FROM `Rota` WHERE `id` = ?1;",
+            (rota_id,),
+            |row| {
+                let starts_at: String = row.get(1)?;
+                let ends_at: String = row.get(2)?;
+                let created_at: String = row.get(9)?;
+                Ok((
+                    row.get::<_, i32>(0)?,
+                    RotaRequest {
+                        starts_at: parse_db_datetime(&starts_at)?,
+                        ends_at: parse_db_datetime(&ends_at)?,
+                        shift_minutes: row.get(3)?,
+                        per_shift: row.get(4)?,
+                        rules: RotaRules {
+                            min_methodology: row.get(5)?,
+                            min_rank_level: row.get(6)?,
+                            group_id: row.get(7)?,
+                            no_back_to_back: row.get(8)?,
+                        },
+                    },
+                    parse_db_datetime(&created_at)?,
+                ))
+            },
+        )
+        .optional()?;
+    let Some((event_id, request, created_at)) = header else {
+        return Err(DbError::NotFound { entity: "rota", id: rota_id });
+    };
+
+    let mut shifts = empty_shifts(&request)?;
+    let mut stmt = conn.prepare_cached(&format!(
+        "SELECT `s`.`shift`, `s`.`slot`, {PERSON_LIST_COLUMNS}
+         FROM `RotaSlot` `s` JOIN `Person` `p` ON `p`.`id` = `s`.`person_id`
+         WHERE `s`.`rota_id` = ?1;"
+    ))?;
+    let guards = stmt
+        .query_map((rota_id,), |row| Ok((row.get::<_, usize>(0)?, row.get::<_, usize>(1)?, person_from_row(row, 2)?)))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    for (shift, slot, person) in guards {
+        if let Some(guard) = shifts.get_mut(shift).and_then(|s| s.guards.get_mut(slot)) {
+            *guard = Some(person);
+        }
+    }
+
+    Ok(Rota {
+        id: rota_id,
+        event_id,
+        request,
+        created_at,
+        shifts,
+    })
+}
+
+/// The shifts of `request` with every slot empty.
+pub(super) fn empty_shifts(request: &RotaRequest) -> DbResult<Vec<RotaShift>> {
+    Ok(shift_windows(request)?
+        .into_iter()
+        .map(|(starts_at, ends_at)| RotaShift {
+            starts_at,
+            ends_at,
+            guards: vec![None; request.per_shift as usize],
+        })
+        .collect())
+}
+
+/// Exchanges the persons in two slots of rota `rota_id` (`(shift, slot)` each). `Duplicate`
+/// if that puts someone twice on one shift.
+pub fn swap_rota_slots(conn: &Connection, rota_id: i32, a: (u32, u32), b: (u32, u32)) -> DbResult<()> {
+    let tx = conn.unchecked_transaction()?;
+    let person_a = slot_person(&tx, rota_id, a)?;
+    let person_b = slot_person(&tx, rota_id, b)?;
+    // Through an empty slot, so the one-person-per-shift index never sees both at once.
+    write_slot(&tx, rota_id, a, None)?;
+    write_slot(&tx, rota_id, b, person_a)?;
+    write_slot(&tx, rota_id, a, person_b)?;
+    tx.commit()?;
+    Ok(())
+}
+
+/// Puts `person_id` (nobody when `None`) in a slot of rota `rota_id`. `NotFound` for an
+/// unknown or archived person, `Duplicate` if they are already on the shift.
+pub fn set_rota_slot(conn: &Connection, rota_id: i32, at: (u32, u32), person_id: Option<i32>) -> DbResult<()> {
+    let tx = conn.unchecked_transaction()?;
+    slot_person(&tx, rota_id, at)?;
+    if let Some(person_id) = person_id {
+        let active: bool = tx.query_row(
+            "SELECT EXISTS(SELECT 1 FROM `Person` WHERE `id` = ?1 AND `archived_at` IS NULL);",
+            (person_id,),
+            |row| row.get(0),
+        )?;
+        if !active {
+            return Err(DbError::NotFound { entity: "person", id: person_id });
+        }
+    }
+    write_slot(&tx, rota_id, at, person_id)?;
+    tx.commit()?;
+    Ok(())
+}
+
+// `NotFound` for an unknown rota or slot.
+fn slot_person(conn: &Connection, rota_id: i32, (shift, slot): (u32, u32)) -> DbResult<Option<i32>> {
+    let person_id: Option<Option<i32>> = conn
+        .query_row(
+            "SELECT `person_id` FROM `RotaSlot` WHERE `rota_id` = ?1 AND `shift` = ?2 AND `slot` = ?3;",
+            (rota_id, shift, slot),
+            |row| row.get(0),
+        )
+        .optional()?;
+    person_id.ok_or(DbError::NotFound { entity: "rota slot", id: rota_id })
+}
+
+fn write_slot(conn: &Connection, rota_id: i32, (shift, slot): (u32, u32), person_id: Option<i32>) -> DbResult<()> {
+    let updated = conn.execute(
+        "UPDATE `RotaSlot` SET `person_id` = ?4 WHERE `rota_id` = ?1 AND `shift` = ?2 AND `slot` = ?3;",
+        (rota_id, shift, slot, person_id),
+    )?;
+    expect_row(updated, "rota slot", rota_id)
+}
+
+pub fn delete_rota(conn: &Connection, rota_id: i32) -> DbResult<()> {
+    let deleted = conn.execute("DELETE FROM `Rota` WHERE `id` = ?1;", (rota_id,))?;
+    expect_row(deleted, "rota", rota_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::db_operations::RankLevel;
+
+    fn person(id: i32, methodology: Methodology, rank_level: RankLevel) -> Person {
+        let mut person = Person::new(format!("P{id}"), "Nowak".to_string(), rank_level, methodology);
+        person.id = id;
+        person
+    }
+
+    fn scouts(ids: impl IntoIterator<Item = i32>) -> Vec<Person> {
+        ids.into_iter().map(|id| person(id, Methodology::Scout, RankLevel::RankNone)).collect()
+    }
+
+    // `shifts` hour-long shifts from 22:00 UTC.
+    fn request(shifts: i64, per_shift: u32, rules: RotaRules) -> RotaRequest {
+        let starts_at = Utc.with_ymd_and_hms(2026, 7, 17, 22, 0, 0).unwrap();
+        RotaRequest {
+            starts_at,
+            ends_at: starts_at + TimeDelta::hours(shifts),
+            shift_minutes: 60,
+            per_shift,
+            rules,
+        }
+    }
+
+    fn anyone_in_a_row() -> RotaRules {
+        RotaRules {
+            no_back_to_back: false,
+            ..Default::default()
+        }
+    }
+
+    // A stored rota with the given guards (one slot per shift), starting `days` before `request`.
+    fn stored(guards: &[i32], days: i64, participants: &[Person]) -> Rota {
+        let request = request(guards.len() as i64, 1, RotaRules::default());
+        let shifts = shift_windows(&request)
+            .unwrap()
+            .into_iter()
+            .zip(guards)
+            .map(|((starts_at, ends_at), id)| RotaShift {
+                starts_at: starts_at - TimeDelta::days(days),
+                ends_at: ends_at - TimeDelta::days(days),
+                guards: vec![participants.iter().find(|p| p.id == *id).cloned()],
+            })
+            .collect();
+        Rota {
+            id: 1,
+            event_id: 1,
+            created_at: request.starts_at,
+            request,
+            shifts,
+        }
+    }
+
+    fn ids(plan: &SlotIds) -> Vec<Vec<i32>> {
+        plan.iter().map(|shift| shift.iter().map(|g| g.unwrap_or(0)).collect()).collect()
+    }
+
+    #[test]
+    fn duties_are_shared_out_evenly() {
+        let participants = scouts([3, 1, 2]);
+        let plan = plan_rota(&request(6, 1, anyone_in_a_row()), &participants, None, &[]).unwrap();
+        assert_eq!(ids(&plan), [[1], [2], [3], [1], [2], [3]]);
+
+        let plan = plan_rota(&request(3, 2, anyone_in_a_row()), &participants, None, &[]).unwrap();
+        assert_eq!(ids(&plan), [[1, 2], [3, 1], [2, 3]]);
+    }
+
+    #[test]
+    fn earlier_rotas_count() {
+        let participants = scouts([1, 2, 3]);
+        // 1 has two duties, 2 and 3 one each, 3's the longest ago.
+        let existing = [stored(&[1, 2], 1, &participants), stored(&[1, 3], 2, &participants)];
+        let plan = plan_rota(&request(4, 1, anyone_in_a_row()), &participants, None, &existing).unwrap();
+        assert_eq!(ids(&plan), [[3], [2], [1], [3]]);
+    }
+
+    #[test]
+    fn no_back_to_back_leaves_slots_empty_rather_than_repeat() {
+        let participants = scouts([1, 2, 3]);
+        let plan = plan_rota(&request(3, 2, RotaRules::default()), &participants, None, &[]).unwrap();
+        assert_eq!(ids(&plan), [[1, 2], [3, 0], [1, 2]]);
+
+        let plan = plan_rota(&request(3, 2, anyone_in_a_row()), &participants, None, &[]).unwrap();
+        assert!(plan.iter().flatten().all(Option::is_some));
+    }
+
+    #[test]
+    fn only_eligible_participants_stand_guard() {
+        let participants = [
+            person(1, Methodology::Cub, RankLevel::RankThirdF),
+            person(2, Methodology::Scout, RankLevel::RankFirstM),
+            person(3, Methodology::Rover, RankLevel::RankSixth),
+            person(4, Methodology::VentureScout, RankLevel::RankThirdM),
+        ];
+        let guards = |rules: RotaRules, members: Option<&HashSet<i32>>| {
+            let rules = RotaRules {
+                no_back_to_back: false,
+                ..rules
+            };
+            let plan = plan_rota(&request(4, 1, rules), &participants, members, &[]).unwrap();
+            let mut guards: Vec<i32> = plan.into_iter().flatten().flatten().collect();
+            guards.sort_unstable();
+            guards.dedup();
+            guards
+        };
+
+        let scouts_up = RotaRules {
+            min_methodology: Some(Methodology::Scout),
+            ..Default::default()
+        };
+        assert_eq!(guards(scouts_up, None), [2, 3, 4]);
+        let third_rank_up = RotaRules {
+            min_rank_level: 3,
+            ..Default::default()
+        };
+        assert_eq!(guards(third_rank_up.clone(), None), [1, 3, 4]);
+        let both = RotaRules {
+            min_methodology: Some(Methodology::Scout),
+            ..third_rank_up
+        };
+        assert_eq!(guards(both, None), [3, 4]);
+        let members = HashSet::from([1, 2, 9]);
+        assert_eq!(guards(RotaRules::default(), Some(&members)), [1, 2]);
+
+        let nobody = RotaRules {
+            min_rank_level: 6,
+            ..Default::default()
+        };
+        assert!(matches!(
+            plan_rota(&request(2, 1, nobody), &participants, Some(&members), &[]),
+            Err(DbError::ConstraintViolation(_))
+        ));
+    }
+
+    #[test]
+    fn the_last_shift_ends_with_the_night() {
+        let mut night = request(3, 1, RotaRules::default());
+        night.shift_minutes = 100;
+        let windows = shift_windows(&night).unwrap();
+        assert_eq!(windows.len(), 2);
+        assert_eq!(windows[1], (night.starts_at + TimeDelta::minutes(100), night.ends_at));
+
+        night.shift_minutes = 1;
+        night.ends_at = night.starts_at + TimeDelta::minutes(MAX_SHIFTS as i64 + 1);
+        assert!(shift_windows(&night).is_err());
+        night.shift_minutes = 0;
+        assert!(shift_windows(&night).is_err());
+    }
+}
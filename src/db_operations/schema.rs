@@ -1,8 +1,104 @@
-use rusqlite::Connection;
+use std::ops::RangeInclusive;
 
-pub(super) fn ensure_schema(conn: &Connection) -> rusqlite::Result<()> {
+use rusqlite::{Connection, Transaction};
+
+use super::migrations::{self, Migration};
+use super::{DbResult, GroupKind, Methodology};
+
+/// Ordered schema history. Append new steps at the end; never edit a released one.
+pub(super) const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "initial schema",
+        up: v1_initial,
+    },
+    Migration {
+        version: 2,
+        description: "batch id for bulk presence changes",
+        up: v2_log_batch_id,
+    },
+    Migration {
+        version: 3,
+        description: "archive flag for persons and groups",
+        up: v3_archived_at,
+    },
+    Migration {
+        version: 4,
+        description: "person snapshot on log rows",
+        up: v4_log_snapshot,
+    },
+    Migration {
+        version: 5,
+        description: "group presence events",
+        up: v5_log_parent_id,
+    },
+    Migration {
+        version: 6,
+        description: "log operator and paging index",
+        up: v6_log_operator,
+    },
+    Migration {
+        version: 7,
+        description: "events with per-event presence",
+        up: v7_events,
+    },
+    Migration {
+        version: 8,
+        description: "group kinds",
+        up: v8_group_kind,
+    },
+    Migration {
+        version: 9,
+        description: "person search index",
+        up: v9_person_search,
+    },
+    Migration {
+        version: 10,
+        description: "person profile",
+        up: v10_person_profile,
+    },
+    Migration {
+        version: 11,
+        description: "data retention",
+        up: v11_retention,
+    },
+    Migration {
+        version: 12,
+        description: "leave passes",
+        up: v12_leave_pass,
+    },
+    Migration {
+        version: 13,
+        description: "roll calls",
+        up: v13_roll_call,
+    },
+    Migration {
+        version: 14,
+        description: "sentry rotas",
+        up: v14_rota,
+    },
+    Migration {
+        version: 15,
+        description: "sentry journal",
+        up: v15_journal,
+    },
+];
+
+/// Event created by the events migration; existing presence and logs were moved into it.
+pub(super) const DEFAULT_EVENT_ID: i32 = 1;
+
+/// Groups created by `seed_default_groups`. Before group kinds (v8) these ids were what
+/// made a group built in; only the steps written then still rely on them.
+const BUILT_IN_GROUP_IDS: RangeInclusive<i32> = 1..=5;
+
+pub(super) fn ensure_schema(conn: &mut Connection) -> DbResult<()> {
+    migrations::migrate(conn)?;
     conn.execute("PRAGMA foreign_keys = ON;", ())?;
+    Ok(())
+}
 
+// `IF NOT EXISTS` lets databases created before versioning adopt version 1 as-is.
+fn v1_initial(conn: &Transaction) -> rusqlite::Result<()> {
     conn.execute(
         "CREATE TABLE IF NOT EXISTS `Person`(
             `id` INTEGER PRIMARY KEY AUTOINCREMENT,
@@ -48,6 +144,418 @@ pub(super) fn ensure_schema(conn: &Connection) -> rusqlite::Result<()> {
     Ok(())
 }
 
+fn v2_log_batch_id(conn: &Transaction) -> rusqlite::Result<()> {
+    // Rows written before bulk check-in/out stay NULL: they were written one by one.
+    conn.execute("ALTER TABLE `Log` ADD COLUMN `batch_id` INTEGER;", ())?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS `Log_batch_id` ON `Log`(`batch_id`);",
+        (),
+    )?;
+    Ok(())
+}
+
+fn v3_archived_at(conn: &Transaction) -> rusqlite::Result<()> {
+    conn.execute("ALTER TABLE `Person` ADD COLUMN `archived_at` TEXT;", ())?;
+    conn.execute("ALTER TABLE `Group` ADD COLUMN `archived_at` TEXT;", ())?;
+    Ok(())
+}
+
+fn v4_log_snapshot(conn: &Transaction) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        "ALTER TABLE `Log` ADD COLUMN `name` TEXT;
+         ALTER TABLE `Log` ADD COLUMN `surname` TEXT;
+         ALTER TABLE `Log` ADD COLUMN `rank_level` INTEGER;
+         ALTER TABLE `Log` ADD COLUMN `methodology` INTEGER;
+         ALTER TABLE `Log` ADD COLUMN `groups` TEXT;",
+    )?;
+
+    // Best effort for existing rows: the current roster is the only record we have.
+    // Rows of already-deleted persons stay NULL.
+    conn.execute(
+        &format!(
+            "UPDATE `Log` SET
+                `name` = (SELECT `p`.`name` FROM `Person` `p` WHERE `p`.`id` = `Log`.`entity_id`),
+                `surname` = (SELECT `p`.`surname` FROM `Person` `p` WHERE `p`.`id` = `Log`.`entity_id`),
+                `rank_level` = (SELECT `p`.`rank_level` FROM `Person` `p` WHERE `p`.`id` = `Log`.`entity_id`),
+                `methodology` = (SELECT `p`.`methodology` FROM `Person` `p` WHERE `p`.`id` = `Log`.`entity_id`),
+                `groups` = ({})
+             WHERE `entity_type` = 0;",
+            person_groups_sql("`Log`.`entity_id`")
+        ),
+        (),
+    )?;
+    Ok(())
+}
+
+fn v5_log_parent_id(conn: &Transaction) -> rusqlite::Result<()> {
+    // Member rows of a group event point at the group row (`entity_type` = 1).
+    conn.execute(
+        "ALTER TABLE `Log` ADD COLUMN `parent_id` INTEGER REFERENCES `Log`(`id`) ON DELETE SET NULL;",
+        (),
+    )?;
+    conn.execute("CREATE INDEX IF NOT EXISTS `Log_parent_id` ON `Log`(`parent_id`);", ())?;
+    Ok(())
+}
+
+fn v6_log_operator(conn: &Transaction) -> rusqlite::Result<()> {
+    // Who pressed GET_IN/GET_OUT; NULL for older rows and when nobody was entered.
+    conn.execute("ALTER TABLE `Log` ADD COLUMN `operator` TEXT;", ())?;
+    // Keyset pagination walks (`timestamp`, `id`) backwards.
+    conn.execute("CREATE INDEX IF NOT EXISTS `Log_timestamp_id` ON `Log`(`timestamp`, `id`);", ())?;
+    Ok(())
+}
+
+fn v7_events(conn: &Transaction) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS `Event`(
+            `id` INTEGER PRIMARY KEY AUTOINCREMENT,
+            `name` TEXT NOT NULL,
+            `starts_on` TEXT,
+            `ends_on` TEXT,
+            `location` TEXT NOT NULL DEFAULT ''
+        );
+        CREATE TABLE IF NOT EXISTS `EventParticipants`(
+            `event_id` INTEGER NOT NULL REFERENCES `Event`(`id`) ON DELETE CASCADE,
+            `person_id` INTEGER NOT NULL REFERENCES `Person`(`id`) ON DELETE CASCADE,
+            `is_inside` BOOLEAN NOT NULL DEFAULT 0,
+            PRIMARY KEY (`event_id`, `person_id`)
+        );
+        CREATE TABLE IF NOT EXISTS `AppSetting`(
+            `key` TEXT PRIMARY KEY,
+            `value` TEXT NOT NULL
+        );
+        ALTER TABLE `Log` ADD COLUMN `event_id` INTEGER REFERENCES `Event`(`id`);
+        CREATE INDEX IF NOT EXISTS `Log_event_timestamp_id` ON `Log`(`event_id`, `timestamp`, `id`);",
+    )?;
+
+    // Everything recorded so far happened in one implicit event; make it explicit.
+    conn.execute(
+        "INSERT INTO `Event`(`id`, `name`) VALUES(?1, 'Camp');",
+        (DEFAULT_EVENT_ID,),
+    )?;
+    conn.execute(
+        "INSERT INTO `EventParticipants`(`event_id`, `person_id`, `is_inside`) SELECT ?1, `id`, `is_inside` FROM `Person`;",
+        (DEFAULT_EVENT_ID,),
+    )?;
+    conn.execute("UPDATE `Log` SET `event_id` = ?1;", (DEFAULT_EVENT_ID,))?;
+    conn.execute(
+        "INSERT INTO `AppSetting`(`key`, `value`) VALUES('active_event_id', ?1);",
+        (DEFAULT_EVENT_ID.to_string(),),
+    )?;
+
+    // Presence now lives in `EventParticipants`.
+    conn.execute("ALTER TABLE `Person` DROP COLUMN `is_inside`;", ())?;
+    Ok(())
+}
+
+fn v8_group_kind(conn: &Transaction) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        "ALTER TABLE `Group` ADD COLUMN `kind` INTEGER NOT NULL DEFAULT 2 CHECK(`kind` IN (0, 1, 2));
+         ALTER TABLE `Group` ADD COLUMN `methodology` INTEGER;",
+    )?;
+
+    // Before kinds existed the built-in groups were only known by the ids `seed_default_groups`
+    // gave them. This is the last place those ids are used.
+    conn.execute("UPDATE `Group` SET `kind` = 0 WHERE `id` = 1;", ())?;
+    conn.execute(
+        "UPDATE `Group` SET `kind` = 1, `methodology` = `id` - 2 WHERE `id` BETWEEN 2 AND 5;",
+        (),
+    )?;
+
+    // One all-members group, one group per methodology.
+    conn.execute_batch(
+        "CREATE UNIQUE INDEX IF NOT EXISTS `Group_all_members` ON `Group`(`kind`) WHERE `kind` = 0;
+         CREATE UNIQUE INDEX IF NOT EXISTS `Group_methodology` ON `Group`(`methodology`) WHERE `kind` = 1;",
+    )?;
+
+    ensure_built_in_groups(conn)?;
+    sync_built_in_memberships(conn, None)?;
+    Ok(())
+}
+
+fn v9_person_search(conn: &Transaction) -> rusqlite::Result<()> {
+    // `rowid` is the person id. unicode61 folds case and most diacritics, but 'ł' has no
+    // decomposition, so it is replaced before indexing (`queries::search_terms` does the same
+    // to the query).
+    conn.execute_batch(
+        "CREATE VIRTUAL TABLE IF NOT EXISTS `PersonSearch` USING fts5(
+            `name`, `surname`, tokenize = 'unicode61 remove_diacritics 2'
+        );
+        CREATE TRIGGER IF NOT EXISTS `Person_search_insert` AFTER INSERT ON `Person` BEGIN
+            INSERT INTO `PersonSearch`(`rowid`, `name`, `surname`) VALUES(
+                new.`id`,
+                replace(replace(new.`name`, 'ł', 'l'), 'Ł', 'L'),
+                replace(replace(new.`surname`, 'ł', 'l'), 'Ł', 'L'));
+        END;
+        CREATE TRIGGER IF NOT EXISTS `Person_search_update` AFTER UPDATE OF `name`, `surname` ON `Person` BEGIN
+            UPDATE `PersonSearch` SET
+                `name` = replace(replace(new.`name`, 'ł', 'l'), 'Ł', 'L'),
+                `surname` = replace(replace(new.`surname`, 'ł', 'l'), 'Ł', 'L')
+            WHERE `rowid` = new.`id`;
+        END;
+        CREATE TRIGGER IF NOT EXISTS `Person_search_delete` AFTER DELETE ON `Person` BEGIN
+            DELETE FROM `PersonSearch` WHERE `rowid` = old.`id`;
+        END;
+        INSERT INTO `PersonSearch`(`rowid`, `name`, `surname`)
+            SELECT `id`, replace(replace(`name`, 'ł', 'l'), 'Ł', 'L'), replace(replace(`surname`, 'ł', 'l'), 'Ł', 'L')
+            FROM `Person`;",
+    )?;
+    Ok(())
+}
+
+fn v10_person_profile(conn: &Transaction) -> rusqlite::Result<()> {
+    // `guardians` is a JSON array of `Guardian`; dates are "YYYY-MM-DD".
+    conn.execute_batch(
+        "ALTER TABLE `Person` ADD COLUMN `nickname` TEXT NOT NULL DEFAULT '';
+         ALTER TABLE `Person` ADD COLUMN `patrol` TEXT NOT NULL DEFAULT '';
+         ALTER TABLE `Person` ADD COLUMN `date_of_birth` TEXT;
+         ALTER TABLE `Person` ADD COLUMN `address` TEXT NOT NULL DEFAULT '';
+         ALTER TABLE `Person` ADD COLUMN `guardians` TEXT NOT NULL DEFAULT '[]';
+         ALTER TABLE `Person` ADD COLUMN `allergies` TEXT NOT NULL DEFAULT '';
+         ALTER TABLE `Person` ADD COLUMN `diet` TEXT NOT NULL DEFAULT '';
+         ALTER TABLE `Person` ADD COLUMN `medical_notes` TEXT NOT NULL DEFAULT '';
+         ALTER TABLE `Person` ADD COLUMN `note` TEXT NOT NULL DEFAULT '';",
+    )?;
+
+    // Nicknames are searchable too. FTS5 tables can't gain columns, so the index from v9
+    // is rebuilt with the same 'ł' handling.
+    conn.execute_batch(
+        "DROP TRIGGER `Person_search_insert`;
+        DROP TRIGGER `Person_search_update`;
+        DROP TRIGGER `Person_search_delete`;
+        DROP TABLE `PersonSearch`;
+        CREATE VIRTUAL TABLE `PersonSearch` USING fts5(
+            `name`, `surname`, `nickname`, tokenize = 'unicode61 remove_diacritics 2'
+        );
+        CREATE TRIGGER `Person_search_insert` AFTER INSERT ON `Person` BEGIN
+            INSERT INTO `PersonSearch`(`rowid`, `name`, `surname`, `nickname`) VALUES(
+                new.`id`,
+                replace(replace(new.`name`, 'ł', 'l'), 'Ł', 'L'),
+                replace(replace(new.`surname`, 'ł', 'l'), 'Ł', 'L'),
+                replace(replace(new.`nickname`, 'ł', 'l'), 'Ł', 'L'));
+        END;
+        CREATE TRIGGER `Person_search_update` AFTER UPDATE OF `name`, `surname`, `nickname` ON `Person` BEGIN
+            UPDATE `PersonSearch` SET
+                `name` = replace(replace(new.`name`, 'ł', 'l'), 'Ł', 'L'),
+                `surname` = replace(replace(new.`surname`, 'ł', 'l'), 'Ł', 'L'),
+                `nickname` = replace(replace(new.`nickname`, 'ł', 'l'), 'Ł', 'L')
+            WHERE `rowid` = new.`id`;
+        END;
+        CREATE TRIGGER `Person_search_delete` AFTER DELETE ON `Person` BEGIN
+            DELETE FROM `PersonSearch` WHERE `rowid` = old.`id`;
+        END;
+        INSERT INTO `PersonSearch`(`rowid`, `name`, `surname`, `nickname`)
+            SELECT `id`, replace(replace(`name`, 'ł', 'l'), 'Ł', 'L'), replace(replace(`surname`, 'ł', 'l'), 'Ł', 'L'), ''
+            FROM `Person`;",
+    )?;
+    Ok(())
+}
+
+fn v11_retention(conn: &Transaction) -> rusqlite::Result<()> {
+    // NULL `retention_days` = use the camp-wide `AppSetting` value (see `privacy.rs`).
+    conn.execute_batch(
+        "ALTER TABLE `Event` ADD COLUMN `retention_days` INTEGER CHECK(`retention_days` >= 0);
+         ALTER TABLE `Person` ADD COLUMN `anonymized_at` TEXT;",
+    )?;
+    Ok(())
+}
+
+fn v12_leave_pass(conn: &Transaction) -> rusqlite::Result<()> {
+    // Event, person and departure time come from the OUT row (`log_id`); the pass is open
+    // until the IN row that brought the person back is set (`return_log_id`).
+    conn.execute_batch(
+        "CREATE TABLE `LeavePass`(
+            `id` INTEGER PRIMARY KEY AUTOINCREMENT,
+            `log_id` INTEGER NOT NULL UNIQUE REFERENCES `Log`(`id`) ON DELETE CASCADE,
+            `destination` TEXT NOT NULL DEFAULT '',
+            `reason` INTEGER NOT NULL DEFAULT 0,
+            `companion` TEXT NOT NULL DEFAULT '',
+            `expected_return` TEXT,
+            `return_log_id` INTEGER REFERENCES `Log`(`id`) ON DELETE SET NULL
+        );
+        CREATE INDEX `LeavePass_return_log_id` ON `LeavePass`(`return_log_id`);",
+    )?;
+    Ok(())
+}
+
+fn v13_roll_call(conn: &Transaction) -> rusqlite::Result<()> {
+    // `expected` is the participant's stored presence when the roll call started; `present`
+    // is whether they were ticked off.
+    conn.execute_batch(
+        "CREATE TABLE `RollCall`(
+            `id` INTEGER PRIMARY KEY AUTOINCREMENT,
+            `event_id` INTEGER NOT NULL REFERENCES `Event`(`id`) ON DELETE CASCADE,
+            `started_at` TEXT NOT NULL,
+            `finished_at` TEXT,
+            `operator` TEXT
+        );
+        CREATE INDEX `RollCall_event_id` ON `RollCall`(`event_id`);
+        CREATE TABLE `RollCallEntry`(
+            `roll_call_id` INTEGER NOT NULL REFERENCES `RollCall`(`id`) ON DELETE CASCADE,
+            `person_id` INTEGER NOT NULL REFERENCES `Person`(`id`) ON DELETE CASCADE,
+            `expected` INTEGER NOT NULL,
+            `present` BOOLEAN NOT NULL DEFAULT 0,
+            `ticked_at` TEXT,
+            PRIMARY KEY (`roll_call_id`, `person_id`)
+        );",
+    )?;
+    Ok(())
+}
+
+fn v14_rota(conn: &Transaction) -> rusqlite::Result<()> {
+    // Shift times follow from `starts_at` and `shift_minutes` (the last one ends at `ends_at`).
+    // A purged person leaves their slot empty.
+    conn.execute_batch(
+        "CREATE TABLE `Rota`(
+            `id` INTEGER PRIMARY KEY AUTOINCREMENT,
+            `event_id` INTEGER NOT NULL REFERENCES `Event`(`id`) ON DELETE CASCADE,
+            `starts_at` TEXT NOT NULL,
+            `ends_at` TEXT NOT NULL,
+            `shift_minutes` INTEGER NOT NULL CHECK(`shift_minutes` > 0),
+            `per_shift` INTEGER NOT NULL CHECK(`per_shift` > 0),
+            `min_methodology` INTEGER,
+            `min_rank_level` INTEGER NOT NULL DEFAULT 0,
+            `group_id` INTEGER REFERENCES `Group`(`id`) ON DELETE SET NULL,
+            `no_back_to_back` BOOLEAN NOT NULL DEFAULT 1,
+            `created_at` TEXT NOT NULL
+        );
+        CREATE INDEX `Rota_event_id` ON `Rota`(`event_id`);
+        CREATE TABLE `RotaSlot`(
+            `rota_id` INTEGER NOT NULL REFERENCES `Rota`(`id`) ON DELETE CASCADE,
+            `shift` INTEGER NOT NULL,
+            `slot` INTEGER NOT NULL,
+            `person_id` INTEGER REFERENCES `Person`(`id`) ON DELETE SET NULL,
+            PRIMARY KEY (`rota_id`, `shift`, `slot`)
+        );
+        CREATE UNIQUE INDEX `RotaSlot_person` ON `RotaSlot`(`rota_id`, `shift`, `person_id`);
+        CREATE INDEX `RotaSlot_person_id` ON `RotaSlot`(`person_id`);",
+    )?;
+    Ok(())
+}
+
+fn v15_journal(conn: &Transaction) -> rusqlite::Result<()> {
+    // Visits belong to a sentry shift; an incident to the shift in progress when it was
+    // recorded, if any. Checkpoints are per event and visits keep them from being deleted.
+    conn.execute_batch(
+        "CREATE TABLE `SentryShift`(
+            `id` INTEGER PRIMARY KEY AUTOINCREMENT,
+            `event_id` INTEGER NOT NULL REFERENCES `Event`(`id`) ON DELETE CASCADE,
+            `started_at` TEXT NOT NULL,
+            `ended_at` TEXT,
+            `guards` TEXT NOT NULL DEFAULT '',
+            `operator` TEXT
+        );
+        CREATE INDEX `SentryShift_event_id` ON `SentryShift`(`event_id`, `started_at`);
+        CREATE TABLE `Checkpoint`(
+            `id` INTEGER PRIMARY KEY AUTOINCREMENT,
+            `event_id` INTEGER NOT NULL REFERENCES `Event`(`id`) ON DELETE CASCADE,
+            `name` TEXT NOT NULL,
+            UNIQUE(`event_id`, `name`)
+        );
+        CREATE TABLE `CheckpointVisit`(
+            `id` INTEGER PRIMARY KEY AUTOINCREMENT,
+            `shift_id` INTEGER NOT NULL REFERENCES `SentryShift`(`id`) ON DELETE CASCADE,
+            `checkpoint_id` INTEGER NOT NULL REFERENCES `Checkpoint`(`id`),
+            `visited_at` TEXT NOT NULL
+        );
+        CREATE INDEX `CheckpointVisit_shift_id` ON `CheckpointVisit`(`shift_id`);
+        CREATE INDEX `CheckpointVisit_checkpoint_id` ON `CheckpointVisit`(`checkpoint_id`);
+        CREATE TABLE `Incident`(
+            `id` INTEGER PRIMARY KEY AUTOINCREMENT,
+            `event_id` INTEGER NOT NULL REFERENCES `Event`(`id`) ON DELETE CASCADE,
+            `shift_id` INTEGER REFERENCES `SentryShift`(`id`) ON DELETE SET NULL,
+            `occurred_at` TEXT NOT NULL,
+            `severity` INTEGER NOT NULL DEFAULT 0,
+            `description` TEXT NOT NULL,
+            `operator` TEXT
+        );
+        CREATE INDEX `Incident_event_id` ON `Incident`(`event_id`, `occurred_at`);
+        CREATE INDEX `Incident_shift_id` ON `Incident`(`shift_id`);
+        CREATE TABLE `IncidentPerson`(
+            `incident_id` INTEGER NOT NULL REFERENCES `Incident`(`id`) ON DELETE CASCADE,
+            `person_id` INTEGER NOT NULL REFERENCES `Person`(`id`) ON DELETE CASCADE,
+            PRIMARY KEY (`incident_id`, `person_id`)
+        );
+        CREATE INDEX `IncidentPerson_person_id` ON `IncidentPerson`(`person_id`);
+        CREATE TABLE `IncidentGroup`(
+            `incident_id` INTEGER NOT NULL REFERENCES `Incident`(`id`) ON DELETE CASCADE,
+            `group_id` INTEGER NOT NULL REFERENCES `Group`(`id`) ON DELETE CASCADE,
+            PRIMARY KEY (`incident_id`, `group_id`)
+        );
+        CREATE INDEX `IncidentGroup_group_id` ON `IncidentGroup`(`group_id`);",
+    )?;
+    Ok(())
+}
+
+/// Scalar subquery: newline-separated names of the user groups `person_id_expr` belongs to.
+/// Built-in groups are left out: "Camp" holds everyone and methodology has its own column.
+pub(super) fn user_groups_sql(person_id_expr: &str) -> String {
+    format!(
+        "SELECT group_concat(`g`.`name`, char(10) ORDER BY `g`.`id`)
+         FROM `GroupMembers` `gm` JOIN `Group` `g` ON `g`.`id` = `gm`.`group_id`
+         WHERE `gm`.`person_id` = {person_id_expr} AND `g`.`kind` = {}",
+        GroupKind::User as i32
+    )
+}
+
+/// `user_groups_sql` as it was before group kinds, for `v4_log_snapshot`. Part of a
+/// released migration step: don't change it.
+fn person_groups_sql(person_id_expr: &str) -> String {
+    format!(
+        "SELECT group_concat(`g`.`name`, char(10) ORDER BY `g`.`id`)
+         FROM `GroupMembers` `gm` JOIN `Group` `g` ON `g`.`id` = `gm`.`group_id`
+         WHERE `gm`.`person_id` = {person_id_expr} AND `g`.`id` NOT BETWEEN {} AND {}",
+        BUILT_IN_GROUP_IDS.start(),
+        BUILT_IN_GROUP_IDS.end()
+    )
+}
+
+/// Puts `person_id` (every person when `None`) into the all-members group and exactly the
+/// methodology group matching `Person.methodology`. User groups are left alone.
+pub(super) fn sync_built_in_memberships(conn: &Connection, person_id: Option<i32>) -> rusqlite::Result<()> {
+    conn.execute(
+        "DELETE FROM `GroupMembers`
+         WHERE (?1 IS NULL OR `person_id` = ?1)
+           AND `group_id` IN (SELECT `id` FROM `Group` WHERE `kind` = 1)
+           AND NOT EXISTS (
+               SELECT 1 FROM `Group` `g` JOIN `Person` `p` ON `p`.`methodology` = `g`.`methodology`
+               WHERE `g`.`id` = `GroupMembers`.`group_id` AND `p`.`id` = `GroupMembers`.`person_id`);",
+        (person_id,),
+    )?;
+    conn.execute(
+        "INSERT OR IGNORE INTO `GroupMembers`(`group_id`, `person_id`)
+         SELECT `g`.`id`, `p`.`id` FROM `Person` `p`
+         JOIN `Group` `g` ON `g`.`kind` = 0 OR (`g`.`kind` = 1 AND `g`.`methodology` = `p`.`methodology`)
+         WHERE ?1 IS NULL OR `p`.`id` = ?1;",
+        (person_id,),
+    )?;
+    Ok(())
+}
+
+// Recreates any built-in group missing from an old database, so every person has one to join.
+pub(super) fn ensure_built_in_groups(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO `Group`(`name`, `kind`) SELECT 'Camp', 0 WHERE NOT EXISTS (SELECT 1 FROM `Group` WHERE `kind` = 0);",
+        (),
+    )?;
+    for (methodology, name) in METHODOLOGY_GROUP_NAMES {
+        conn.execute(
+            "INSERT INTO `Group`(`name`, `kind`, `methodology`) SELECT ?2, 1, ?1
+             WHERE NOT EXISTS (SELECT 1 FROM `Group` WHERE `kind` = 1 AND `methodology` = ?1);",
+            (methodology, name),
+        )?;
+    }
+    Ok(())
+}
+
+/// Default names of the methodology groups.
+pub(super) const METHODOLOGY_GROUP_NAMES: [(Methodology, &str); 4] = [
+    (Methodology::Cub, "Cub"),
+    (Methodology::Scout, "Scout"),
+    (Methodology::VentureScout, "Venture Scout"),
+    (Methodology::Rover, "Rover"),
+];
+
 fn seed_default_groups(conn: &Connection) -> rusqlite::Result<()> {
     let count: i32 = conn.query_row("SELECT COUNT(`id`) FROM `Group`;", [], |row| row.get(0))?;
 
@@ -0,0 +1,46 @@
+//! Helpers shared by the `db_operations` unit tests.
+
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::{fs, process};
+
+use rusqlite::Connection;
+
+use super::schema::ensure_schema;
+use super::{Methodology, Person, RankLevel};
+
+/// A fresh directory under the system temp dir, removed with everything in it on drop.
+pub(super) struct TempDir(PathBuf);
+
+impl TempDir {
+    pub(super) fn new(name: &str) -> Self {
+        static NEXT: AtomicU32 = AtomicU32::new(0);
+        let n = NEXT.fetch_add(1, Ordering::Relaxed);
+        let path = std::env::temp_dir().join(format!("ewartownik-{name}-{}-{n}", process::id()));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).expect("create temp dir");
+        TempDir(path)
+    }
+
+    pub(super) fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// A migrated in-memory database, with its one implicit event (id 1) active.
+pub(crate) fn memory_db() -> Connection {
+    let mut conn = Connection::open_in_memory().expect("open in-memory database");
+    ensure_schema(&mut conn).expect("migrate");
+    conn
+}
+
+/// A new roster entry without a rank.
+pub(crate) fn person(name: &str, surname: &str, methodology: Methodology) -> Person {
+    Person::new(name.to_string(), surname.to_string(), RankLevel::RankNone, methodology)
+}
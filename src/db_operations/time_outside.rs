@@ -0,0 +1,287 @@
+//! Time spent outside, from pairs of OUT and IN rows of the `Log`.
+//!
+//! Each person's rows of an event are walked oldest first (timestamp, then id, like
+//! `presence.rs`). An OUT starts an outing and the next IN ends it. Rows that don't fit are
+//! counted as unmatched and otherwise ignored: an OUT while already out (the outing keeps
+//! its first time) and an IN while already in, except for a first IN, which is the arrival.
+//! An outing without an IN yet runs until the `until` the caller passes.
+//!
+//! Days are local days; an outing over midnight counts on both, its leave on the first.
+
+use chrono::{DateTime, Days, Local, NaiveDate, TimeDelta, TimeZone, Utc};
+use rusqlite::Connection;
+
+use super::queries::{log_from_row, LOG_COLUMNS};
+use super::types::EntityType;
+use super::{format_db_datetime, DbResult, IsInside, Log, LogSnapshot};
+
+/// One stretch of time outside.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Outing {
+    pub left: DateTime<Utc>,
+    /// `None` while the person is still out.
+    pub returned: Option<DateTime<Utc>>,
+}
+
+/// Time outside on one local day.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DayOutside {
+    pub day: NaiveDate,
+    /// Outings started that day.
+    pub leaves: usize,
+    pub outside: TimeDelta,
+}
+
+/// Everything one person spent outside during an event.
+#[derive(Debug, Clone)]
+pub struct TimeOutside {
+    pub person_id: i32,
+    /// From the person's newest row, so purged persons keep their name; `None` for rows
+    /// written before snapshots existed.
+    pub snapshot: Option<LogSnapshot>,
+    /// Oldest first.
+    pub outings: Vec<Outing>,
+    /// Rows that didn't pair up, see the module docs.
+    pub unmatched: usize,
+    pub total: TimeDelta,
+    pub longest: TimeDelta,
+    /// Days with time outside, oldest first.
+    pub days: Vec<DayOutside>,
+}
+
+impl TimeOutside {
+    pub fn leaves(&self) -> usize {
+        self.outings.len()
+    }
+
+    /// Out at `until`.
+    pub fn is_outside(&self) -> bool {
+        self.outings.last().is_some_and(|o| o.returned.is_none())
+    }
+}
+
+/// Time outside of every person logged in event `event_id` up to `until`, by person id.
+pub fn time_outside(conn: &Connection, event_id: i32, until: DateTime<Utc>) -> DbResult<Vec<TimeOutside>> {
+    let mut stmt = conn.prepare_cached(&format!(
+        "SELECT {LOG_COLUMNS} FROM `Log` `l`
+         WHERE `l`.`entity_type` = ?1 AND `l`.`event_id` = ?2 AND `l`.`timestamp` <= ?3
+         ORDER BY `l`.`entity_id`, `l`.`timestamp`, `l`.`id`;"
+    ))?;
+    let logs = stmt
+        .query_map((EntityType::Person, event_id, format_db_datetime(&until)), |row| log_from_row(row, 0))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    Ok(summarize(&logs, until))
+}
+
+/// Pairs up `logs`, which must be one event's person rows up to `until`, ordered by person,
+/// then oldest first.
+pub(super) fn summarize(logs: &[Log], until: DateTime<Utc>) -> Vec<TimeOutside> {
+    logs.chunk_by(|a, b| a.entity_id == b.entity_id)
+        .map(|rows| summarize_person(rows, until))
+        .collect()
+}
+
+fn summarize_person(rows: &[Log], until: DateTime<Utc>) -> TimeOutside {
+    let mut outings: Vec<Outing> = Vec::new();
+    let mut unmatched = 0;
+    let mut out_since: Option<DateTime<Utc>> = None;
+
+    for (i, row) in rows.iter().enumerate() {
+        match (row.is_inside, out_since) {
+            (IsInside::Out, None) => out_since = Some(row.time),
+            (IsInside::In, Some(left)) => {
+                outings.push(Outing {
+                    left,
+                    returned: Some(row.time),
+                });
+                out_since = None;
+            }
+            (IsInside::In, None) if i == 0 => {}
+            _ => unmatched += 1,
+        }
+    }
+    if let Some(left) = out_since {
+        outings.push(Outing { left, returned: None });
+    }
+
+    let spans: Vec<(DateTime<Utc>, DateTime<Utc>)> =
+        outings.iter().map(|o| (o.left, o.returned.unwrap_or(until))).collect();
+
+    TimeOutside {
+        person_id: rows[0].entity_id,
+        snapshot: rows.iter().rev().find_map(|r| r.snapshot.clone()),
+        unmatched,
+        total: spans.iter().map(|(from, to)| *to - *from).sum(),
+        longest: spans.iter().map(|(from, to)| *to - *from).max().unwrap_or_default(),
+        days: split_by_day(&spans),
+        outings,
+    }
+}
+
+fn split_by_day(spans: &[(DateTime<Utc>, DateTime<Utc>)]) -> Vec<DayOutside> {
+    let mut days: Vec<DayOutside> = Vec::new();
+    for &(from, to) in spans {
+        let mut start = from;
+        let mut first = true;
+        loop {
+            let day = start.with_timezone(&Local).date_naive();
+            let end = next_midnight(day).map_or(to, |midnight| midnight.min(to));
+            let entry = match days.iter_mut().find(|d| d.day == day) {
+                Some(entry) => entry,
+                None => {
+                    days.push(DayOutside {
+                        day,
+                        leaves: 0,
+                        outside: TimeDelta::zero(),
+                    });
+                    days.last_mut().expect("just pushed")
+                }
+            };
+            entry.outside += end - start;
+            if first {
+                entry.leaves += 1;
+                first = false;
+            }
+            if end >= to {
+                break;
+            }
+            start = end;
+        }
+    }
+    days.sort_by_key(|d| d.day);
+    days
+}
+
+// Start of the local day after `day`.
+fn next_midnight(day: NaiveDate) -> Option<DateTime<Utc>> {
+    let next = day.checked_add_days(Days::new(1))?;
+    Local
+        .from_local_datetime(&next.and_time(chrono::NaiveTime::MIN))
+        .earliest()
+        .map(|t| t.with_timezone(&Utc))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDateTime;
+
+    use super::*;
+    use crate::db_operations::{Methodology, RankLevel};
+
+    // Local wall-clock time, like the days are counted in.
+    fn at(time: &str) -> DateTime<Utc> {
+        let time = NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M").unwrap();
+        Local.from_local_datetime(&time).earliest().unwrap().with_timezone(&Utc)
+    }
+
+    fn day(date: &str) -> NaiveDate {
+        date.parse().unwrap()
+    }
+
+    fn row(id: i32, person_id: i32, is_inside: IsInside, time: &str) -> Log {
+        Log {
+            id,
+            entity_type: EntityType::Person,
+            entity_id: person_id,
+            event_id: Some(1),
+            is_inside,
+            time: at(time),
+            batch_id: None,
+            snapshot: None,
+            parent_id: None,
+            operator: None,
+        }
+    }
+
+    fn outside(day: NaiveDate, leaves: usize, hours: i64) -> DayOutside {
+        DayOutside {
+            day,
+            leaves,
+            outside: TimeDelta::hours(hours),
+        }
+    }
+
+    #[test]
+    fn outings_over_midnight_count_on_every_day() {
+        let logs = [
+            row(1, 7, IsInside::Out, "2026-07-17 22:00"),
+            row(2, 7, IsInside::In, "2026-07-18 02:00"),
+            row(3, 7, IsInside::Out, "2026-07-18 10:00"),
+            row(4, 7, IsInside::In, "2026-07-18 11:00"),
+            row(5, 7, IsInside::Out, "2026-07-18 23:00"),
+        ];
+        let summary = summarize(&logs, at("2026-07-20 01:00"));
+
+        assert_eq!(summary.len(), 1);
+        let person = &summary[0];
+        assert_eq!((person.person_id, person.leaves(), person.unmatched), (7, 3, 0));
+        assert!(person.is_outside());
+        assert_eq!(person.outings[2], Outing { left: at("2026-07-18 23:00"), returned: None });
+        assert_eq!(person.total, TimeDelta::hours(4 + 1 + 26));
+        assert_eq!(person.longest, TimeDelta::hours(26));
+        assert_eq!(
+            person.days,
+            [
+                outside(day("2026-07-17"), 1, 2),
+                outside(day("2026-07-18"), 2, 2 + 1 + 1),
+                outside(day("2026-07-19"), 0, 24),
+                outside(day("2026-07-20"), 0, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn rows_that_dont_pair_up_are_counted_and_skipped() {
+        let logs = [
+            // The arrival.
+            row(1, 3, IsInside::In, "2026-07-17 08:00"),
+            row(2, 3, IsInside::Out, "2026-07-17 09:00"),
+            // Out twice: the outing keeps its first time.
+            row(3, 3, IsInside::Out, "2026-07-17 09:30"),
+            row(4, 3, IsInside::In, "2026-07-17 10:00"),
+            // In twice.
+            row(5, 3, IsInside::In, "2026-07-17 10:30"),
+        ];
+        let summary = summarize(&logs, at("2026-07-17 12:00"));
+
+        let person = &summary[0];
+        assert_eq!(person.unmatched, 2);
+        assert_eq!(person.outings, [Outing { left: at("2026-07-17 09:00"), returned: Some(at("2026-07-17 10:00")) }]);
+        assert!(!person.is_outside());
+        assert_eq!(person.days, [outside(day("2026-07-17"), 1, 1)]);
+    }
+
+    #[test]
+    fn persons_are_summarized_apart() {
+        let mut named = row(4, 2, IsInside::In, "2026-07-17 12:00");
+        named.snapshot = Some(LogSnapshot {
+            name: "Ola".to_string(),
+            surname: "Nowak".to_string(),
+            rank_level: RankLevel::RankNone,
+            methodology: Methodology::Cub,
+            groups: Vec::new(),
+        });
+        let logs = [
+            row(1, 1, IsInside::In, "2026-07-17 08:00"),
+            row(2, 2, IsInside::Out, "2026-07-17 09:00"),
+            row(3, 2, IsInside::In, "2026-07-17 10:00"),
+            row(5, 2, IsInside::Out, "2026-07-17 11:00"),
+            named,
+        ];
+        let summary = summarize(&logs, at("2026-07-17 13:00"));
+
+        assert_eq!(summary.iter().map(|s| s.person_id).collect::<Vec<_>>(), [1, 2]);
+        assert_eq!((summary[0].leaves(), summary[0].total, summary[0].days.len()), (0, TimeDelta::zero(), 0));
+        assert_eq!((summary[1].leaves(), summary[1].total), (2, TimeDelta::hours(2)));
+        assert_eq!(summary[1].snapshot.as_ref().map(|s| s.name.as_str()), Some("Ola"));
+        assert!(summary[0].snapshot.is_none());
+    }
+
+    #[test]
+    fn an_outing_ending_at_midnight_stays_on_its_day() {
+        let spans = [(at("2026-07-17 23:00"), at("2026-07-18 00:00"))];
+        assert_eq!(split_by_day(&spans), [outside(day("2026-07-17"), 1, 1)]);
+        assert!(split_by_day(&[]).is_empty());
+    }
+}
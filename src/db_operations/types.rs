@@ -1,4 +1,4 @@
-use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, TimeZone, Utc};
 use rusqlite::{
     types::{FromSql, FromSqlError, FromSqlResult, ToSqlOutput, ValueRef},
     ToSql,
@@ -6,6 +6,8 @@ use rusqlite::{
 use serde::{Deserialize, Serialize};
 use slint::Color;
 
+use super::{Incident, InvalidEnumValue};
+
 macro_rules! impl_sql_enum_for {
     ($enum_type:ident{
         $($variant:ident = $value:expr),* $(,)?
@@ -20,9 +22,10 @@ macro_rules! impl_sql_enum_for {
             fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
                 match value.as_i64()? {
                     $($value => Ok($enum_type::$variant),)*
-                    other => Err(FromSqlError::Other(
-                        format!("Invalid value {} for enum {}", other, stringify!($enum_type)).into(),
-                    )),
+                    other => Err(FromSqlError::Other(Box::new(InvalidEnumValue {
+                        enum_name: stringify!($enum_type),
+                        value: other,
+                    }))),
                 }
             }
         }
@@ -59,7 +62,7 @@ impl_sql_enum_for!(RankLevel {
 });
 
 impl core::convert::TryFrom<i32> for RankLevel {
-    type Error = &'static str;
+    type Error = InvalidEnumValue;
 
     fn try_from(value: i32) -> Result<Self, Self::Error> {
         match value {
@@ -74,12 +77,28 @@ impl core::convert::TryFrom<i32> for RankLevel {
             8 => Ok(RankLevel::RankFourthF),
             9 => Ok(RankLevel::RankFifth),
             10 => Ok(RankLevel::RankSixth),
-            _ => Err("invalid RankLevel"),
+            other => Err(InvalidEnumValue {
+                enum_name: "RankLevel",
+                value: other.into(),
+            }),
         }
     }
 }
 
 impl RankLevel {
+    /// 0 for no rank, else 1 to 6; the male and female names of a rank share a level.
+    pub fn level(&self) -> u8 {
+        match self {
+            RankLevel::RankNone => 0,
+            RankLevel::RankFirstM | RankLevel::RankFirstF => 1,
+            RankLevel::RankSecondM | RankLevel::RankSecondF => 2,
+            RankLevel::RankThirdM | RankLevel::RankThirdF => 3,
+            RankLevel::RankFourthM | RankLevel::RankFourthF => 4,
+            RankLevel::RankFifth => 5,
+            RankLevel::RankSixth => 6,
+        }
+    }
+
     pub fn as_str(&self) -> &'static str {
         match self {
             RankLevel::RankNone => "",
@@ -124,7 +143,7 @@ impl Methodology {
 }
 
 impl core::convert::TryFrom<i32> for Methodology {
-    type Error = &'static str;
+    type Error = InvalidEnumValue;
 
     fn try_from(value: i32) -> Result<Self, Self::Error> {
         match value {
@@ -132,7 +151,10 @@ impl core::convert::TryFrom<i32> for Methodology {
             1 => Ok(Methodology::Scout),
             2 => Ok(Methodology::VentureScout),
             3 => Ok(Methodology::Rover),
-            _ => Err("invalid Methodology"),
+            other => Err(InvalidEnumValue {
+                enum_name: "Methodology",
+                value: other.into(),
+            }),
         }
     }
 }
@@ -176,21 +198,119 @@ pub struct Person {
     pub id: i32,
     pub name: String,
     pub surname: String,
+    pub nickname: String,
     pub rank_level: RankLevel,
     pub methodology: Methodology,
-    pub is_inside: IsInside,
+    pub patrol: String,
+    pub date_of_birth: Option<NaiveDate>,
+    /// Set while the person is archived: hidden from lists, history kept.
+    pub archived_at: Option<DateTime<Utc>>,
+    /// Set once the personal data was removed (see `db_operations::anonymize_person`).
+    pub anonymized_at: Option<DateTime<Utc>>,
+    // Sensitive: the UI only shows the fields below on request. Empty means not given.
+    // List queries leave all but `allergies` and `diet` empty; read the single person for them.
+    pub address: String,
+    pub guardians: Vec<Guardian>,
+    pub allergies: String,
+    pub diet: String,
+    pub medical_notes: String,
+    pub note: String,
+}
+
+impl Person {
+    /// A new roster entry with an empty profile; `id` is assigned on insert.
+    pub fn new(name: String, surname: String, rank_level: RankLevel, methodology: Methodology) -> Self {
+        Person {
+            id: 0,
+            name,
+            surname,
+            nickname: String::new(),
+            rank_level,
+            methodology,
+            patrol: String::new(),
+            date_of_birth: None,
+            archived_at: None,
+            anonymized_at: None,
+            address: String::new(),
+            guardians: Vec::new(),
+            allergies: String::new(),
+            diet: String::new(),
+            medical_notes: String::new(),
+            note: String::new(),
+        }
+    }
+}
+
+/// Parent or guardian to contact about a person.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Guardian {
+    pub name: String,
+    /// "mother", "father", "grandmother", ...
+    pub relation: String,
+    pub phone: String,
+    pub email: String,
+}
+
+/// What a group is for. Members of built-in groups follow the roster automatically.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum GroupKind {
+    /// Everyone on the roster ("Camp").
+    AllMembers = 0,
+    /// Everyone with the group's `methodology`.
+    Methodology = 1,
+    /// Created and filled by the user.
+    User = 2,
+}
+
+impl_sql_enum_for!(GroupKind {
+    AllMembers = 0,
+    Methodology = 1,
+    User = 2,
+});
+
+impl GroupKind {
+    /// Built-in groups can't be archived, purged or have members added by hand.
+    pub fn is_built_in(&self) -> bool {
+        *self != GroupKind::User
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Group {
     pub id: i32,
     pub name: String,
+    pub kind: GroupKind,
+    /// Set for `GroupKind::Methodology` groups only.
+    pub methodology: Option<Methodology>,
+    /// Set while the group is archived: hidden from lists, memberships kept.
+    pub archived_at: Option<DateTime<Utc>>,
+}
+
+/// A camp, outing or other event the roster takes part in. Presence and logs are per event.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Event {
+    pub id: i32,
+    pub name: String,
+    pub starts_on: Option<NaiveDate>,
+    pub ends_on: Option<NaiveDate>,
+    pub location: String,
+    /// Days after the event ends before its participants' personal data is due for
+    /// anonymization; `None` falls back to the camp-wide setting.
+    pub retention_days: Option<u32>,
+}
+
+/// A person taking part in an event, with their presence in that event.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Participant {
+    pub person: Person,
+    pub is_inside: IsInside,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GroupWithMembers {
     pub id: i32,
     pub name: String,
+    pub kind: GroupKind,
     pub members: Vec<Person>,
 }
 
@@ -199,8 +319,202 @@ pub struct Log {
     pub id: i32,
     pub(in crate::db_operations) entity_type: EntityType,
     pub entity_id: i32,
+    /// Event the presence change belongs to; `None` only for rows written outside any event.
+    pub event_id: Option<i32>,
     pub is_inside: IsInside,
     pub time: DateTime<Utc>,
+    /// Shared by every row written in one bulk check-in/out; `None` for single writes.
+    pub batch_id: Option<i64>,
+    /// How the person looked when the row was written; `None` for group rows and for rows
+    /// whose person was deleted before snapshots existed.
+    pub snapshot: Option<LogSnapshot>,
+    /// Group event this member row belongs to (see `LogEntry::Group`).
+    pub parent_id: Option<i32>,
+    /// Who recorded the event, if anyone was entered.
+    pub operator: Option<String>,
+}
+
+/// One line on the logs screen: a single person event, or a group event with its members.
+#[derive(Debug, Clone)]
+pub enum LogEntry {
+    Person(Log),
+    Group {
+        log: Log,
+        /// Group name when the event was written.
+        group_name: String,
+        members: Vec<Log>,
+    },
+}
+
+impl LogEntry {
+    pub fn log(&self) -> &Log {
+        match self {
+            LogEntry::Person(log) | LogEntry::Group { log, .. } => log,
+        }
+    }
+}
+
+/// Filters for `query_log`; `None` fields match everything.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LogFilter {
+    pub event_id: Option<i32>,
+    /// Inclusive lower bound.
+    pub from: Option<DateTime<Utc>>,
+    /// Exclusive upper bound.
+    pub to: Option<DateTime<Utc>>,
+    /// The person's own events and group events they were part of.
+    pub person_id: Option<i32>,
+    /// The group's events and events of the persons who are its members *now*: membership
+    /// is checked against `GroupMembers`, not the `groups` snapshot of each row, so a
+    /// person's whole history shows up under a group they joined later and disappears from
+    /// one they left. (The snapshot holds group names, which don't survive a rename.)
+    pub group_id: Option<i32>,
+    pub is_inside: Option<IsInside>,
+    pub operator: Option<String>,
+}
+
+/// Keyset position of the last entry on a page; the next page starts strictly after it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LogCursor {
+    pub time: DateTime<Utc>,
+    pub id: i32,
+}
+
+#[derive(Debug, Clone)]
+pub struct LogPage {
+    pub entries: Vec<LogEntry>,
+    /// `None` on the last page.
+    pub next: Option<LogCursor>,
+}
+
+/// Display data copied into a `Log` row, so later roster edits don't rewrite history.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogSnapshot {
+    pub name: String,
+    pub surname: String,
+    pub rank_level: RankLevel,
+    pub methodology: Methodology,
+    /// User group names (built-in groups excluded).
+    pub groups: Vec<String>,
+}
+
+/// Why someone left the camp, as written on their leave pass.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum LeaveReason {
+    #[default]
+    Other = 0,
+    Shop = 1,
+    Hospital = 2,
+    WentHome = 3,
+    Outing = 4,
+}
+
+impl_sql_enum_for!(LeaveReason {
+    Other = 0,
+    Shop = 1,
+    Hospital = 2,
+    WentHome = 3,
+    Outing = 4,
+});
+
+impl core::convert::TryFrom<i32> for LeaveReason {
+    type Error = InvalidEnumValue;
+
+    fn try_from(value: i32) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(LeaveReason::Other),
+            1 => Ok(LeaveReason::Shop),
+            2 => Ok(LeaveReason::Hospital),
+            3 => Ok(LeaveReason::WentHome),
+            4 => Ok(LeaveReason::Outing),
+            other => Err(InvalidEnumValue {
+                enum_name: "LeaveReason",
+                value: other.into(),
+            }),
+        }
+    }
+}
+
+/// How serious a sentry journal incident is.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    #[default]
+    Info = 0,
+    Minor = 1,
+    Serious = 2,
+    Emergency = 3,
+}
+
+impl_sql_enum_for!(Severity {
+    Info = 0,
+    Minor = 1,
+    Serious = 2,
+    Emergency = 3,
+});
+
+impl core::convert::TryFrom<i32> for Severity {
+    type Error = InvalidEnumValue;
+
+    fn try_from(value: i32) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Severity::Info),
+            1 => Ok(Severity::Minor),
+            2 => Ok(Severity::Serious),
+            3 => Ok(Severity::Emergency),
+            other => Err(InvalidEnumValue {
+                enum_name: "Severity",
+                value: other.into(),
+            }),
+        }
+    }
+}
+
+/// What GET_OUT asks about a departure. Free text fields may be empty.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct LeaveDetails {
+    pub destination: String,
+    pub reason: LeaveReason,
+    /// Adult going along.
+    pub companion: String,
+    pub expected_return: Option<DateTime<Utc>>,
+}
+
+/// Leave pass of one person, tied to the OUT `Log` row that took them out.
+#[derive(Debug, Clone, Serialize)]
+pub struct LeavePass {
+    pub id: i32,
+    /// The OUT row.
+    pub log_id: i32,
+    pub event_id: Option<i32>,
+    pub person_id: i32,
+    pub left: DateTime<Utc>,
+    pub details: LeaveDetails,
+    /// The IN row that closed the pass; `None` while the person is still out.
+    pub return_log_id: Option<i32>,
+    pub returned: Option<DateTime<Utc>>,
+}
+
+/// Everything stored about one person, for a data access request.
+#[derive(Debug, Clone, Serialize)]
+pub struct PersonExport {
+    pub exported_at: DateTime<Utc>,
+    pub person: Person,
+    /// Every group the person belongs to, built-in ones included.
+    pub groups: Vec<String>,
+    pub events: Vec<Participation>,
+    /// The person's own presence rows, oldest first.
+    pub logs: Vec<Log>,
+    /// Leave passes of those rows, oldest first.
+    pub leave_passes: Vec<LeavePass>,
+    /// Sentry journal incidents the person is tagged in, oldest first.
+    pub incidents: Vec<Incident>,
+}
+
+/// An event the person takes part in, with their presence in it.
+#[derive(Debug, Clone, Serialize)]
+pub struct Participation {
+    pub event: Event,
+    pub is_inside: IsInside,
 }
 
 #[allow(dead_code)]
@@ -209,10 +523,51 @@ pub enum DatabaseRecord {
     Group(Group),
     GroupMembers(i32, i32),
     Log(Log),
+    Event(Event),
+    /// (event_id, person_id)
+    EventParticipants(i32, i32),
+}
+
+pub(in crate::db_operations) fn format_db_datetime(time: &DateTime<Utc>) -> String {
+    time.format("%Y-%m-%d %H:%M:%S").to_string()
 }
 
 pub(in crate::db_operations) fn parse_db_datetime(time_str: &str) -> rusqlite::Result<DateTime<Utc>> {
-    let naive = NaiveDateTime::parse_from_str(time_str, "%Y-%m-%d %H:%M:%S")
-        .map_err(|e| rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Text, Box::new(e)))?;
+    let naive = NaiveDateTime::parse_from_str(time_str, "%Y-%m-%d %H:%M:%S").map_err(|e| {
+        rusqlite::Error::FromSqlConversionFailure(
+            0,
+            rusqlite::types::Type::Text,
+            format!("invalid timestamp '{time_str}': {e}").into(),
+        )
+    })?;
     Ok(Utc.from_utc_datetime(&naive))
 }
+
+pub(in crate::db_operations) fn format_db_date(date: &NaiveDate) -> String {
+    date.format("%Y-%m-%d").to_string()
+}
+
+pub(in crate::db_operations) fn parse_db_date(date_str: &str) -> rusqlite::Result<NaiveDate> {
+    NaiveDate::parse_from_str(date_str, "%Y-%m-%d").map_err(|e| {
+        rusqlite::Error::FromSqlConversionFailure(
+            0,
+            rusqlite::types::Type::Text,
+            format!("invalid date '{date_str}': {e}").into(),
+        )
+    })
+}
+
+// `Person.guardians` is stored as a JSON array.
+pub(in crate::db_operations) fn format_guardians(guardians: &[Guardian]) -> String {
+    serde_json::to_string(guardians).expect("guardians always serialize")
+}
+
+pub(in crate::db_operations) fn parse_guardians(json: &str) -> rusqlite::Result<Vec<Guardian>> {
+    serde_json::from_str(json).map_err(|e| {
+        rusqlite::Error::FromSqlConversionFailure(
+            0,
+            rusqlite::types::Type::Text,
+            format!("invalid guardians '{json}': {e}").into(),
+        )
+    })
+}
@@ -1,64 +1,479 @@
-use std::error::Error;
-
+use chrono::{DateTime, Utc};
 use rusqlite::Connection;
 
-use super::{DatabaseRecord, Group, Log, Person};
-use super::IsInside;
+use super::schema::{user_groups_sql, sync_built_in_memberships};
+use super::leave_pass::{close_leave_passes, insert_leave_pass};
+use super::{expect_row, format_db_date, format_db_datetime, format_guardians, DatabaseRecord, DbError, DbResult, Event, Group, Log, Person};
+use super::{IsInside, LeaveDetails};
 
 #[allow(dead_code)]
-pub fn update_db(conn: &Connection, record: DatabaseRecord) -> Result<(), Box<dyn Error>> {
+pub fn update_db(conn: &Connection, record: DatabaseRecord) -> DbResult<()> {
     match record {
         DatabaseRecord::Person(p) => update_person(conn, &p),
         DatabaseRecord::Group(g) => update_group(conn, &g),
         DatabaseRecord::GroupMembers(_gid, _pid) => Ok(()),
         DatabaseRecord::Log(l) => update_log(conn, &l),
+        DatabaseRecord::Event(e) => update_event(conn, &e),
+        DatabaseRecord::EventParticipants(_eid, _pid) => Ok(()),
     }
 }
 
-fn update_person(conn: &Connection, person: &Person) -> Result<(), Box<dyn Error>> {
-    conn.execute(
-        "UPDATE `Person` SET `name` = ?2, `surname` = ?3, `rank_level` = ?4, `methodology` = ?5, `is_inside` = ?6 WHERE `id` = ?1;",
-        (
+/// Saves the whole profile. Also moves the person to the methodology group of their
+/// (possibly new) methodology.
+pub(in crate::db_operations) fn update_person(conn: &Connection, person: &Person) -> DbResult<()> {
+    let tx = conn.unchecked_transaction()?;
+    let updated = tx.execute(
+        "UPDATE `Person` SET `name` = ?2, `surname` = ?3, `rank_level` = ?4, `methodology` = ?5, `nickname` = ?6, `patrol` = ?7,
+            `date_of_birth` = ?8, `address` = ?9, `guardians` = ?10, `allergies` = ?11, `diet` = ?12, `medical_notes` = ?13, `note` = ?14
+         WHERE `id` = ?1;",
+        rusqlite::params![
             &person.id,
             &person.name,
             &person.surname,
             &(person.rank_level as i32),
             &(person.methodology as i32),
-            &person.is_inside,
-        ),
+            &person.nickname,
+            &person.patrol,
+            person.date_of_birth.as_ref().map(format_db_date),
+            &person.address,
+            format_guardians(&person.guardians),
+            &person.allergies,
+            &person.diet,
+            &person.medical_notes,
+            &person.note,
+        ],
     )?;
+    expect_row(updated, "person", person.id)?;
+    sync_built_in_memberships(&tx, Some(person.id))?;
+    tx.commit()?;
     Ok(())
 }
 
-fn update_group(conn: &Connection, group: &Group) -> Result<(), Box<dyn Error>> {
-    conn.execute(
+pub(in crate::db_operations) fn update_group(conn: &Connection, group: &Group) -> DbResult<()> {
+    let updated = conn.execute(
         "UPDATE `Group` SET `name` = ?2 WHERE `id` = ?1;",
         (&group.id, &group.name),
     )?;
-    Ok(())
+    expect_row(updated, "group", group.id)
 }
 
-fn update_log(conn: &Connection, log: &Log) -> Result<(), Box<dyn Error>> {
-    let time_str = log.time.format("%Y-%m-%d %H:%M:%S").to_string();
-    conn.execute(
-        "UPDATE `Log` SET `entity_type` = ?2, `entity_id` = ?3, `is_inside` = ?4, `timestamp` = ?5 WHERE `id` = ?1",
-        (&log.id, &log.entity_type, &log.entity_id, &log.is_inside, &time_str),
+pub(in crate::db_operations) fn update_event(conn: &Connection, event: &Event) -> DbResult<()> {
+    let updated = conn.execute(
+        "UPDATE `Event` SET `name` = ?2, `starts_on` = ?3, `ends_on` = ?4, `location` = ?5, `retention_days` = ?6 WHERE `id` = ?1;",
+        (
+            &event.id,
+            &event.name,
+            event.starts_on.as_ref().map(format_db_date),
+            event.ends_on.as_ref().map(format_db_date),
+            &event.location,
+            event.retention_days,
+        ),
     )?;
-    Ok(())
+    expect_row(updated, "event", event.id)
 }
 
-pub fn set_person_is_inside(conn: &Connection, person_id: i32, is_inside: IsInside) -> Result<(), Box<dyn Error>> {
-    // Persist current state on the Person row
-    conn.execute(
-        "UPDATE `Person` SET `is_inside` = ?2 WHERE `id` = ?1;",
-        (&person_id, &is_inside),
+fn update_log(conn: &Connection, log: &Log) -> DbResult<()> {
+    let time_str = format_db_datetime(&log.time);
+    let snapshot = log.snapshot.as_ref();
+    let updated = conn.execute(
+        "UPDATE `Log` SET `entity_type` = ?2, `entity_id` = ?3, `is_inside` = ?4, `timestamp` = ?5, `batch_id` = ?6,
+            `name` = ?7, `surname` = ?8, `rank_level` = ?9, `methodology` = ?10, `groups` = ?11, `parent_id` = ?12, `operator` = ?13, `event_id` = ?14
+         WHERE `id` = ?1",
+        rusqlite::params![
+            &log.id,
+            &log.entity_type,
+            &log.entity_id,
+            &log.is_inside,
+            &time_str,
+            &log.batch_id,
+            snapshot.map(|s| &s.name),
+            snapshot.map(|s| &s.surname),
+            snapshot.map(|s| s.rank_level),
+            snapshot.map(|s| s.methodology),
+            snapshot.filter(|s| !s.groups.is_empty()).map(|s| s.groups.join("\n")),
+            &log.parent_id,
+            &log.operator,
+            &log.event_id,
+        ],
     )?;
+    expect_row(updated, "log", log.id)
+}
+
+#[allow(dead_code)]
+pub fn set_person_is_inside(conn: &Connection, event_id: i32, person_id: i32, is_inside: IsInside) -> DbResult<()> {
+    set_persons_is_inside(conn, event_id, &[person_id], is_inside, None)?;
+    Ok(())
+}
+
+/// Result of a committed bulk check-in/out.
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+pub struct PresenceBatch {
+    pub event_id: i32,
+    pub batch_id: i64,
+    pub time: DateTime<Utc>,
+    pub person_ids: Vec<i32>,
+    /// Id of the group event row when written by `set_group_is_inside`.
+    pub group_log_id: Option<i32>,
+}
+
+/// Moves every person in `person_ids` in or out of event `event_id` as one transaction.
+///
+/// Every person must be an active participant of the event. All `Log` rows share one
+/// timestamp, one `batch_id` and the `operator`. If any id fails, nothing is written and
+/// the error is `DbError::BatchFailed` listing every failing id.
+pub fn set_persons_is_inside(
+    conn: &Connection,
+    event_id: i32,
+    person_ids: &[i32],
+    is_inside: IsInside,
+    operator: Option<&str>,
+) -> DbResult<PresenceBatch> {
+    write_presence_batch(conn, event_id, None, person_ids, is_inside, operator, None)
+}
+
+/// Like `set_persons_is_inside`, but also records one `EntityType::Group` row for the
+/// whole group and links every member row to it through `parent_id`.
+pub fn set_group_is_inside(
+    conn: &Connection,
+    event_id: i32,
+    group_id: i32,
+    person_ids: &[i32],
+    is_inside: IsInside,
+    operator: Option<&str>,
+) -> DbResult<PresenceBatch> {
+    write_presence_batch(conn, event_id, Some(group_id), person_ids, is_inside, operator, None)
+}
+
+/// Moves `person_ids` out of event `event_id` like `set_persons_is_inside` (or like
+/// `set_group_is_inside` with a `group_id`) and writes a leave pass with `details` for
+/// every OUT row. The passes are closed by the persons' next IN.
+pub fn leave_with_pass(
+    conn: &Connection,
+    event_id: i32,
+    group_id: Option<i32>,
+    person_ids: &[i32],
+    operator: Option<&str>,
+    details: &LeaveDetails,
+) -> DbResult<PresenceBatch> {
+    write_presence_batch(conn, event_id, group_id, person_ids, IsInside::Out, operator, Some(details))
+}
+
+fn write_presence_batch(
+    conn: &Connection,
+    event_id: i32,
+    group_id: Option<i32>,
+    person_ids: &[i32],
+    is_inside: IsInside,
+    operator: Option<&str>,
+    pass: Option<&LeaveDetails>,
+) -> DbResult<PresenceBatch> {
+    let tx = conn.unchecked_transaction()?;
+    // On error, dropping `tx` rolls everything back.
+    let batch = write_presence_rows(&tx, event_id, group_id, person_ids, is_inside, operator, pass)?;
+    tx.commit()?;
+    Ok(batch)
+}
+
+/// The writes of `write_presence_batch`, for callers that already hold a transaction
+/// (e.g. `presence::reconcile_presence`); they must roll back on error.
+///
+/// `pass` is only written for OUT rows. Every IN row closes the person's open passes in the event.
+pub(super) fn write_presence_rows(
+    tx: &Connection,
+    event_id: i32,
+    group_id: Option<i32>,
+    person_ids: &[i32],
+    is_inside: IsInside,
+    operator: Option<&str>,
+    pass: Option<&LeaveDetails>,
+) -> DbResult<PresenceBatch> {
+    let event_exists: bool = tx.query_row("SELECT EXISTS(SELECT 1 FROM `Event` WHERE `id` = ?1);", (event_id,), |row| {
+        row.get(0)
+    })?;
+    if !event_exists {
+        return Err(DbError::NotFound { entity: "event", id: event_id });
+    }
 
-    // Append an audit log row (timestamp defaults to now)
-    conn.execute(
-        "INSERT INTO `Log`(`entity_type`, `entity_id`, `is_inside`) VALUES(0, ?1, ?2);",
-        (&person_id, &is_inside),
+    let time = Utc::now();
+    let time_str = format_db_datetime(&time);
+    let batch_id: i64 = tx.query_row(
+        "SELECT COALESCE(MAX(`batch_id`), 0) + 1 FROM `Log`;",
+        [],
+        |row| row.get(0),
     )?;
 
-    Ok(())
+    // The group row stores the group's name at that moment in `name`.
+    let group_log_id = match group_id {
+        None => None,
+        Some(group_id) => {
+            let inserted = tx.execute(
+                "INSERT INTO `Log`(`entity_type`, `entity_id`, `is_inside`, `timestamp`, `batch_id`, `operator`, `event_id`, `name`)
+                 SELECT 1, `g`.`id`, ?2, ?3, ?4, ?5, ?6, `g`.`name` FROM `Group` `g` WHERE `g`.`id` = ?1 AND `g`.`archived_at` IS NULL;",
+                (&group_id, &is_inside, &time_str, &batch_id, &operator, &event_id),
+            )?;
+            expect_row(inserted, "group", group_id)?;
+            Some(tx.last_insert_rowid() as i32)
+        }
+    };
+
+    let mut failures: Vec<(i32, DbError)> = Vec::new();
+    {
+        // Archived persons and non-participants can't be moved; they count as missing.
+        let mut update_stmt = tx.prepare(
+            "UPDATE `EventParticipants` SET `is_inside` = ?3
+             WHERE `event_id` = ?1 AND `person_id` = ?2
+               AND `person_id` IN (SELECT `id` FROM `Person` WHERE `archived_at` IS NULL);",
+        )?;
+        // Copies the person's current display data into the row (see `LogSnapshot`).
+        let mut log_stmt = tx.prepare(&format!(
+            "INSERT INTO `Log`(`entity_type`, `entity_id`, `is_inside`, `timestamp`, `batch_id`, `parent_id`, `operator`, `event_id`, `name`, `surname`, `rank_level`, `methodology`, `groups`)
+             SELECT 0, `p`.`id`, ?2, ?3, ?4, ?5, ?6, ?7, `p`.`name`, `p`.`surname`, `p`.`rank_level`, `p`.`methodology`, ({})
+             FROM `Person` `p` WHERE `p`.`id` = ?1;",
+            user_groups_sql("`p`.`id`")
+        ))?;
+
+        for &person_id in person_ids {
+            let written = update_stmt
+                .execute((&event_id, &person_id, &is_inside))
+                .map_err(DbError::from)
+                .and_then(|updated| expect_row(updated, "participant", person_id))
+                .and_then(|()| {
+                    log_stmt.execute((&person_id, &is_inside, &time_str, &batch_id, &group_log_id, &operator, &event_id))?;
+                    let log_id = tx.last_insert_rowid() as i32;
+                    match (is_inside, pass) {
+                        (IsInside::Out, Some(pass)) => insert_leave_pass(tx, log_id, pass),
+                        (IsInside::In, _) => close_leave_passes(tx, event_id, person_id, log_id),
+                        (IsInside::Out, None) => Ok(()),
+                    }
+                });
+
+            if let Err(e) = written {
+                failures.push((person_id, e));
+            }
+        }
+    }
+
+    if !failures.is_empty() {
+        return Err(DbError::BatchFailed(failures));
+    }
+
+    Ok(PresenceBatch {
+        event_id,
+        batch_id,
+        time,
+        person_ids: person_ids.to_vec(),
+        group_log_id,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use chrono::{TimeDelta, Timelike, Utc};
+
+    use super::{leave_with_pass, set_group_is_inside, set_persons_is_inside};
+    use crate::db_operations::leave_pass::{open_leave_passes, person_leave_passes};
+    use crate::db_operations::test_support::{memory_db, person};
+    use crate::db_operations::{
+        DbError, Event, EventRepository, Group, GroupKind, GroupRepository, IsInside, LeaveDetails, LeaveReason, Methodology, PersonRepository,
+        PresenceLogRepository, SqliteRepository,
+    };
+
+    const EVENT_ID: i32 = 1;
+
+    fn camp() -> (Rc<RefCell<rusqlite::Connection>>, i32, i32) {
+        let conn = Rc::new(RefCell::new(memory_db()));
+        let repo = SqliteRepository::new(conn.clone());
+        let a = repo.add_person(&person("Jan", "Kowalski", Methodology::Scout)).unwrap();
+        let b = repo.add_person(&person("Ola", "Nowak", Methodology::Cub)).unwrap();
+        repo.add_participant(EVENT_ID, a).unwrap();
+        repo.add_participant(EVENT_ID, b).unwrap();
+        (conn, a, b)
+    }
+
+    fn count(conn: &rusqlite::Connection, table: &str) -> i32 {
+        conn.query_row(&format!("SELECT COUNT(*) FROM `{table}`;"), [], |row| row.get(0)).unwrap()
+    }
+
+    fn inside_ids(conn: &rusqlite::Connection) -> Vec<i32> {
+        let mut stmt = conn
+            .prepare("SELECT `person_id` FROM `EventParticipants` WHERE `is_inside` = 1 ORDER BY `person_id`;")
+            .unwrap();
+        stmt.query_map([], |row| row.get(0)).unwrap().collect::<rusqlite::Result<_>>().unwrap()
+    }
+
+    #[test]
+    fn failing_row_rolls_back_the_whole_batch() {
+        let (conn, a, b) = camp();
+        let conn = conn.borrow();
+        let logs_before = count(&conn, "Log");
+
+        // `a` is written before the unknown id fails; it must not stay behind.
+        match set_persons_is_inside(&conn, EVENT_ID, &[a, 999, b], IsInside::In, Some("op")) {
+            Err(DbError::BatchFailed(failures)) => {
+                assert_eq!(failures.len(), 1);
+                assert_eq!(failures[0].0, 999);
+                assert!(matches!(failures[0].1, DbError::NotFound { entity: "participant", id: 999 }));
+            }
+            other => panic!("expected BatchFailed, got {other:?}"),
+        }
+        assert_eq!(count(&conn, "Log"), logs_before);
+        assert!(inside_ids(&conn).is_empty());
+
+        let batch = set_persons_is_inside(&conn, EVENT_ID, &[a, b], IsInside::In, Some("op")).unwrap();
+        assert_eq!(count(&conn, "Log"), logs_before + 2);
+        assert_eq!(inside_ids(&conn), [a, b]);
+        let shared: i32 = conn
+            .query_row(
+                "SELECT COUNT(DISTINCT `timestamp`) FROM `Log` WHERE `batch_id` = ?1;",
+                (batch.batch_id,),
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(shared, 1);
+    }
+
+    #[test]
+    fn failing_row_rolls_back_leave_passes() {
+        let (conn, a, b) = camp();
+        let conn = conn.borrow();
+        set_persons_is_inside(&conn, EVENT_ID, &[a, b], IsInside::In, None).unwrap();
+        let logs_before = count(&conn, "Log");
+
+        let details = LeaveDetails {
+            destination: "shop".to_string(),
+            reason: LeaveReason::Shop,
+            companion: String::new(),
+            expected_return: Some(Utc::now()),
+        };
+        let result = leave_with_pass(&conn, EVENT_ID, None, &[a, b, 999], None, &details);
+        assert!(matches!(result, Err(DbError::BatchFailed(_))));
+        assert_eq!(count(&conn, "Log"), logs_before);
+        assert_eq!(count(&conn, "LeavePass"), 0);
+        assert_eq!(inside_ids(&conn), [a, b]);
+    }
+
+    #[test]
+    fn leave_pass_opens_on_out_and_closes_on_next_in() {
+        let (conn, a, b) = camp();
+        let conn = conn.borrow();
+        set_persons_is_inside(&conn, EVENT_ID, &[a, b], IsInside::In, None).unwrap();
+
+        // Due back an hour ago (whole seconds, as stored): still out past it keeps the pass open.
+        let details = LeaveDetails {
+            destination: "Hospital".to_string(),
+            reason: LeaveReason::Hospital,
+            companion: "Leader".to_string(),
+            expected_return: Utc::now().with_nanosecond(0).map(|now| now - TimeDelta::hours(1)),
+        };
+        let out = leave_with_pass(&conn, EVENT_ID, None, &[a], Some("op"), &details).unwrap();
+        set_persons_is_inside(&conn, EVENT_ID, &[b], IsInside::Out, None).unwrap();
+        let open = open_leave_passes(&conn, EVENT_ID).unwrap();
+        assert_eq!(open.len(), 1, "an OUT without details writes no pass");
+        let pass = &open[0];
+        let out_row: i32 = conn
+            .query_row("SELECT `id` FROM `Log` WHERE `batch_id` = ?1;", (out.batch_id,), |row| row.get(0))
+            .unwrap();
+        assert_eq!((pass.person_id, pass.log_id, &pass.details), (a, out_row, &details));
+        assert!(pass.details.expected_return.unwrap() < Utc::now());
+        assert_eq!((pass.return_log_id, pass.returned), (None, None));
+        assert_eq!(inside_ids(&conn), Vec::<i32>::new());
+
+        // Only `a`'s next IN closes it, with that row.
+        set_persons_is_inside(&conn, EVENT_ID, &[b], IsInside::In, None).unwrap();
+        assert_eq!(open_leave_passes(&conn, EVENT_ID).unwrap().len(), 1);
+        let back = set_persons_is_inside(&conn, EVENT_ID, &[a], IsInside::In, None).unwrap();
+        let in_row: i32 = conn
+            .query_row("SELECT `id` FROM `Log` WHERE `batch_id` = ?1;", (back.batch_id,), |row| row.get(0))
+            .unwrap();
+        assert!(open_leave_passes(&conn, EVENT_ID).unwrap().is_empty());
+        let closed = person_leave_passes(&conn, a).unwrap();
+        assert_eq!(closed.len(), 1);
+        assert_eq!(closed[0].return_log_id, Some(in_row));
+        assert!(closed[0].returned.is_some_and(|t| t >= closed[0].left));
+    }
+
+    #[test]
+    fn group_event_links_every_member_row() {
+        let (conn, a, b) = camp();
+        let repo = SqliteRepository::new(conn.clone());
+        let scouts = Group {
+            id: 0,
+            name: "Scouts".to_string(),
+            kind: GroupKind::User,
+            methodology: None,
+            archived_at: None,
+        };
+        let scouts = repo.add_group(&scouts).unwrap();
+        let conn = conn.borrow();
+
+        let batch = set_group_is_inside(&conn, EVENT_ID, scouts, &[a, b], IsInside::In, Some("op")).unwrap();
+        let group_log = batch.group_log_id.expect("group row");
+        let (entity_type, entity_id, name, parent): (i32, i32, String, Option<i32>) = conn
+            .query_row(
+                "SELECT `entity_type`, `entity_id`, `name`, `parent_id` FROM `Log` WHERE `id` = ?1;",
+                (group_log,),
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+            )
+            .unwrap();
+        assert_eq!((entity_type, entity_id, name.as_str(), parent), (1, scouts, "Scouts", None));
+        let mut stmt = conn
+            .prepare("SELECT `entity_id`, `batch_id`, `operator` FROM `Log` WHERE `parent_id` = ?1 ORDER BY `entity_id`;")
+            .unwrap();
+        let members: Vec<(i32, i64, Option<String>)> = stmt
+            .query_map((group_log,), |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        let op = Some("op".to_string());
+        assert_eq!(members, [(a, batch.batch_id, op.clone()), (b, batch.batch_id, op)]);
+        assert_eq!(inside_ids(&conn), [a, b]);
+
+        // A failing member takes the group row down with it.
+        let logs_before = count(&conn, "Log");
+        let result = set_group_is_inside(&conn, EVENT_ID, scouts, &[a, 999], IsInside::Out, None);
+        assert!(matches!(result, Err(DbError::BatchFailed(_))));
+        assert_eq!(count(&conn, "Log"), logs_before);
+        assert!(matches!(
+            set_group_is_inside(&conn, EVENT_ID, 999, &[a], IsInside::Out, None),
+            Err(DbError::NotFound { entity: "group", id: 999 })
+        ));
+        assert_eq!(inside_ids(&conn), [a, b]);
+    }
+
+    #[test]
+    fn presence_is_per_event() {
+        let (conn, a, b) = camp();
+        let repo = SqliteRepository::new(conn.clone());
+        let outing = Event {
+            id: 0,
+            name: "Outing".to_string(),
+            starts_on: None,
+            ends_on: None,
+            location: String::new(),
+            retention_days: None,
+        };
+        let outing = repo.add_event(&outing).unwrap();
+        repo.add_participant(outing, a).unwrap();
+
+        set_persons_is_inside(&conn.borrow(), outing, &[a], IsInside::In, None).unwrap();
+        let inside = |event_id| -> Vec<(i32, IsInside)> {
+            repo.participants(event_id).unwrap().iter().map(|p| (p.person.id, p.is_inside)).collect()
+        };
+        assert_eq!(inside(outing), [(a, IsInside::In)]);
+        assert_eq!(inside(EVENT_ID), [(a, IsInside::Out), (b, IsInside::Out)]);
+        let logged: Vec<Option<i32>> = repo.person_logs().unwrap().iter().map(|l| l.event_id).collect();
+        assert_eq!(logged, [Some(outing)]);
+
+        // b doesn't take part in the outing, so the whole batch fails there.
+        let result = set_persons_is_inside(&conn.borrow(), outing, &[a, b], IsInside::Out, None);
+        assert!(matches!(result, Err(DbError::BatchFailed(failures)) if failures.len() == 1 && failures[0].0 == b));
+        assert!(matches!(
+            set_persons_is_inside(&conn.borrow(), 99, &[a], IsInside::In, None),
+            Err(DbError::NotFound { entity: "event", id: 99 })
+        ));
+        assert_eq!(inside(outing), [(a, IsInside::In)]);
+    }
 }
@@ -2,25 +2,94 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 use std::error::Error;
+use std::path::PathBuf;
 use std::rc::Rc;
 use std::cell::RefCell;
 use rusqlite::Connection;
 
+use app_controller::OpenedCamp;
+use settings::Settings;
+
 mod db_operations;
 mod app_controller;
+mod settings;
 
 slint::include_modules!();
 
 fn main() -> Result<(), Box<dyn Error>> {
-    let conn: Rc<RefCell<Connection>> = db_operations::get_db()?;
+    let mut settings = Settings::load();
 
     let app = MainWindow::new()?;
 
     slint::select_bundled_translation("en")?;
 
-    app_controller::install(&app, conn.clone());
+    let Some(camp) = open_camp_db(&mut settings)? else {
+        // Startup chooser closed without picking a camp.
+        return Ok(());
+    };
+    let conn: Rc<RefCell<Connection>> = Rc::new(RefCell::new(camp.conn));
+
+    app_controller::install(&app, conn.clone(), settings, camp.path, camp.passphrase);
 
     app.run()?;
 
     Ok(())
 }
+
+// Camp database: CLI / environment, then the default camp, then ask (see `settings`).
+// Encrypted camps always go through the chooser, which asks for the passphrase, and so does
+// a camp that fails to open, with the error shown.
+fn open_camp_db(settings: &mut Settings) -> Result<Option<OpenedCamp>, Box<dyn Error>> {
+    let mut open_error = None;
+    let mut locked = None;
+
+    if let Some(path) = settings::db_path_override() {
+        if db_operations::is_encrypted(&path) {
+            locked = Some(path);
+        } else {
+            match open_plain_camp(settings, path, "camp") {
+                Ok(camp) => return Ok(Some(camp)),
+                Err(e) => open_error = Some(e),
+            }
+        }
+    }
+
+    // Not after the camp asked for on the command line failed: the user meant that one.
+    if locked.is_none()
+        && open_error.is_none()
+        && let Some(path) = settings.default_db.clone()
+    {
+        if db_operations::is_encrypted(&path) {
+            locked = Some(path);
+        } else {
+            match open_plain_camp(settings, path, "default camp") {
+                Ok(camp) => return Ok(Some(camp)),
+                Err(e) => open_error = Some(e),
+            }
+        }
+    }
+
+    Ok(app_controller::choose_camp(settings, open_error, locked)?)
+}
+
+// The message for the chooser on error; `what` names the camp in it.
+fn open_plain_camp(settings: &mut Settings, path: PathBuf, what: &str) -> Result<OpenedCamp, String> {
+    match db_operations::open_db(&path, None) {
+        Ok(conn) => {
+            remember_camp(settings, &path);
+            Ok(OpenedCamp { path, conn, passphrase: None })
+        }
+        Err(e) => {
+            eprintln!("Couldn't open {what} {}: {e}", path.display());
+            Err(format!("Couldn't open the {what} {}: {e}", path.display()))
+        }
+    }
+}
+
+fn remember_camp(settings: &mut Settings, path: &std::path::Path) {
+    let name = settings.profile_name(path);
+    settings.touch_recent(&name, path);
+    if let Err(e) = settings.save() {
+        eprintln!("Couldn't save settings: {e}");
+    }
+}
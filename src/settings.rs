@@ -0,0 +1,153 @@
+//! App settings and camp profiles.
+//!
+//! Every camp (summer camp, winter camp, weekend event...) gets its own database file.
+//! A "camp profile" is just a name attached to such a file. Known profiles are kept in
+//! `settings.json` in the user's config directory, most recently opened first.
+//!
+//! Which database is opened at startup, first match wins:
+//! 1. `--db <path>` on the command line,
+//! 2. the `EWARTOWNIK_DB` environment variable,
+//! 3. `default_db` in `settings.json`,
+//! 4. otherwise the user picks a profile in the startup chooser.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Environment variable with the database path (see module docs).
+pub const DB_ENV_VAR: &str = "EWARTOWNIK_DB";
+
+/// Recent profiles kept in the list.
+const MAX_RECENT: usize = 10;
+
+/// Defaults for the backup fields of `Settings`.
+const DEFAULT_BACKUP_INTERVAL_MINUTES: u32 = 15;
+const DEFAULT_BACKUPS_KEPT: usize = 20;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CampProfile {
+    pub name: String,
+    pub path: PathBuf,
+    /// `None` for a database found on disk but never opened through a profile.
+    pub last_opened: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// Opened at startup without showing the chooser.
+    pub default_db: Option<PathBuf>,
+    /// Most recently opened first.
+    pub recent: Vec<CampProfile>,
+    /// Minutes between automatic backups of the open camp; 0 = only on opening and before
+    /// deleting data.
+    pub backup_interval_minutes: u32,
+    /// Backups kept per camp and reason; older ones are deleted after each new one.
+    pub backups_kept: usize,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            default_db: None,
+            recent: Vec::new(),
+            backup_interval_minutes: DEFAULT_BACKUP_INTERVAL_MINUTES,
+            backups_kept: DEFAULT_BACKUPS_KEPT,
+        }
+    }
+}
+
+impl Settings {
+    /// Reads `settings.json`; a missing or unreadable file gives the defaults.
+    pub fn load() -> Settings {
+        let path = settings_path();
+        match fs::read_to_string(&path) {
+            Ok(text) => serde_json::from_str(&text).unwrap_or_else(|e| {
+                eprintln!("Ignoring invalid settings file {}: {e}", path.display());
+                Settings::default()
+            }),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Settings::default(),
+            Err(e) => {
+                eprintln!("Couldn't read settings file {}: {e}", path.display());
+                Settings::default()
+            }
+        }
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let path = settings_path();
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let text = serde_json::to_string_pretty(self).map_err(io::Error::other)?;
+        fs::write(path, text)
+    }
+
+    /// Moves `path` to the top of the recent list, adding it under `name` if new.
+    pub fn touch_recent(&mut self, name: &str, path: &Path) {
+        self.recent.retain(|p| p.path != path);
+        self.recent.insert(
+            0,
+            CampProfile {
+                name: name.to_string(),
+                path: path.to_path_buf(),
+                last_opened: Some(Utc::now()),
+            },
+        );
+        self.recent.truncate(MAX_RECENT);
+    }
+
+    /// Profile name for `path`: the known one, or the file name.
+    pub fn profile_name(&self, path: &Path) -> String {
+        self.recent
+            .iter()
+            .find(|p| p.path == path)
+            .map(|p| p.name.clone())
+            .unwrap_or_else(|| {
+                path.file_stem()
+                    .map(|s| s.to_string_lossy().into_owned())
+                    .unwrap_or_else(|| path.display().to_string())
+            })
+    }
+}
+
+/// Database given on the command line or in the environment (steps 1-2 in the module docs).
+pub fn db_path_override() -> Option<PathBuf> {
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--db" {
+            return args.next().map(PathBuf::from);
+        }
+        if let Some(path) = arg.strip_prefix("--db=") {
+            return Some(PathBuf::from(path));
+        }
+    }
+
+    std::env::var_os(DB_ENV_VAR).filter(|v| !v.is_empty()).map(PathBuf::from)
+}
+
+/// File for a new profile called `name`, inside the app's data directory.
+/// `None` when the name has no usable characters.
+pub fn new_profile_path(name: &str) -> Option<PathBuf> {
+    let slug: String = name
+        .trim()
+        .chars()
+        .map(|c| if c.is_alphanumeric() || c == '-' || c == '_' { c } else { '-' })
+        .flat_map(char::to_lowercase)
+        .collect();
+    let slug = slug.trim_matches('-');
+    if slug.is_empty() {
+        return None;
+    }
+    Some(crate::db_operations::data_dir().join("camps").join(format!("{slug}.db")))
+}
+
+fn settings_path() -> PathBuf {
+    dirs::config_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("eWartownik")
+        .join("settings.json")
+}